@@ -0,0 +1,296 @@
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    rc::{Rc, Weak},
+};
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{BinaryType, CloseEvent, Document, EventTarget, MessageEvent, WebSocket};
+
+use crate::core::{should_reconnect, ReconnectPolicy, HEARTBEAT_INTERVAL_MS};
+
+pub(crate) fn log(message: &str) {
+    web_sys::console::log_1(&message.into());
+}
+
+// Implemented by the connections of the integrations without seed, which
+// receive the events of their `ReconnectingWebSocket`.
+pub(crate) trait SocketEvents: 'static {
+    fn socket(&mut self) -> &mut ReconnectingWebSocket;
+
+    fn opened(&mut self);
+
+    fn received(&mut self, bytes: Vec<u8>);
+
+    fn closed(&mut self, _reason: String) {}
+
+    // The given attempt to reconnect is scheduled.
+    fn reconnecting(&mut self, _attempt: usize) {}
+
+    // Called periodically while a web socket is open. Returns whether the
+    // connection is dead and has to be replaced.
+    fn heartbeat(&mut self) -> bool {
+        false
+    }
+
+    // The page was hidden or is visible again.
+    fn visibility_changed(&mut self, _hidden: bool) {}
+}
+
+// A web socket that reconnects with backoff when the connection is lost.
+pub(crate) struct ReconnectingWebSocket {
+    ws_path: String,
+    web_socket: Option<WebSocket>,
+    // Keeps the callbacks of the current web socket alive.
+    handlers: Vec<Closure<dyn FnMut(JsValue)>>,
+    // The interval that calls `SocketEvents::heartbeat`.
+    heartbeat: Option<i32>,
+    // Calls `SocketEvents::visibility_changed`, set when the web socket is
+    // opened the first time.
+    on_visibility_change: Option<Closure<dyn FnMut(JsValue)>>,
+    retries: usize,
+    closed: bool,
+    policy: ReconnectPolicy,
+    on_give_up: Option<Rc<dyn Fn()>>,
+}
+
+impl ReconnectingWebSocket {
+    pub(crate) fn new(ws_path: String) -> Self {
+        ReconnectingWebSocket {
+            ws_path,
+            web_socket: None,
+            handlers: Vec::new(),
+            heartbeat: None,
+            on_visibility_change: None,
+            retries: 0,
+            closed: false,
+            policy: ReconnectPolicy::default(),
+            on_give_up: None,
+        }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: ReconnectPolicy) {
+        self.policy = policy;
+    }
+
+    pub(crate) fn set_on_give_up(&mut self, on_give_up: impl Fn() + 'static) {
+        self.on_give_up = Some(Rc::new(on_give_up));
+    }
+
+    pub(crate) fn send(&self, bytes: &[u8]) -> Result<(), JsValue> {
+        match &self.web_socket {
+            Some(web_socket) => web_socket.send_with_u8_array(bytes),
+            None => Err("WebSocket isn't open".into()),
+        }
+    }
+
+    pub(crate) fn open<T: SocketEvents>(this: &Rc<RefCell<T>>) {
+        let web_socket = {
+            let mut events = this.borrow_mut();
+            WebSocket::new(&events.socket().ws_path)
+        };
+        let web_socket = match web_socket {
+            Ok(web_socket) => web_socket,
+            Err(err) => {
+                let reason = format!("couldn't build WebSocket: {err:?}");
+                log(&reason);
+                this.borrow_mut().closed(reason);
+                Self::schedule_reconnect(&Rc::downgrade(this));
+                return;
+            }
+        };
+        web_socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let weak = Rc::downgrade(this);
+        let on_open = Closure::<dyn FnMut(JsValue)>::new(move |_| {
+            if let Some(this) = weak.upgrade() {
+                let mut events = this.borrow_mut();
+                events.socket().retries = 0;
+                log("WebSocket connection is open now");
+                events.opened();
+            }
+        });
+        let weak = Rc::downgrade(this);
+        let on_message = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            let event: MessageEvent = event.unchecked_into();
+            if let Ok(buffer) = event.data().dyn_into::<ArrayBuffer>() {
+                if let Some(this) = weak.upgrade() {
+                    this.borrow_mut()
+                        .received(Uint8Array::new(&buffer).to_vec());
+                }
+            }
+        });
+        let weak = Rc::downgrade(this);
+        let on_close = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            let event: CloseEvent = event.unchecked_into();
+            log(&format!(
+                "WebSocket connection was closed, reason: {}",
+                event.reason()
+            ));
+            if let Some(this) = weak.upgrade() {
+                this.borrow_mut().closed(event.reason());
+            }
+            if should_reconnect(event.was_clean(), event.code()) {
+                Self::schedule_reconnect(&weak);
+            }
+        });
+
+        let weak = Rc::downgrade(this);
+        let on_heartbeat = Closure::<dyn FnMut(JsValue)>::new(move |_| {
+            let Some(this) = weak.upgrade() else {
+                return;
+            };
+            let dead = this.borrow_mut().heartbeat();
+            if dead {
+                log("server doesn't answer pings, reconnecting");
+                this.borrow_mut().socket().abandon();
+                Self::schedule_reconnect(&weak);
+            }
+        });
+
+        web_socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        web_socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        web_socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        let mut events = this.borrow_mut();
+        let socket = events.socket();
+        if socket.on_visibility_change.is_none() {
+            socket.on_visibility_change = Some(Self::watch_visibility(this));
+        }
+        socket.stop_heartbeat();
+        socket.heartbeat = web_sys::window()
+            .unwrap()
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                on_heartbeat.as_ref().unchecked_ref(),
+                i32::try_from(HEARTBEAT_INTERVAL_MS).unwrap_or(i32::MAX),
+            )
+            .ok();
+        socket.web_socket = Some(web_socket);
+        socket.handlers = vec![on_open, on_message, on_close, on_heartbeat];
+    }
+
+    fn watch_visibility<T: SocketEvents>(this: &Rc<RefCell<T>>) -> Closure<dyn FnMut(JsValue)> {
+        let weak = Rc::downgrade(this);
+        let on_visibility_change = Closure::<dyn FnMut(JsValue)>::new(move |_| {
+            let hidden = document().hidden();
+            if let Some(this) = weak.upgrade() {
+                this.borrow_mut().visibility_changed(hidden);
+            }
+        });
+        let document = document();
+        let target: &EventTarget = document.as_ref();
+        target
+            .add_event_listener_with_callback(
+                "visibilitychange",
+                on_visibility_change.as_ref().unchecked_ref(),
+            )
+            .ok();
+        on_visibility_change
+    }
+
+    fn stop_heartbeat(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            web_sys::window()
+                .unwrap()
+                .clear_interval_with_handle(heartbeat);
+        }
+    }
+
+    // Closes a dead web socket without waiting for the closing handshake.
+    // The handlers are kept, as this is called by one of them, and replaced
+    // once the connection is opened again.
+    fn abandon(&mut self) {
+        self.stop_heartbeat();
+        if let Some(web_socket) = self.web_socket.take() {
+            web_socket.set_onopen(None);
+            web_socket.set_onmessage(None);
+            web_socket.set_onclose(None);
+            web_socket.close().ok();
+        }
+    }
+
+    fn schedule_reconnect<T: SocketEvents>(weak: &Weak<RefCell<T>>) {
+        let Some(this) = weak.upgrade() else {
+            return;
+        };
+        let (retries, delay) = {
+            let mut events = this.borrow_mut();
+            let socket = events.socket();
+            if socket.closed {
+                return;
+            }
+            socket.retries += 1;
+            let retries = socket.retries;
+            let delay = socket.policy.delay(retries, js_sys::Math::random());
+            if delay.is_some() {
+                events.reconnecting(retries);
+            }
+            (retries, delay)
+        };
+        let Some(delay) = delay else {
+            let reason = format!("gave up reconnecting after {} attempts", retries - 1);
+            log(&reason);
+            let on_give_up = {
+                let mut events = this.borrow_mut();
+                events.closed(reason);
+                events.socket().on_give_up.clone()
+            };
+            // Called without a borrow, as it may use the connection.
+            if let Some(on_give_up) = on_give_up {
+                on_give_up();
+            }
+            return;
+        };
+
+        let weak = weak.clone();
+        let reconnect = Closure::once_into_js(move || {
+            if let Some(this) = weak.upgrade() {
+                log(&format!("Reconnect attempt: {retries}"));
+                Self::open(&this);
+            }
+        });
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                reconnect.unchecked_ref(),
+                i32::try_from(delay.as_millis()).unwrap_or(i32::MAX),
+            )
+            .unwrap();
+    }
+
+    // Closes the connection so that it's reconnected like a lost one.
+    pub(crate) fn restart(&self, reason: &str) {
+        if let Some(web_socket) = &self.web_socket {
+            web_socket.close_with_code_and_reason(4000, reason).ok();
+        }
+    }
+
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.stop_heartbeat();
+        if let Some(on_visibility_change) = self.on_visibility_change.take() {
+            let document = document();
+            let target: &EventTarget = document.as_ref();
+            target
+                .remove_event_listener_with_callback(
+                    "visibilitychange",
+                    on_visibility_change.as_ref().unchecked_ref(),
+                )
+                .ok();
+        }
+        if let Some(web_socket) = self.web_socket.take() {
+            web_socket.set_onclose(None);
+            web_socket.close().ok();
+        }
+        self.handlers.clear();
+    }
+}
+
+fn document() -> Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+pub(crate) fn reload() {
+    web_sys::window().unwrap().location().reload().unwrap();
+}
@@ -0,0 +1,1436 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use engine_shared::{
+    Checksum, ClientEvent, Event, EventData, Req, Res, SchemaVersion, Seed, ServerEvent, State,
+    StateWrapper, SyncData,
+};
+use i18n::Locale;
+use serde::Serialize;
+
+#[cfg(debug_assertions)]
+const MAX_LOGGED_DIVERGENCES: usize = 32;
+const MAX_QUEUED_EVENTS: usize = 256;
+const MAX_BUFFERED_EVENTS: usize = 256;
+const MAX_UNDO_EVENTS: usize = 32;
+const DEFAULT_FEED_LEN: usize = 64;
+#[cfg(feature = "devtools")]
+const MAX_RECENT_EVENTS: usize = 32;
+const PING_INTERVAL_MS: f64 = 5000.0;
+// Proxies can keep a socket open long after the server became unreachable,
+// so a ping that isn't answered in time means the connection is dead.
+const PONG_TIMEOUT_MS: f64 = 10_000.0;
+#[cfg(any(
+    feature = "seed",
+    feature = "yew",
+    feature = "leptos",
+    feature = "worker"
+))]
+pub(crate) const HEARTBEAT_INTERVAL_MS: u32 = 2500;
+// Predicted ticks that the server hasn't confirmed yet. If the server falls
+// further behind, the prediction waits for it.
+const MAX_PREDICTED_TICKS: usize = 2;
+// After the page was hidden for longer, the events that piled up meanwhile
+// aren't applied one by one, the state is synced instead.
+const MAX_HIDDEN_MS: f64 = 60_000.0;
+// The server's seed is unknown until it confirms an event, so predictions
+// that depend on randomness are corrected then.
+const PREDICTION_SEED: Seed = [0; 32];
+
+// The server runs another version of the game, e.g. because it was
+// redeployed with a changed state, so the client has to be reloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outdated {
+    pub client: SchemaVersion,
+    pub server: SchemaVersion,
+}
+
+impl fmt::Display for Outdated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client schema version {} is older than server schema version {}",
+            self.client, self.server
+        )
+    }
+}
+
+impl std::error::Error for Outdated {}
+
+// The state of the connection, e.g. to show a banner while the client is
+// offline.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConnectionStatus {
+    Connecting,
+    // The connection is open and the client waits for the state.
+    Open,
+    // The state became invalid and the client waits for the server to
+    // resend it.
+    Syncing,
+    Live,
+    Reconnecting { attempt: usize },
+    Closed { reason: String },
+    // The state kept diverging from the server even after reconnecting, e.g.
+    // because the client runs another version of the game's logic. It stays
+    // out of date until the page is reloaded.
+    OutOfDate,
+}
+
+// How the browser clients reconnect after the connection was lost. The delay
+// doubles with every attempt, starting at `initial_delay`, and a random part
+// of up to `jitter` is added so that clients don't reconnect all at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    // Gives up after this many attempts, or never if `None`.
+    pub max_retries: Option<usize>,
+    pub jitter: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    // The same truncated exponential backoff as seed's `streams::backoff`.
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(32),
+            max_retries: None,
+            jitter: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // The delay before the given attempt, counted from 1, or `None` if the
+    // client should give up. `random` is between 0 and 1.
+    pub fn delay(&self, retries: usize, random: f64) -> Option<Duration> {
+        use std::convert::TryFrom;
+
+        if self
+            .max_retries
+            .is_some_and(|max_retries| retries > max_retries)
+        {
+            return None;
+        }
+        let exponent = u32::try_from(retries.saturating_sub(1)).unwrap_or(u32::MAX);
+        let delay = self
+            .initial_delay
+            .saturating_mul(2_u32.saturating_pow(exponent))
+            .saturating_add(self.jitter.mul_f64(random.clamp(0.0, 1.0)));
+        Some(delay.min(self.max_delay))
+    }
+}
+
+// What the client does when received events don't apply to its state. They
+// are buffered and retried first, and a sync is requested once
+// `max_buffered` events failed. Syncs are at least `debounce` apart, which
+// doubles with every sync that didn't help up to `max_debounce`. After
+// `max_resyncs` syncs, the client reconnects, and after `max_reconnects`
+// reconnects, it's `ConnectionStatus::OutOfDate`. Syncs and reconnects only
+// count as helping once the state stayed valid for `stable_after`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResyncPolicy {
+    pub max_buffered: usize,
+    pub debounce: Duration,
+    pub max_debounce: Duration,
+    pub max_resyncs: usize,
+    pub max_reconnects: usize,
+    pub stable_after: Duration,
+}
+
+impl Default for ResyncPolicy {
+    fn default() -> Self {
+        ResyncPolicy {
+            max_buffered: 2,
+            debounce: Duration::from_secs(2),
+            max_debounce: Duration::from_secs(30),
+            max_resyncs: 3,
+            max_reconnects: 2,
+            stable_after: Duration::from_secs(60),
+        }
+    }
+}
+
+// How many events the client keeps at most. `queued` are the events sent
+// while offline, `buffered` the received ones that don't apply yet, and
+// `undo` the own ones that can be undone. The oldest are dropped first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizes {
+    pub queued: usize,
+    pub buffered: usize,
+    pub undo: usize,
+}
+
+impl Default for BufferSizes {
+    fn default() -> Self {
+        BufferSizes {
+            queued: MAX_QUEUED_EVENTS,
+            buffered: MAX_BUFFERED_EVENTS,
+            undo: MAX_UNDO_EVENTS,
+        }
+    }
+}
+
+// Logged whenever received events don't apply to the state and a sync is
+// requested, so determinism bugs can be told apart from lost events. In debug
+// builds, the differences to the synced state are added once it arrives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DivergenceReport {
+    pub error: String,
+    // Syncs and reconnects in a row that didn't help, including this one.
+    pub resyncs: usize,
+    pub reconnects: usize,
+    pub buffered: usize,
+    pub differences: Vec<String>,
+}
+
+// An event that was applied to the state, for activity feeds. Ticks aren't
+// part of the feed.
+#[derive(Debug, Clone)]
+pub struct FeedEntry<S: State> {
+    pub event: EventData<S>,
+    // `None` for server events.
+    pub user_id: Option<S::UserId>,
+    // By the server's clock if it's known yet.
+    pub applied_at: SystemTime,
+}
+
+// The part of a client that doesn't depend on the connection or the
+// framework. The integrations pass it the responses of the server and send
+// the requests it returns.
+pub struct ClientCore<S: State> {
+    state: Option<SyncData<S>>,
+    // Whether the state was restored from a snapshot and not synced yet.
+    stale: bool,
+    messages: Vec<String>,
+    rejections: Vec<(S::ClientEvent, String)>,
+    // Extension messages with their kinds and payloads, see `Extensions`.
+    extensions: Vec<(String, Vec<u8>)>,
+    locales: Option<Vec<Locale>>,
+    log: fn(&str),
+    // The current time in milliseconds.
+    now: fn() -> f64,
+    status: ConnectionStatus,
+    // Whether the connection is open and synced. Until then, events are
+    // queued and sent after the sync.
+    online: bool,
+    queued: VecDeque<S::ClientEvent>,
+    // Own events that were sent but aren't confirmed yet, most recent last.
+    // They're only tracked while optimistic, as the prediction tells when
+    // they're confirmed.
+    undo_stack: Vec<S::ClientEvent>,
+    optimistic: bool,
+    // Spectators only watch the game, so they don't send any events.
+    spectator: bool,
+    acknowledged: bool,
+    next_event_id: u64,
+    // Sent events that the server didn't acknowledge yet, and whether they
+    // were sent again already.
+    unacked: VecDeque<(u64, S::ClientEvent, bool)>,
+    retry_unacked: bool,
+    // Whether events are validated against the local state before they're
+    // sent.
+    validate: bool,
+    // The confirmed state with the pending events applied.
+    predicted: Option<StateWrapper<S>>,
+    // Own events the server hasn't confirmed yet, in the order they were
+    // sent. `None` is the init event, which isn't predicted.
+    pending: VecDeque<Option<S::ClientEvent>>,
+    predict_ticks: bool,
+    // Ticks applied to the predicted state ahead of the server.
+    predicted_ticks: usize,
+    // When the last tick was confirmed or predicted.
+    last_tick: Option<f64>,
+    resync_policy: ResyncPolicy,
+    buffer_sizes: BufferSizes,
+    // Received events that couldn't be applied yet.
+    buffered: VecDeque<EventData<S>>,
+    // When a sync was requested last.
+    sync_requested: Option<f64>,
+    // Syncs in a row that didn't make the events apply again.
+    resyncs: usize,
+    reconnects: usize,
+    // When received events last didn't apply, see `ResyncPolicy`.
+    diverged_at: Option<f64>,
+    divergence: Option<DivergenceReport>,
+    reconnect: bool,
+    // Since when the page is hidden, as browsers throttle the timers of
+    // background tabs.
+    hidden_since: Option<f64>,
+    ping_id: u32,
+    // When the last ping was sent, and whether it's still unanswered.
+    last_ping: Option<f64>,
+    ping_pending: bool,
+    // The smoothed round trip time in milliseconds.
+    latency: Option<f64>,
+    // How far the server's clock is ahead of the local one, in milliseconds.
+    // It's kept across reconnects since clocks don't jump.
+    clock_offset: Option<f64>,
+    feed: VecDeque<FeedEntry<S>>,
+    feed_len: usize,
+    #[cfg(debug_assertions)]
+    diverged: Option<StateWrapper<S>>,
+    #[cfg(feature = "devtools")]
+    recent_events: VecDeque<Event<S>>,
+    #[cfg(feature = "devtools")]
+    syncs: usize,
+}
+
+impl<S: State> ClientCore<S> {
+    pub fn new(log: fn(&str), now: fn() -> f64) -> Self {
+        ClientCore {
+            state: None,
+            stale: false,
+            messages: Vec::new(),
+            rejections: Vec::new(),
+            extensions: Vec::new(),
+            locales: None,
+            log,
+            now,
+            status: ConnectionStatus::Connecting,
+            online: false,
+            queued: VecDeque::new(),
+            undo_stack: Vec::new(),
+            optimistic: false,
+            spectator: false,
+            acknowledged: false,
+            // Ids start at the current time, so they don't repeat after the
+            // page is reloaded.
+            next_event_id: (now() as u64) << 16,
+            unacked: VecDeque::new(),
+            retry_unacked: false,
+            validate: false,
+            predicted: None,
+            pending: VecDeque::new(),
+            predict_ticks: false,
+            predicted_ticks: 0,
+            last_tick: None,
+            resync_policy: ResyncPolicy::default(),
+            buffer_sizes: BufferSizes::default(),
+            buffered: VecDeque::new(),
+            sync_requested: None,
+            resyncs: 0,
+            reconnects: 0,
+            diverged_at: None,
+            divergence: None,
+            reconnect: false,
+            hidden_since: None,
+            ping_id: 0,
+            last_ping: None,
+            ping_pending: false,
+            latency: None,
+            clock_offset: None,
+            feed: VecDeque::new(),
+            feed_len: DEFAULT_FEED_LEN,
+            #[cfg(debug_assertions)]
+            diverged: None,
+            #[cfg(feature = "devtools")]
+            recent_events: VecDeque::new(),
+            #[cfg(feature = "devtools")]
+            syncs: 0,
+        }
+    }
+
+    // Applies own events to a predicted state right away instead of waiting
+    // for the server to confirm them. `get_state` returns the prediction
+    // until the confirmed state catches up.
+    pub fn set_optimistic(&mut self, optimistic: bool) {
+        self.optimistic = optimistic;
+        self.pending.clear();
+        self.undo_stack.clear();
+        self.predict();
+    }
+
+    // Simulates ticks locally when they're due, so timers and resource
+    // counters keep running smoothly even if the server's ticks arrive late.
+    // Adapters call `predict_tick` once `next_tick_in` elapsed.
+    pub fn set_predict_ticks(&mut self, predict_ticks: bool) {
+        self.predict_ticks = predict_ticks;
+        self.predicted_ticks = 0;
+        self.predict();
+    }
+
+    // The time until the next tick is predicted, or `None` if none is.
+    pub fn next_tick_in(&self) -> Option<Duration> {
+        if !self.predict_ticks
+            || !self.online
+            || self.hidden_since.is_some()
+            || self.predicted_ticks == MAX_PREDICTED_TICKS
+        {
+            return None;
+        }
+        let due = self.last_tick? + S::DURATION_PER_TICK.as_secs_f64() * 1000.0;
+        Some(Duration::from_secs_f64(
+            (due - (self.now)()).max(0.0) / 1000.0,
+        ))
+    }
+
+    // Applies a tick to the predicted state if one is due. Returns whether
+    // it did.
+    pub fn predict_tick(&mut self) -> bool {
+        match self.next_tick_in() {
+            Some(delay) if delay.is_zero() => {}
+            _ => return false,
+        }
+        self.predicted_ticks += 1;
+        self.last_tick = Some((self.now)());
+        self.predict();
+        true
+    }
+
+    // Takes effect for the init event on the next connection.
+    pub fn set_spectator(&mut self, spectator: bool) {
+        self.spectator = spectator;
+        if spectator {
+            self.queued.clear();
+            self.pending.clear();
+            self.predicted = None;
+        }
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+
+    pub fn set_resync_policy(&mut self, resync_policy: ResyncPolicy) {
+        self.resync_policy = resync_policy;
+    }
+
+    pub fn set_buffer_sizes(&mut self, buffer_sizes: BufferSizes) {
+        self.buffer_sizes = buffer_sizes;
+        while self.queued.len() > buffer_sizes.queued {
+            self.queued.pop_front();
+        }
+        while self.buffered.len() > buffer_sizes.buffered {
+            self.buffered.pop_front();
+        }
+        let undone = self.undo_stack.len().saturating_sub(buffer_sizes.undo);
+        self.undo_stack.drain(..undone);
+    }
+
+    // Sends events with ids the server acknowledges, so events that were
+    // lost while the connection was lost are sent again after reconnecting.
+    pub fn set_acknowledged(&mut self, acknowledged: bool) {
+        self.acknowledged = acknowledged;
+    }
+
+    pub fn set_validate(&mut self, validate: bool) {
+        self.validate = validate;
+    }
+
+    // Checks an event against the predicted state before it's sent. Events
+    // pass while the state is unknown, as the server decides anyway.
+    pub fn validate(&self, event: &S::ClientEvent) -> Result<(), String> {
+        if !self.validate {
+            return Ok(());
+        }
+        match (self.get_state(), self.get_user_id()) {
+            (Some(state), Some(user_id)) => state.validate(event, user_id),
+            _ => Ok(()),
+        }
+    }
+
+    // Checks a batch of events like `validate`, each one with the events
+    // before it applied. Returns the first event that didn't pass.
+    pub fn validate_events(
+        &self,
+        events: &[S::ClientEvent],
+    ) -> Result<(), (S::ClientEvent, String)> {
+        if !self.validate {
+            return Ok(());
+        }
+        let state = match &self.predicted {
+            Some(predicted) => predicted,
+            None => match &self.state {
+                Some(SyncData { state, .. }) => state,
+                None => return Ok(()),
+            },
+        };
+        let user_id = match self.get_user_id() {
+            Some(user_id) => user_id,
+            None => return Ok(()),
+        };
+        let mut batch: Option<StateWrapper<S>> = None;
+        for (i, event) in events.iter().enumerate() {
+            let current = batch.as_ref().unwrap_or(state);
+            if let Err(reason) = current.state.validate(event, user_id) {
+                return Err((event.clone(), reason));
+            }
+            if i + 1 < events.len() {
+                batch.get_or_insert_with(|| state.clone()).update_unchecked(
+                    Event::ClientEvent(event.clone(), user_id.clone()),
+                    PREDICTION_SEED,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_state(&self) -> Option<&S> {
+        match &self.predicted {
+            Some(predicted) => Some(&predicted.state),
+            None => self.get_confirmed_state(),
+        }
+    }
+
+    // The state as last confirmed by the server, without predictions.
+    pub fn get_confirmed_state(&self) -> Option<&S> {
+        self.state.as_ref().map(|data| &data.state.state)
+    }
+
+    // The checksum of the confirmed state, which the server compares with
+    // its own before every event.
+    pub fn checksum(&self) -> Option<Checksum>
+    where
+        S: Serialize,
+    {
+        self.state.as_ref().map(|data| data.state.checksum())
+    }
+
+    // The last events received from the server, oldest first.
+    #[cfg(feature = "devtools")]
+    pub fn recent_events(&self) -> impl DoubleEndedIterator<Item = &Event<S>> {
+        self.recent_events.iter()
+    }
+
+    // How often the server sent the whole state, e.g. to resync.
+    #[cfg(feature = "devtools")]
+    pub fn sync_count(&self) -> usize {
+        self.syncs
+    }
+
+    pub fn get_user_id(&self) -> Option<&S::UserId> {
+        self.state.as_ref().map(|data| &data.user_id)
+    }
+
+    pub fn get_user_data(&self, user_id: &S::UserId) -> Option<&S::UserData> {
+        self.state
+            .as_ref()
+            .and_then(|data| data.state.users.get(user_id))
+    }
+
+    // Forgets everything about the current game, e.g. before joining
+    // another one. The locales and options are kept.
+    pub fn reset(&mut self) {
+        self.state = None;
+        self.stale = false;
+        self.messages.clear();
+        self.rejections.clear();
+        self.extensions.clear();
+        self.undo_stack.clear();
+        self.status = ConnectionStatus::Connecting;
+        self.online = false;
+        self.queued.clear();
+        self.unacked.clear();
+        self.retry_unacked = false;
+        self.predicted = None;
+        self.pending.clear();
+        self.predicted_ticks = 0;
+        self.last_tick = None;
+        self.buffered.clear();
+        self.sync_requested = None;
+        self.resyncs = 0;
+        self.reconnects = 0;
+        self.diverged_at = None;
+        self.divergence = None;
+        self.reconnect = false;
+        self.last_ping = None;
+        self.ping_pending = false;
+        self.latency = None;
+        self.feed.clear();
+        #[cfg(debug_assertions)]
+        {
+            self.diverged = None;
+        }
+        #[cfg(feature = "devtools")]
+        {
+            self.recent_events.clear();
+            self.syncs = 0;
+        }
+    }
+
+    // Shows a state saved earlier until the server syncs, e.g. right after
+    // the page is loaded.
+    pub fn restore(&mut self, sync_data: SyncData<S>) {
+        if self.state.is_none() {
+            self.state = Some(sync_data);
+            self.stale = true;
+        }
+    }
+
+    // Applies the responses received after a restored state was saved, so
+    // it's as recent as when the page was left. Stops at the first event
+    // that doesn't apply.
+    pub fn restore_tail(&mut self, tail: impl IntoIterator<Item = Res<S>>)
+    where
+        S: Serialize,
+    {
+        if !self.stale {
+            return;
+        }
+        let Some(SyncData { state, .. }) = &mut self.state else {
+            return;
+        };
+        for res in tail {
+            let applied = match res {
+                Res::Event(event) => state.update_checked(event).is_ok(),
+                Res::UserUpdate(diff) => {
+                    state.users.apply_diff(diff);
+                    true
+                }
+                _ => true,
+            };
+            if !applied {
+                break;
+            }
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    // The synced state to save, unless it's still the restored one.
+    pub fn snapshot(&self) -> Option<&SyncData<S>> {
+        self.state.as_ref().filter(|_| !self.stale)
+    }
+
+    // Lets an integration keep the state in its own storage between two
+    // responses.
+    #[cfg(feature = "leptos")]
+    pub(crate) fn state_mut(&mut self) -> &mut Option<SyncData<S>> {
+        &mut self.state
+    }
+
+    // Messages from the server, already rendered in the user's language.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.messages)
+    }
+
+    // Own events the server didn't apply, with the reasons.
+    pub fn take_rejections(&mut self) -> Vec<(S::ClientEvent, String)> {
+        std::mem::take(&mut self.rejections)
+    }
+
+    // Extension messages from the server, with their kinds and encoded
+    // payloads.
+    pub fn take_extensions(&mut self) -> Vec<(String, Vec<u8>)> {
+        std::mem::take(&mut self.extensions)
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    // The events that are sent once the client is online again.
+    pub fn queued_events(&self) -> impl Iterator<Item = &S::ClientEvent> {
+        self.queued.iter()
+    }
+
+    // The round trip time to the server, measured with pings while the
+    // client is online.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency.map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    // The current time on the server's clock, estimated from the pings. It
+    // can be compared to times of the server, e.g. when a countdown ends,
+    // even if the local clock is wrong.
+    pub fn server_now(&self) -> Option<SystemTime> {
+        Some(system_time((self.now)() + self.clock_offset?))
+    }
+
+    // The last applied events, oldest first.
+    pub fn feed(&self) -> impl DoubleEndedIterator<Item = &FeedEntry<S>> {
+        self.feed.iter()
+    }
+
+    // How many events the feed keeps, 0 turns it off.
+    pub fn set_feed_len(&mut self, feed_len: usize) {
+        self.feed_len = feed_len;
+        while self.feed.len() > feed_len {
+            self.feed.pop_front();
+        }
+    }
+
+    pub fn status(&self) -> &ConnectionStatus {
+        &self.status
+    }
+
+    pub fn closed(&mut self, reason: String) {
+        self.online = false;
+        if !self.is_out_of_date() {
+            self.status = ConnectionStatus::Closed { reason };
+        }
+    }
+
+    // The connection was lost and the given attempt to reconnect is
+    // scheduled.
+    pub fn reconnecting(&mut self, attempt: usize) {
+        if !self.is_out_of_date() {
+            self.status = ConnectionStatus::Reconnecting { attempt };
+        }
+    }
+
+    // The integrations close the connection then, as nothing it sends
+    // applies anymore.
+    pub fn is_out_of_date(&self) -> bool {
+        self.status == ConnectionStatus::OutOfDate
+    }
+
+    // The last time received events didn't apply to the state.
+    pub fn divergence_report(&self) -> Option<&DivergenceReport> {
+        self.divergence.as_ref()
+    }
+
+    // The page went to the background. Ticks aren't predicted and pings
+    // aren't timed until it's visible again.
+    pub fn hidden(&mut self) {
+        if self.hidden_since.is_none() {
+            self.hidden_since = Some((self.now)());
+        }
+    }
+
+    // The page is visible again. Returns the requests to catch up with the
+    // server: after a short while, the events that arrived meanwhile are
+    // applied, after a longer one, the state is stale until it's synced.
+    pub fn visible(&mut self) -> Vec<Req<S>> {
+        let Some(hidden_since) = self.hidden_since.take() else {
+            return Vec::new();
+        };
+        // The timers were late, so the ping and the predicted ticks start
+        // over.
+        self.last_ping = None;
+        self.ping_pending = false;
+        self.last_tick = None;
+        if self.predicted_ticks > 0 {
+            self.predicted_ticks = 0;
+            self.predict();
+        }
+
+        let now = (self.now)();
+        if !self.online || self.state.is_none() || now - hidden_since < MAX_HIDDEN_MS {
+            return Vec::new();
+        }
+        self.stale = true;
+        self.sync_requested = Some(now);
+        vec![Req::Sync]
+    }
+
+    // The requests to send whenever a connection was opened.
+    pub fn opened(&mut self) -> Vec<Req<S>> {
+        if self.is_out_of_date() {
+            return Vec::new();
+        }
+        self.online = false;
+        self.status = ConnectionStatus::Open;
+        self.last_ping = None;
+        self.ping_pending = false;
+        self.pending.clear();
+        self.predicted = None;
+        self.buffered.clear();
+        self.sync_requested = Some((self.now)());
+        self.retry_unacked = !self.unacked.is_empty();
+
+        // The locales go first, so the server already renders the messages
+        // caused by the init event in them. Unless they were set, they're the
+        // ones negotiated with `i18n::set_locales`.
+        let locales = match &self.locales {
+            Some(locales) => locales.clone(),
+            None => i18n::get_global_locales().to_vec(),
+        };
+        let mut requests = Vec::new();
+        if !locales.is_empty() {
+            requests.push(Req::SetLocales(locales));
+        }
+        requests.push(Req::Sync);
+        if !self.spectator {
+            if self.optimistic {
+                self.pending.push_back(None);
+            }
+            requests.push(Req::Event(<S::ClientEvent as ClientEvent>::init()));
+        }
+        requests
+    }
+
+    // Returns the request to send, or `None` if the client is offline and
+    // the event was queued or dropped, or if it's a spectator.
+    pub fn send_event(&mut self, event: S::ClientEvent) -> Option<Req<S>> {
+        let req = self.send_unrecorded(event.clone())?;
+        self.record(event);
+        Some(req)
+    }
+
+    // Sends an event that can't be undone.
+    fn send_unrecorded(&mut self, event: S::ClientEvent) -> Option<Req<S>> {
+        if self.spectator {
+            (self.log)(&format!("spectators can't send {event:?}"));
+            return None;
+        }
+        if !self.online {
+            self.queue_event(event);
+            return None;
+        }
+        self.predict_event(&event);
+        if self.acknowledged {
+            let id = self.next_event_id;
+            self.next_event_id += 1;
+            self.unacked.push_back((id, event.clone(), false));
+            return Some(Req::AckedEvent(id, event));
+        }
+        Some(Req::Event(event))
+    }
+
+    // Sends the events as one batch, which the server applies without other
+    // events in between. The batch is predicted as a whole and rolled back as
+    // a whole if it couldn't be sent. Acknowledged events each need an id, so
+    // they're sent one by one.
+    pub fn send_events(&mut self, events: Vec<S::ClientEvent>) -> Vec<Req<S>> {
+        if self.spectator {
+            (self.log)(&format!("spectators can't send {events:?}"));
+            return Vec::new();
+        }
+        if !self.online {
+            for event in events {
+                self.queue_event(event);
+            }
+            return Vec::new();
+        }
+        for event in &events {
+            self.predict_event(event);
+        }
+        if self.acknowledged {
+            for event in &events {
+                self.record(event.clone());
+            }
+            return events
+                .into_iter()
+                .map(|event| {
+                    let id = self.next_event_id;
+                    self.next_event_id += 1;
+                    self.unacked.push_back((id, event.clone(), false));
+                    Req::AckedEvent(id, event)
+                })
+                .collect();
+        }
+        if events.is_empty() {
+            return Vec::new();
+        }
+        for event in &events {
+            self.record(event.clone());
+        }
+        vec![Req::Events(events)]
+    }
+
+    fn record(&mut self, event: S::ClientEvent) {
+        if !self.optimistic || self.buffer_sizes.undo == 0 {
+            return;
+        }
+        if self.undo_stack.len() >= self.buffer_sizes.undo {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(event);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.queued.is_empty()
+            || self
+                .undo_stack
+                .last()
+                .is_some_and(|event| event.undo().is_some())
+    }
+
+    // Takes back the last own event that the server hasn't confirmed yet and
+    // returns it. Events that are queued while offline are just dropped, sent
+    // ones are reverted with the event of `ClientEvent::undo`, whose request
+    // is returned to be sent. Confirmed events can't be undone.
+    pub fn undo(&mut self) -> Option<(S::ClientEvent, Option<Req<S>>)> {
+        if let Some(event) = self.queued.pop_back() {
+            return Some((event, None));
+        }
+        let revert = self.undo_stack.last()?.undo()?;
+        let event = self.undo_stack.pop()?;
+        Some((event, self.send_unrecorded(revert)))
+    }
+
+    fn predict_event(&mut self, event: &S::ClientEvent) {
+        if self.optimistic {
+            self.pending.push_back(Some(event.clone()));
+            if let Some(SyncData { user_id, state, .. }) = &self.state {
+                self.predicted
+                    .get_or_insert_with(|| state.clone())
+                    .update_unchecked(
+                        Event::ClientEvent(event.clone(), user_id.clone()),
+                        PREDICTION_SEED,
+                    );
+            }
+        }
+    }
+
+    // Puts a request back into the queue after it couldn't be sent because
+    // the connection was lost.
+    pub fn send_failed(&mut self, req: Req<S>) {
+        self.online = false;
+        let (sent, events) = match req {
+            Req::Event(event) => (1, vec![event]),
+            Req::Events(events) => (events.len(), events),
+            // Keeps its id, as an earlier attempt may have arrived.
+            Req::AckedEvent(id, _) => {
+                if let Some(unacked) = self.unacked.iter_mut().find(|unacked| unacked.0 == id) {
+                    unacked.2 = false;
+                }
+                (1, Vec::new())
+            }
+            _ => return,
+        };
+        if self.optimistic {
+            let len = self.pending.len();
+            self.pending.truncate(len.saturating_sub(sent));
+            self.predict();
+        }
+        // Queued events are undone without the undo stack.
+        for event in &events {
+            forget(&mut self.undo_stack, event, true);
+        }
+        for event in events {
+            self.queue_event(event);
+        }
+    }
+
+    // Sends the events that weren't acknowledged before the connection was
+    // lost again, but only once, so the server applies them at most once.
+    fn retry_unacked(&mut self) -> Vec<Req<S>> {
+        if !std::mem::take(&mut self.retry_unacked) {
+            return Vec::new();
+        }
+        let mut requests = Vec::new();
+        for (id, event, retried) in std::mem::take(&mut self.unacked) {
+            if retried {
+                (self.log)(&format!("event was lost, dropped {event:?}"));
+                continue;
+            }
+            self.predict_event(&event);
+            self.unacked.push_back((id, event.clone(), true));
+            requests.push(Req::AckedEvent(id, event));
+        }
+        requests
+    }
+
+    fn queue_event(&mut self, event: S::ClientEvent) {
+        if event.droppable() {
+            return;
+        }
+        if self.queued.len() >= self.buffer_sizes.queued {
+            if let Some(dropped) = self.queued.pop_front() {
+                (self.log)(&format!(
+                    "too many events while offline, dropped {dropped:?}"
+                ));
+            }
+        }
+        self.queued.push_back(event);
+    }
+
+    // Rolls the prediction back to the confirmed state and applies the
+    // events that are still pending, so it never drifts from the server.
+    fn predict(&mut self) {
+        self.predicted = None;
+        if let Some(SyncData { user_id, state, .. }) = &self.state {
+            let events = self
+                .pending
+                .iter()
+                .flatten()
+                .map(|event| Event::ClientEvent(event.clone(), user_id.clone()));
+            let ticks = (0..self.predicted_ticks)
+                .map(|_| Event::ServerEvent(<S::ServerEvent as ServerEvent<S>>::tick()));
+            for event in events.chain(ticks) {
+                self.predicted
+                    .get_or_insert_with(|| state.clone())
+                    .update_unchecked(event, PREDICTION_SEED);
+            }
+        }
+    }
+
+    // A response couldn't be decoded, so an event may be missing and the
+    // state is synced again. That includes the sync itself while the
+    // connection is open but not synced yet, so a sync that never decodes
+    // ends up out of date instead of waiting forever.
+    pub fn decode_failed(&mut self, err: impl fmt::Display) -> Vec<Req<S>> {
+        (self.log)(&format!("couldn't decode response: {err}"));
+        if !self.online && self.status != ConnectionStatus::Open {
+            return Vec::new();
+        }
+        self.request_sync(format!("couldn't decode response: {err}"))
+    }
+
+    // Applies the buffered events in order until one fails, e.g. because it
+    // depends on user data that hasn't arrived yet.
+    fn apply_buffered(&mut self) -> Vec<Req<S>>
+    where
+        S: Serialize,
+    {
+        let log = self.log;
+        let now = (self.now)();
+        let applied_at = system_time(now + self.clock_offset.unwrap_or_default());
+        let Some(SyncData { state, user_id, .. }) = &mut self.state else {
+            return Vec::new();
+        };
+        let mut error = None;
+        while let Some(event) = self.buffered.front() {
+            let own = match &event.event {
+                Event::ClientEvent(own, sender) if sender == user_id => Some(own.clone()),
+                _ => None,
+            };
+            let tick = is_tick(&event.event);
+            let entry = (!tick && self.feed_len > 0).then(|| FeedEntry {
+                user_id: match &event.event {
+                    Event::ClientEvent(_, sender) => Some(sender.clone()),
+                    Event::ServerEvent(_) => None,
+                },
+                event: event.clone(),
+                applied_at,
+            });
+            // Failed updates don't change the state, so the event can be
+            // retried.
+            match state.update_checked(event.clone()) {
+                Ok(()) => {}
+                Err(err @ engine_shared::Error::InvariantViolated { .. }) => {
+                    log(&err.to_string());
+                }
+                Err(err) => {
+                    #[cfg(debug_assertions)]
+                    if let engine_shared::Error::InvalidChecksum { .. } = err {
+                        self.diverged.get_or_insert_with(|| state.clone());
+                    }
+                    error = Some(err);
+                    break;
+                }
+            }
+            self.buffered.pop_front();
+            if let Some(entry) = entry {
+                if self.feed.len() == self.feed_len {
+                    self.feed.pop_front();
+                }
+                self.feed.push_back(entry);
+            }
+            let stable_after = self.resync_policy.stable_after.as_secs_f64() * 1000.0;
+            if self
+                .diverged_at
+                .is_none_or(|diverged_at| now - diverged_at >= stable_after)
+            {
+                self.resyncs = 0;
+                self.reconnects = 0;
+                self.diverged_at = None;
+            }
+            if let Some(own) = own {
+                self.pending.pop_front();
+                forget(&mut self.undo_stack, &own, false);
+            }
+            if tick {
+                // A predicted tick got confirmed, otherwise the server was
+                // ahead of the prediction.
+                if self.predicted_ticks > 0 {
+                    self.predicted_ticks -= 1;
+                } else {
+                    self.last_tick = Some((self.now)());
+                }
+            }
+        }
+        if self.predicted.is_some() || !self.pending.is_empty() {
+            self.predict();
+        }
+
+        match error {
+            Some(err) if self.buffered.len() >= self.resync_policy.max_buffered => {
+                self.request_sync(format!("invalid state: {err}"))
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Syncs are debounced, and if they don't help, the connection is
+    // replaced, which the adapter learns with `take_reconnect`. If that
+    // doesn't help either, the client is out of date.
+    fn request_sync(&mut self, error: String) -> Vec<Req<S>> {
+        use std::convert::TryFrom;
+
+        let now = (self.now)();
+        let policy = self.resync_policy;
+        let exponent = u32::try_from(self.resyncs).unwrap_or(u32::MAX);
+        let debounce = policy
+            .debounce
+            .saturating_mul(2_u32.saturating_pow(exponent))
+            .min(policy.max_debounce)
+            .as_secs_f64()
+            * 1000.0;
+        if self
+            .sync_requested
+            .is_some_and(|sync_requested| now - sync_requested < debounce)
+        {
+            return Vec::new();
+        }
+        self.diverged_at = Some(now);
+        self.resyncs += 1;
+        let report = DivergenceReport {
+            error,
+            resyncs: self.resyncs,
+            reconnects: self.reconnects,
+            buffered: self.buffered.len(),
+            differences: Vec::new(),
+        };
+        (self.log)(&format!("{report:?}"));
+        self.divergence = Some(report);
+        if self.resyncs > policy.max_resyncs {
+            self.resyncs = 0;
+            self.buffered.clear();
+            self.reconnects += 1;
+            if self.reconnects > policy.max_reconnects {
+                (self.log)("state keeps diverging from the server, the client is out of date");
+                self.online = false;
+                self.status = ConnectionStatus::OutOfDate;
+                return Vec::new();
+            }
+            self.reconnect = true;
+            self.closed("state keeps diverging from the server".to_owned());
+            return Vec::new();
+        }
+        self.sync_requested = Some(now);
+        self.status = ConnectionStatus::Syncing;
+        vec![Req::Sync]
+    }
+
+    // Whether the connection has to be replaced, as syncing didn't help or
+    // it's dead.
+    pub fn take_reconnect(&mut self) -> bool {
+        std::mem::take(&mut self.reconnect)
+    }
+
+    // The server renders its messages in these locales from now on, and the
+    // next render picks them up on the client.
+    pub fn set_locales(&mut self, locales: Vec<Locale>) -> Req<S> {
+        i18n::set_locales(&locales);
+        self.locales = Some(locales.clone());
+        Req::SetLocales(locales)
+    }
+
+    // Applies a response of the server. Returns the requests to send, e.g.
+    // to resync if the state became invalid.
+    pub fn receive(&mut self, res: Res<S>) -> Result<Vec<Req<S>>, Outdated>
+    where
+        S: Serialize,
+    {
+        if self.is_out_of_date() {
+            return Ok(Vec::new());
+        }
+        let mut requests = self.apply(res)?;
+        // The server sends responses at least every tick, so there's no
+        // need for a separate timer.
+        requests.extend(self.ping());
+        Ok(requests)
+    }
+
+    // Called periodically, as a dead connection doesn't receive anything
+    // that would send the pings. If the last ping wasn't answered, the
+    // connection is closed and has to be replaced, see `take_reconnect`.
+    pub fn heartbeat(&mut self) -> Option<Req<S>> {
+        // Throttled timers would make the pong seem late.
+        if self.hidden_since.is_some() {
+            return None;
+        }
+        let now = (self.now)();
+        let timed_out = self
+            .last_ping
+            .is_some_and(|last_ping| now - last_ping >= PONG_TIMEOUT_MS);
+        if self.online && self.ping_pending && timed_out {
+            self.ping_pending = false;
+            self.reconnect = true;
+            self.closed("server didn't answer ping".to_owned());
+            return None;
+        }
+        self.ping()
+    }
+
+    fn ping(&mut self) -> Option<Req<S>> {
+        let now = (self.now)();
+        let due = self
+            .last_ping
+            .is_none_or(|last_ping| now - last_ping >= PING_INTERVAL_MS);
+        if !self.online || self.ping_pending || !due {
+            return None;
+        }
+        self.ping_id = self.ping_id.wrapping_add(1);
+        self.last_ping = Some(now);
+        self.ping_pending = true;
+        Some(Req::Ping(self.ping_id))
+    }
+
+    fn apply(&mut self, res: Res<S>) -> Result<Vec<Req<S>>, Outdated>
+    where
+        S: Serialize,
+    {
+        let log = self.log;
+        match res {
+            Res::Sync(sync_data) => {
+                if sync_data.schema_version > S::SCHEMA_VERSION {
+                    return Err(Outdated {
+                        client: S::SCHEMA_VERSION,
+                        server: sync_data.schema_version,
+                    });
+                }
+                if sync_data.schema_version < S::SCHEMA_VERSION {
+                    log(&format!(
+                        "server schema version {} is older than client schema version {}",
+                        sync_data.schema_version,
+                        S::SCHEMA_VERSION
+                    ));
+                }
+                #[cfg(debug_assertions)]
+                if let Some(diverged) = self.diverged.take() {
+                    // The synced state can already contain later events, so
+                    // not every difference has to be part of the divergence.
+                    match engine_shared::diff::divergences(&sync_data.state, &diverged) {
+                        Ok(divergences) => {
+                            log("state diverged from the server, differences after resync:");
+                            let differences = divergences
+                                .iter()
+                                .take(MAX_LOGGED_DIVERGENCES)
+                                .map(ToString::to_string);
+                            let differences: Vec<_> = differences.collect();
+                            for difference in &differences {
+                                log(difference);
+                            }
+                            if let Some(report) = &mut self.divergence {
+                                report.differences = differences;
+                            }
+                            if divergences.len() > MAX_LOGGED_DIVERGENCES {
+                                log(&format!(
+                                    "and {} more differences",
+                                    divergences.len() - MAX_LOGGED_DIVERGENCES
+                                ));
+                            }
+                        }
+                        Err(err) => log(&format!("couldn't diff diverged state: {err}")),
+                    }
+                }
+                self.state = Some(sync_data);
+                self.stale = false;
+                #[cfg(feature = "devtools")]
+                {
+                    self.syncs += 1;
+                }
+                // The synced state may already contain pending events.
+                self.pending.clear();
+                self.undo_stack.clear();
+                self.predicted = None;
+                self.predicted_ticks = 0;
+                self.last_tick = None;
+                // The synced state contains the buffered events.
+                self.buffered.clear();
+
+                self.online = true;
+                self.status = ConnectionStatus::Live;
+                let mut requests = self.retry_unacked();
+                let queued = std::mem::take(&mut self.queued);
+                requests.extend(
+                    queued
+                        .into_iter()
+                        .filter_map(|event| self.send_event(event)),
+                );
+                return Ok(requests);
+            }
+            // Events can arrive before the sync, and they don't apply to a
+            // restored state.
+            Res::Event(_) if self.stale => {}
+            Res::Event(event) => {
+                #[cfg(feature = "devtools")]
+                {
+                    if self.recent_events.len() == MAX_RECENT_EVENTS {
+                        self.recent_events.pop_front();
+                    }
+                    self.recent_events.push_back(event.event.clone());
+                }
+                if self.state.is_some() {
+                    if self.buffered.len() >= self.buffer_sizes.buffered {
+                        self.buffered.pop_front();
+                    }
+                    self.buffered.push_back(event);
+                    return Ok(self.apply_buffered());
+                }
+            }
+            Res::UserUpdate(diff) => {
+                if let Some(SyncData { state, .. }) = &mut self.state {
+                    state.users.apply_diff(diff);
+                }
+                // Buffered events may have depended on the update.
+                if !self.buffered.is_empty() {
+                    return Ok(self.apply_buffered());
+                }
+                if self.predicted.is_some() {
+                    self.predict();
+                }
+            }
+            Res::Message(message) => self.messages.push(message),
+            Res::Extension(kind, payload) => self.extensions.push((kind, payload)),
+            Res::Rejected(event, reason) => {
+                // The event won't be confirmed, so it's no longer predicted.
+                let encoded = rmp_serde::to_vec(&event).ok();
+                let rejected = self.pending.iter().position(|pending| {
+                    pending
+                        .as_ref()
+                        .is_some_and(|pending| rmp_serde::to_vec(pending).ok() == encoded)
+                });
+                if let Some(index) = rejected {
+                    self.pending.remove(index);
+                    self.predict();
+                }
+                forget(&mut self.undo_stack, &event, false);
+                self.rejections.push((event, reason));
+            }
+            Res::Ack(id) => self.unacked.retain(|(unacked_id, ..)| *unacked_id != id),
+            Res::Pong(id, server_time) => match self.last_ping {
+                Some(last_ping) if self.ping_pending && id == self.ping_id => {
+                    self.ping_pending = false;
+                    let now = (self.now)();
+                    let rtt = now - last_ping;
+                    // Smoothed like TCP's round trip time estimate.
+                    self.latency = Some(match self.latency {
+                        Some(latency) => latency + (rtt - latency) / 8.0,
+                        None => rtt,
+                    });
+                    // The server answered about halfway through the round
+                    // trip.
+                    let offset = server_time as f64 + rtt / 2.0 - now;
+                    self.clock_offset = Some(match self.clock_offset {
+                        Some(clock_offset) => clock_offset + (offset - clock_offset) / 8.0,
+                        None => offset,
+                    });
+                }
+                _ => {}
+            },
+            Res::Catalog(locale, source) => {
+                #[cfg(feature = "hot-reload")]
+                if let Err(err) = i18n::load_catalog(locale, &source) {
+                    log(&format!("couldn't reload catalog: {err}"));
+                }
+                #[cfg(not(feature = "hot-reload"))]
+                let _ = (locale, source);
+            }
+        }
+        Ok(Vec::new())
+    }
+}
+
+// Removes the first event from the undo stack that's the same as the given
+// one, or the last one. Events don't have to be comparable either, see
+// `is_tick`.
+fn forget<E: Serialize>(undo_stack: &mut Vec<E>, event: &E, last: bool) {
+    let encoded = rmp_serde::to_vec(event).ok();
+    let same = |undoable: &E| rmp_serde::to_vec(undoable).ok() == encoded;
+    let index = if last {
+        undo_stack.iter().rposition(same)
+    } else {
+        undo_stack.iter().position(same)
+    };
+    if let Some(index) = index {
+        undo_stack.remove(index);
+    }
+}
+
+// Milliseconds since the Unix epoch.
+fn system_time(ms: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+}
+
+// Server events don't have to be comparable, so ticks are recognized by
+// their encoding.
+pub(crate) fn is_tick<S: State>(event: &Event<S>) -> bool {
+    match event {
+        Event::ServerEvent(event) => {
+            let tick = <S::ServerEvent as ServerEvent<S>>::tick();
+            rmp_serde::to_vec(event).ok() == rmp_serde::to_vec(&tick).ok()
+        }
+        Event::ClientEvent(..) => false,
+    }
+}
+
+#[cfg(any(
+    feature = "seed",
+    feature = "native",
+    feature = "yew",
+    feature = "leptos",
+    feature = "worker"
+))]
+pub(crate) fn encode<S: State + Serialize>(req: &Req<S>) -> Vec<u8> {
+    rmp_serde::to_vec(req).unwrap()
+}
+
+#[cfg(any(
+    feature = "seed",
+    feature = "native",
+    feature = "yew",
+    feature = "leptos",
+    feature = "worker"
+))]
+pub(crate) fn decode<S: State + serde::de::DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<Res<S>, rmp_serde::decode::Error> {
+    // Without the feature, compressed frames are invalid like any other.
+    #[cfg(feature = "compression")]
+    let bytes = &*engine_shared::compression::decompress(bytes)
+        .map_err(|err| rmp_serde::decode::Error::Uncategorized(err.to_string()))?;
+    rmp_serde::from_slice(bytes)
+}
+
+// A sync that can't be decoded is only outdated if the server's schema
+// version is newer, as reloading gets the same client otherwise. Everything
+// else goes through `ClientCore::decode_failed`.
+#[cfg(any(
+    feature = "seed",
+    feature = "native",
+    feature = "yew",
+    feature = "leptos",
+    feature = "worker"
+))]
+pub(crate) fn mismatched_sync<S: State>(bytes: &[u8]) -> Option<Outdated> {
+    use serde::{de::IgnoredAny, Deserialize};
+
+    // Mirrors `Res::Sync` without decoding the state.
+    #[derive(Deserialize)]
+    enum Header {
+        Sync(SyncHeader),
+    }
+
+    #[derive(Deserialize)]
+    struct SyncHeader {
+        #[allow(dead_code)]
+        user_id: IgnoredAny,
+        schema_version: SchemaVersion,
+        #[allow(dead_code)]
+        state: IgnoredAny,
+    }
+
+    #[cfg(feature = "compression")]
+    let bytes = &*engine_shared::compression::decompress(bytes).ok()?;
+    let Header::Sync(header) = rmp_serde::from_slice(bytes).ok()?;
+    if header.schema_version <= S::SCHEMA_VERSION {
+        return None;
+    }
+    Some(Outdated {
+        client: S::SCHEMA_VERSION,
+        server: header.schema_version,
+    })
+}
+
+// Chrome doesn't report an error when the connection is lost, so unclean
+// closes are reconnected too.
+#[cfg(any(
+    feature = "seed",
+    feature = "yew",
+    feature = "leptos",
+    feature = "worker"
+))]
+pub(crate) fn should_reconnect(was_clean: bool, code: u16) -> bool {
+    !was_clean || code == 4000
+}
@@ -0,0 +1,52 @@
+use engine_shared::State;
+use seed::{prelude::*, *};
+use serde::Serialize;
+
+use crate::core::ClientCore;
+
+pub(crate) const TOGGLE_KEY: &str = "F2";
+
+// Shows what the client knows about the connection and the state, e.g. to
+// find out which event made the client diverge from the server.
+pub(crate) fn view<S: State + Serialize, M: 'static>(core: &ClientCore<S>) -> Node<M> {
+    let checksum = core.checksum().map(|checksum| {
+        checksum
+            .iter()
+            .take(8)
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    });
+    let latency = core.latency().map(|latency| latency.as_millis());
+
+    div![
+        style! {
+            St::Position => "fixed",
+            St::Right => px(0),
+            St::Bottom => px(0),
+            St::Width => em(32),
+            St::MaxHeight => "50vh",
+            St::Overflow => "auto",
+            St::Padding => em(0.5),
+            St::Background => "rgba(0, 0, 0, 0.8)",
+            St::Color => "white",
+            St::FontFamily => "monospace",
+            St::FontSize => px(12),
+            St::ZIndex => "10000",
+        },
+        div![format!("status: {:?}", core.status())],
+        div![format!("checksum: {}", checksum.as_deref().unwrap_or("-"))],
+        div![format!("syncs: {}", core.sync_count())],
+        div![format!("queued events: {}", core.queued_events().count())],
+        div![match latency {
+            Some(latency) => format!("latency: {latency} ms"),
+            None => "latency: -".to_owned(),
+        }],
+        div!["recent events:"],
+        ol![
+            attrs! { At::Reversed => AtValue::None },
+            core.recent_events()
+                .rev()
+                .map(|event| li![format!("{event:?}")])
+        ],
+    ]
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+
+type Handler = Box<dyn FnMut(&[u8]) -> Result<(), rmp_serde::decode::Error>>;
+
+// Handlers for the extension messages of the application, e.g. chat messages
+// or notifications that the server sends next to the game's events. Each kind
+// has one handler, which gets the payload as the type the server encoded.
+#[derive(Default)]
+pub struct Extensions {
+    handlers: HashMap<String, Handler>,
+}
+
+impl Extensions {
+    // Replaces the previous handler of the kind.
+    pub fn on<T, F>(&mut self, kind: impl Into<String>, mut handler: F)
+    where
+        T: DeserializeOwned,
+        F: FnMut(T) + 'static,
+    {
+        self.handlers.insert(
+            kind.into(),
+            Box::new(move |payload| {
+                handler(rmp_serde::from_slice(payload)?);
+                Ok(())
+            }),
+        );
+    }
+
+    pub fn remove(&mut self, kind: &str) {
+        self.handlers.remove(kind);
+    }
+
+    // Calls the handler of the kind. Fails if there's none or the payload
+    // doesn't decode, so the integration can log it.
+    pub fn dispatch(&mut self, kind: &str, payload: &[u8]) -> Result<(), String> {
+        let handler = self
+            .handlers
+            .get_mut(kind)
+            .ok_or_else(|| format!("no handler for extension {kind}"))?;
+        handler(payload).map_err(|err| format!("couldn't decode extension {kind}: {err}"))
+    }
+}
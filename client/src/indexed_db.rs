@@ -0,0 +1,242 @@
+use std::{cell::RefCell, rc::Rc};
+
+use engine_shared::{GameId, Res, State, SyncData};
+use seed::{
+    prelude::{
+        js_sys::{Array, Date, Function, Promise, Uint8Array},
+        wasm_bindgen::closure::Closure,
+        web_sys::{IdbDatabase, IdbKeyRange, IdbRequest, IdbTransactionMode},
+        JsCast, JsValue,
+    },
+    JsFuture,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Snapshots of big worlds don't fit into the local storage, so they're kept
+// in IndexedDB instead. Snapshots are saved rarely, and the responses that
+// changed the state since are appended one by one.
+
+const NAME: &str = "engine-snapshots";
+const SNAPSHOTS: &str = "snapshots";
+const TAILS: &str = "tails";
+const SNAPSHOT_INTERVAL_MS: f64 = 60_000.0;
+const MAX_TAIL_LEN: u32 = 4096;
+
+pub(crate) struct Cache {
+    // The key of the first game, which joined games extend with their ids.
+    prefix: String,
+    key: String,
+    // Set once the database is open.
+    db: Rc<RefCell<Option<IdbDatabase>>>,
+    last_snapshot: f64,
+    tail_len: u32,
+}
+
+impl Cache {
+    pub(crate) fn new(key: String) -> Self {
+        Cache {
+            prefix: key.clone(),
+            key,
+            db: Rc::default(),
+            last_snapshot: 0.0,
+            tail_len: 0,
+        }
+    }
+
+    // Opens the database and loads the saved snapshot with its tail.
+    pub(crate) fn open<S: State + DeserializeOwned>(
+        &self,
+    ) -> impl std::future::Future<Output = Option<(SyncData<S>, Vec<Res<S>>)>> {
+        let key = self.key.clone();
+        let slot = self.db.clone();
+        async move {
+            let loaded = async {
+                let db = open().await?;
+                *slot.borrow_mut() = Some(db.clone());
+                load(&db, &key).await
+            };
+            match loaded.await {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    seed::log!("couldn't load snapshot:", err);
+                    None
+                }
+            }
+        }
+    }
+
+    // Loads the saved snapshot of the current key once the database is open,
+    // e.g. after joining another game.
+    pub(crate) fn load<S: State + DeserializeOwned>(
+        &self,
+    ) -> impl std::future::Future<Output = Option<(SyncData<S>, Vec<Res<S>>)>> {
+        let key = self.key.clone();
+        let db = self.db.borrow().clone();
+        async move {
+            match load(&db?, &key).await {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    seed::log!("couldn't load snapshot:", err);
+                    None
+                }
+            }
+        }
+    }
+
+    // Starts over with the next response under the key of the game.
+    pub(crate) fn join(&mut self, game_id: GameId) {
+        self.key = format!("{}/{game_id}", self.prefix);
+        self.last_snapshot = 0.0;
+        self.tail_len = 0;
+    }
+
+    // Called after a response was applied. `tail` is the response if it
+    // changed the state.
+    pub(crate) fn received<S: State + Serialize>(
+        &mut self,
+        synced: bool,
+        tail: Option<Res<S>>,
+        sync_data: Option<&SyncData<S>>,
+    ) {
+        let db = self.db.borrow().clone();
+        let (Some(db), Some(sync_data)) = (db, sync_data) else {
+            return;
+        };
+        let now = Date::now();
+        let due = now - self.last_snapshot >= SNAPSHOT_INTERVAL_MS || self.tail_len >= MAX_TAIL_LEN;
+        let result = if synced || due {
+            self.last_snapshot = now;
+            self.tail_len = 0;
+            save(&db, &self.key, sync_data)
+        } else if let Some(res) = tail {
+            self.tail_len += 1;
+            append(&db, &self.key, self.tail_len, &res)
+        } else {
+            Ok(())
+        };
+        if let Err(err) = result {
+            seed::log!("couldn't save snapshot:", err);
+        }
+    }
+}
+
+// Resolves once the request succeeded, with its result. The handlers are set
+// right away, so requests can be awaited one after another.
+fn request(request: &IdbRequest) -> JsFuture {
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move |_: JsValue| {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            resolve.call1(&JsValue::NULL, &result).ok();
+        });
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move |_: JsValue| {
+            let error = failed.error().ok().flatten().map(JsValue::from);
+            reject
+                .call1(&JsValue::NULL, &error.unwrap_or(JsValue::UNDEFINED))
+                .ok();
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise)
+}
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    let factory = seed::window()
+        .indexed_db()?
+        .ok_or("IndexedDB isn't available")?;
+    let open = factory.open_with_u32(NAME, 1)?;
+    let upgraded = open.clone();
+    let on_upgrade = Closure::once_into_js(move |_: JsValue| {
+        if let Ok(db) = upgraded.result() {
+            let db: IdbDatabase = db.unchecked_into();
+            db.create_object_store(SNAPSHOTS).ok();
+            db.create_object_store(TAILS).ok();
+        }
+    });
+    open.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+    Ok(request(&open).await?.unchecked_into())
+}
+
+// The responses of a tail are numbered, so they're loaded in order.
+fn tail_key(key: &str, index: u32) -> String {
+    format!("{key}/{index:010}")
+}
+
+fn tail_range(key: &str) -> Result<JsValue, JsValue> {
+    IdbKeyRange::bound(&tail_key(key, 0).into(), &tail_key(key, u32::MAX).into()).map(Into::into)
+}
+
+fn decode<T: DeserializeOwned>(value: &JsValue) -> Option<T> {
+    let bytes = value.dyn_ref::<Uint8Array>()?.to_vec();
+    rmp_serde::from_slice(&bytes).ok()
+}
+
+async fn load<S: State + DeserializeOwned>(
+    db: &IdbDatabase,
+    key: &str,
+) -> Result<Option<(SyncData<S>, Vec<Res<S>>)>, JsValue> {
+    let transaction = db.transaction_with_str_sequence(&js_array(&[SNAPSHOTS, TAILS]))?;
+    let snapshot = request(&transaction.object_store(SNAPSHOTS)?.get(&key.into())?);
+    let tail = request(
+        &transaction
+            .object_store(TAILS)?
+            .get_all_with_key(&tail_range(key)?)?,
+    );
+    let (snapshot, tail) = (snapshot.await?, tail.await?);
+
+    let sync_data = match decode::<SyncData<S>>(&snapshot) {
+        Some(sync_data) if sync_data.schema_version == S::SCHEMA_VERSION => sync_data,
+        // Snapshots of other versions are outdated once the server syncs
+        // anyway.
+        _ => return Ok(None),
+    };
+    let tail = match tail.dyn_into::<Array>() {
+        Ok(tail) => tail.iter().map_while(|res| decode(&res)).collect(),
+        Err(_) => Vec::new(),
+    };
+    Ok(Some((sync_data, tail)))
+}
+
+// Replaces the snapshot and drops its tail.
+fn save<S: State + Serialize>(
+    db: &IdbDatabase,
+    key: &str,
+    sync_data: &SyncData<S>,
+) -> Result<(), JsValue> {
+    let transaction = db.transaction_with_str_sequence_and_mode(
+        &js_array(&[SNAPSHOTS, TAILS]),
+        IdbTransactionMode::Readwrite,
+    )?;
+    let bytes = rmp_serde::to_vec(sync_data).unwrap();
+    transaction.object_store(TAILS)?.delete(&tail_range(key)?)?;
+    transaction
+        .object_store(SNAPSHOTS)?
+        .put_with_key(&Uint8Array::from(&bytes[..]), &key.into())?;
+    Ok(())
+}
+
+// Transactions on the same stores run in the order they were created, so the
+// tail is written after its snapshot.
+fn append<S: State + Serialize>(
+    db: &IdbDatabase,
+    key: &str,
+    index: u32,
+    res: &Res<S>,
+) -> Result<(), JsValue> {
+    let transaction = db.transaction_with_str_and_mode(TAILS, IdbTransactionMode::Readwrite)?;
+    let bytes = rmp_serde::to_vec(res).unwrap();
+    transaction
+        .object_store(TAILS)?
+        .put_with_key(&Uint8Array::from(&bytes[..]), &tail_key(key, index).into())?;
+    Ok(())
+}
+
+fn js_array(values: &[&str]) -> JsValue {
+    values
+        .iter()
+        .map(|value| JsValue::from_str(value))
+        .collect::<Array>()
+        .into()
+}
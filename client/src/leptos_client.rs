@@ -0,0 +1,408 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use engine_shared::{
+    utils::custom_map::CustomMap, Req, Res, SchemaVersion, State, StateWrapper, SyncData,
+};
+use i18n::Locale;
+use leptos::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    browser::{log, reload, ReconnectingWebSocket, SocketEvents},
+    core::{
+        decode, encode, mismatched_sync, ClientCore, ConnectionStatus, FeedEntry, ReconnectPolicy,
+        ResyncPolicy,
+    },
+    Extensions,
+};
+
+// Setting a signal runs its effects right away, and they could dispatch
+// events while the connection is still borrowed, so subscribers are only
+// notified after the message is handled.
+fn set_later<T>(signal: RwSignal<T>, f: impl FnOnce(&mut T)) {
+    signal.update_untracked(f);
+    queue_microtask(move || signal.update(|_| {}));
+}
+
+struct Connection<S: State> {
+    socket: ReconnectingWebSocket,
+    core: ClientCore<S>,
+    schema_version: SchemaVersion,
+    state: RwSignal<Option<S>>,
+    user_id: RwSignal<Option<S::UserId>>,
+    users: RwSignal<CustomMap<S::UserId, S::UserData>>,
+    status: RwSignal<ConnectionStatus>,
+    latency: RwSignal<Option<Duration>>,
+    messages: RwSignal<Vec<String>>,
+    rejections: RwSignal<Vec<(S::ClientEvent, String)>>,
+    extensions: Rc<RefCell<Extensions>>,
+}
+
+impl<S: State + Serialize + DeserializeOwned> Connection<S> {
+    fn request(&mut self, req: Req<S>) {
+        if let Err(err) = self.socket.send(&encode(&req)) {
+            log(&format!("couldn't send request: {err:?}"));
+            self.core.send_failed(req);
+        }
+    }
+
+    // The state lives in the signals between two responses, so it's moved
+    // into the core to apply a response instead of being cloned.
+    fn lend_state(&mut self) {
+        let state = self.state.try_update_untracked(Option::take).flatten();
+        let user_id = self.user_id.get_untracked();
+        if let (Some(state), Some(user_id)) = (state, user_id) {
+            let users = self
+                .users
+                .try_update_untracked(std::mem::take)
+                .unwrap_or_default();
+            *self.core.state_mut() = Some(SyncData {
+                user_id,
+                schema_version: self.schema_version,
+                state: StateWrapper { state, users },
+            });
+        }
+    }
+
+    fn update_status(&self) {
+        let status = self.core.status();
+        if self.status.with_untracked(|current| current != status) {
+            let status = status.clone();
+            set_later(self.status, |current| *current = status);
+        }
+    }
+
+    fn take_back_state(&mut self) {
+        if let Some(SyncData {
+            user_id,
+            schema_version,
+            state: StateWrapper { state, users },
+        }) = self.core.state_mut().take()
+        {
+            self.schema_version = schema_version;
+            set_later(self.user_id, |current| *current = Some(user_id));
+            set_later(self.users, |current| *current = users);
+            set_later(self.state, |current| *current = Some(state));
+        }
+    }
+}
+
+impl<S: State + Serialize + DeserializeOwned> SocketEvents for Connection<S> {
+    fn socket(&mut self) -> &mut ReconnectingWebSocket {
+        &mut self.socket
+    }
+
+    fn opened(&mut self) {
+        for req in self.core.opened() {
+            self.request(req);
+        }
+        self.update_status();
+    }
+
+    fn received(&mut self, bytes: Vec<u8>) {
+        let res = match decode(&bytes) {
+            Ok(res) => res,
+            Err(err) => {
+                if let Some(outdated) = mismatched_sync::<S>(&bytes) {
+                    log(&format!("{outdated}, reloading"));
+                    reload();
+                    return;
+                }
+                for req in self.core.decode_failed(err) {
+                    self.request(req);
+                }
+                if self.core.take_reconnect() {
+                    self.socket.restart("state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.socket.close();
+                }
+                self.update_status();
+                return;
+            }
+        };
+        let changes_state = matches!(
+            res,
+            Res::Sync(_) | Res::Event(_) | Res::UserUpdate(_) | Res::Rejected(..)
+        );
+
+        if changes_state {
+            self.lend_state();
+        }
+        let received = self.core.receive(res);
+        if changes_state {
+            self.take_back_state();
+        }
+
+        match received {
+            Ok(requests) => {
+                for req in requests {
+                    self.request(req);
+                }
+                if self.core.take_reconnect() {
+                    self.socket.restart("state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.socket.close();
+                }
+            }
+            Err(outdated) => {
+                log(&format!("{outdated}, reloading"));
+                reload();
+            }
+        }
+
+        let messages = self.core.take_messages();
+        if !messages.is_empty() {
+            set_later(self.messages, |current| current.extend(messages));
+        }
+        let rejections = self.core.take_rejections();
+        if !rejections.is_empty() {
+            set_later(self.rejections, |current| current.extend(rejections));
+        }
+        // Like effects, handlers may dispatch events.
+        let received = self.core.take_extensions();
+        if !received.is_empty() {
+            let extensions = self.extensions.clone();
+            queue_microtask(move || {
+                for (kind, payload) in received {
+                    if let Err(err) = extensions.borrow_mut().dispatch(&kind, &payload) {
+                        log(&err);
+                    }
+                }
+            });
+        }
+        let latency = self.core.latency();
+        if self.latency.get_untracked() != latency {
+            set_later(self.latency, |current| *current = latency);
+        }
+        self.update_status();
+    }
+
+    fn closed(&mut self, reason: String) {
+        self.core.closed(reason);
+        self.update_status();
+    }
+
+    fn reconnecting(&mut self, attempt: usize) {
+        self.core.reconnecting(attempt);
+        self.update_status();
+    }
+
+    fn heartbeat(&mut self) -> bool {
+        if let Some(req) = self.core.heartbeat() {
+            self.request(req);
+        }
+        let dead = self.core.take_reconnect();
+        if dead {
+            self.update_status();
+        }
+        dead
+    }
+
+    fn visibility_changed(&mut self, hidden: bool) {
+        if hidden {
+            self.core.hidden();
+            return;
+        }
+        for req in self.core.visible() {
+            self.request(req);
+        }
+        self.update_status();
+    }
+}
+
+// The synced state as signals, created with `create_game_state`. It's `Copy`
+// like the signals, so it can be moved into closures and passed to child
+// components.
+pub struct GameState<S: State> {
+    pub state: RwSignal<Option<S>>,
+    pub user_id: RwSignal<Option<S::UserId>>,
+    pub users: RwSignal<CustomMap<S::UserId, S::UserData>>,
+    pub status: RwSignal<ConnectionStatus>,
+    // The round trip time to the server.
+    pub latency: RwSignal<Option<Duration>>,
+    // Messages from the server, already rendered in the user's language.
+    pub messages: RwSignal<Vec<String>>,
+    // Own events the server didn't apply, with the reasons.
+    pub rejections: RwSignal<Vec<(S::ClientEvent, String)>>,
+    connection: StoredValue<Rc<RefCell<Connection<S>>>>,
+}
+
+impl<S: State> Clone for GameState<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: State> Copy for GameState<S> {}
+
+impl<S: State + Serialize + DeserializeOwned> GameState<S> {
+    pub fn dispatch(&self, event: S::ClientEvent) {
+        self.connection.with_value(|connection| {
+            let mut connection = connection.borrow_mut();
+            if let Some(req) = connection.core.send_event(event) {
+                connection.request(req);
+            }
+        });
+    }
+
+    pub fn dispatch_events(&self, events: Vec<S::ClientEvent>) {
+        self.connection.with_value(|connection| {
+            let mut connection = connection.borrow_mut();
+            for req in connection.core.send_events(events) {
+                connection.request(req);
+            }
+        });
+    }
+
+    // Undoes the last own event that isn't confirmed yet, see
+    // `ClientCore::undo`.
+    pub fn undo(&self) -> Option<S::ClientEvent> {
+        self.connection.with_value(|connection| {
+            let mut connection = connection.borrow_mut();
+            let (event, req) = connection.core.undo()?;
+            if let Some(req) = req {
+                connection.request(req);
+            }
+            Some(event)
+        })
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.connection
+            .with_value(|connection| connection.borrow().core.can_undo())
+    }
+
+    pub fn take_messages(&self) -> Vec<String> {
+        self.messages.try_update(std::mem::take).unwrap_or_default()
+    }
+
+    // Called with the payloads of the extension messages of the kind, see
+    // `Extensions`.
+    pub fn on_extension<T, F>(&self, kind: impl Into<String>, handler: F)
+    where
+        T: DeserializeOwned,
+        F: FnMut(T) + 'static,
+    {
+        self.connection.with_value(|connection| {
+            let connection = connection.borrow();
+            let mut extensions = connection.extensions.borrow_mut();
+            extensions.on(kind, handler);
+        });
+    }
+
+    // Not a signal, since it changes all the time. Read it when rendering,
+    // e.g. on an interval.
+    pub fn server_now(&self) -> Option<SystemTime> {
+        self.connection
+            .with_value(|connection| connection.borrow().core.server_now())
+    }
+
+    // The last applied events, oldest first. It changes with the state, so
+    // it's up to date when read along with it.
+    pub fn feed(&self) -> Vec<FeedEntry<S>> {
+        self.connection
+            .with_value(|connection| connection.borrow().core.feed().cloned().collect())
+    }
+
+    pub fn set_feed_len(&self, feed_len: usize) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().core.set_feed_len(feed_len));
+    }
+
+    pub fn take_rejections(&self) -> Vec<(S::ClientEvent, String)> {
+        self.rejections
+            .try_update(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn set_locales(&self, locales: Vec<Locale>) {
+        self.connection.with_value(|connection| {
+            let mut connection = connection.borrow_mut();
+            let req = connection.core.set_locales(locales);
+            connection.request(req);
+        });
+        // Notifies the views so they pick up the locales.
+        self.state.update(|_| {});
+    }
+
+    // Watches the game without joining it. Events aren't sent.
+    pub fn set_spectator(&self, spectator: bool) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().core.set_spectator(spectator));
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.connection
+            .with_value(|connection| connection.borrow().core.is_spectator())
+    }
+
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().socket.set_policy(policy));
+    }
+
+    pub fn set_resync_policy(&self, policy: ResyncPolicy) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().core.set_resync_policy(policy));
+    }
+
+    pub fn set_acknowledged(&self, acknowledged: bool) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().core.set_acknowledged(acknowledged));
+    }
+
+    // Called once the reconnect policy ran out of retries, after `status`
+    // became `Closed`.
+    pub fn on_give_up(&self, on_give_up: impl Fn() + 'static) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().socket.set_on_give_up(on_give_up));
+    }
+
+    pub fn close(&self) {
+        self.connection
+            .with_value(|connection| connection.borrow_mut().socket.close());
+    }
+}
+
+// Connects to the server. The connection is closed when the current reactive
+// scope is cleaned up.
+pub fn create_game_state<S>(ws_path: &str) -> GameState<S>
+where
+    S: State + Serialize + DeserializeOwned,
+{
+    let connection = Rc::new(RefCell::new(Connection {
+        socket: ReconnectingWebSocket::new(ws_path.to_owned()),
+        core: ClientCore::new(log, js_sys::Date::now),
+        schema_version: S::SCHEMA_VERSION,
+        state: create_rw_signal(None),
+        user_id: create_rw_signal(None),
+        users: create_rw_signal(CustomMap::default()),
+        status: create_rw_signal(ConnectionStatus::Connecting),
+        latency: create_rw_signal(None),
+        messages: create_rw_signal(Vec::new()),
+        rejections: create_rw_signal(Vec::new()),
+        extensions: Rc::default(),
+    }));
+    ReconnectingWebSocket::open(&connection);
+
+    let game_state = {
+        let signals = connection.borrow();
+        GameState {
+            state: signals.state,
+            user_id: signals.user_id,
+            users: signals.users,
+            status: signals.status,
+            latency: signals.latency,
+            messages: signals.messages,
+            rejections: signals.rejections,
+            connection: store_value(Rc::clone(&connection)),
+        }
+    };
+    on_cleanup(move || connection.borrow_mut().socket.close());
+
+    game_state
+}
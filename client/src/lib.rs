@@ -1,187 +1,50 @@
-use std::rc::Rc;
-
-use engine_shared::{
-    utils::custom_map::CustomMap, ClientEvent, EventData, Req, Res, State, SyncData,
+#[cfg(any(feature = "yew", feature = "leptos", feature = "worker"))]
+mod browser;
+mod core;
+#[cfg(feature = "devtools")]
+mod devtools;
+mod extensions;
+#[cfg(feature = "indexed-db")]
+mod indexed_db;
+#[cfg(feature = "leptos")]
+mod leptos_client;
+#[cfg(feature = "seed")]
+mod multi_client;
+#[cfg(feature = "native")]
+mod native;
+mod observe;
+#[cfg(feature = "seed")]
+mod replay;
+#[cfg(feature = "seed")]
+mod seed_client;
+#[cfg(feature = "seed")]
+mod snapshot;
+#[cfg(feature = "seed")]
+mod transport;
+#[cfg(feature = "worker")]
+mod worker;
+#[cfg(feature = "yew")]
+mod yew_client;
+
+pub use crate::core::{
+    BufferSizes, ClientCore, ConnectionStatus, DivergenceReport, FeedEntry, Outdated,
+    ReconnectPolicy, ResyncPolicy,
 };
-use seed::{prelude::*, *};
-use serde::{de::DeserializeOwned, Serialize};
-
-pub struct ClientState<S: State> {
-    web_socket: WebSocket,
-    web_socket_reconnector: Option<StreamHandle>,
-    state: Option<SyncData<S>>,
-    ws_path: String,
-}
-
-pub trait Msg<S: State>: 'static + From<EventWrapper<S>> {
-    fn send_event(event: S::ClientEvent) -> Self
-    where
-        Self: Sized,
-    {
-        Self::from(EventWrapper::SendGameEvent(event))
-    }
-}
-
-impl<S: State> ClientState<S> {
-    pub fn init<M: Msg<S>>(orders: &mut impl Orders<M>, ws_path: String) -> Self
-    where
-        S: DeserializeOwned,
-    {
-        let web_socket = Self::create_websocket(orders, &ws_path);
-
-        ClientState {
-            web_socket,
-            web_socket_reconnector: None,
-            state: None,
-            ws_path,
-        }
-    }
-
-    pub fn get_state(&self) -> Option<&S> {
-        self.state.as_ref().map(|data| &data.state.state)
-    }
-
-    pub fn get_user_id(&self) -> Option<&S::UserId> {
-        self.state.as_ref().map(|data| &data.user_id)
-    }
-
-    pub fn get_user_data(&self, user_id: &S::UserId) -> Option<&S::UserData> {
-        self.state
-            .as_ref()
-            .and_then(|data| data.state.users.get(user_id))
-    }
-
-    pub fn update<M: Msg<S>>(&mut self, msg: EventWrapper<S>, orders: &mut impl Orders<M>)
-    where
-        S: DeserializeOwned + Serialize,
-    {
-        let web_socket = &self.web_socket;
-        let send = |event| {
-            let serialized = rmp_serde::to_vec(&Req::<S>::Event(event)).unwrap();
-            web_socket.send_bytes(&serialized).unwrap();
-        };
-
-        let sync = || {
-            let serialized = rmp_serde::to_vec(&Req::<S>::Sync).unwrap();
-            web_socket.send_bytes(&serialized).unwrap();
-        };
-
-        match msg {
-            EventWrapper::WebSocketOpened => {
-                self.web_socket_reconnector = None;
-                log!("WebSocket connection is open now");
-
-                sync();
-                send(<S::ClientEvent as ClientEvent>::init());
-            }
-            EventWrapper::CloseWebSocket => {
-                self.web_socket_reconnector = None;
-                self.web_socket
-                    .close(None, Some("user clicked close button"))
-                    .unwrap();
-            }
-            EventWrapper::WebSocketClosed(close_event) => {
-                log!(
-                    "WebSocket connection was closed, reason:",
-                    close_event.reason()
-                );
-
-                // Chrome doesn't invoke `on_error` when the connection is lost.
-                if (!close_event.was_clean() || close_event.code() == 4000)
-                    && self.web_socket_reconnector.is_none()
-                {
-                    self.web_socket_reconnector = Some(orders.stream_with_handle(
-                        streams::backoff(None, |retries| M::from(EventWrapper::<S>::ReconnectWebSocket(retries))),
-                    ));
-                }
-            }
-            EventWrapper::WebSocketFailed => {
-                log!("WebSocket failed");
-                if self.web_socket_reconnector.is_none() {
-                    self.web_socket_reconnector = Some(orders.stream_with_handle(
-                        streams::backoff(None, |retries| M::from(EventWrapper::<S>::ReconnectWebSocket(retries))),
-                    ));
-                }
-            }
-            EventWrapper::ReconnectWebSocket(retries) => {
-                log!("Reconnect attempt:", retries);
-                self.web_socket = Self::create_websocket(orders, &self.ws_path);
-            }
-            EventWrapper::SendGameEvent(event) => send(event),
-            EventWrapper::InitGameState(sync_data) => {
-                self.state = Some(sync_data);
-            }
-            EventWrapper::ReceiveGameEvent(event) => {
-                if let Some(SyncData { state, .. }) = &mut self.state {
-                    if state.update_checked(event).is_err() {
-                        log!("invalid state");
-                        //web_socket.close(Some(4000), Some("invalid state")).unwrap();
-                        sync();
-                    }
-                }
-            }
-            EventWrapper::UserUpdate(map) => {
-                if let Some(SyncData { state, .. }) = &mut self.state {
-                    state.users = map;
-                }
-            }
-        }
-    }
-
-    fn create_websocket<M: Msg<S>>(orders: &impl Orders<M>, ws_path: &str) -> WebSocket
-    where
-        S: DeserializeOwned,
-    {
-        let msg_sender = orders.msg_sender();
-
-        WebSocket::builder(ws_path, orders)
-            .on_open(|| M::from(EventWrapper::<S>::WebSocketOpened))
-            .on_message(move |msg| Self::decode_message(msg, msg_sender))
-            .on_close(|evt| M::from(EventWrapper::<S>::WebSocketClosed(evt)))
-            .on_error(|| M::from(EventWrapper::<S>::WebSocketFailed))
-            .build_and_open()
-            .expect("couldn't build WebSocket")
-    }
-
-    fn decode_message<M: Msg<S>>(message: WebSocketMessage, msg_sender: Rc<dyn Fn(Option<M>)>)
-    where
-        S: DeserializeOwned,
-    {
-        if message.contains_text() {
-            unreachable!()
-        } else {
-            spawn_local(async move {
-                let bytes = message
-                    .bytes()
-                    .await
-                    .expect("WebsocketError on binary data");
-
-                let msg: Res<S> = rmp_serde::from_slice(&bytes).unwrap();
-                match msg {
-                    Res::Event(event) => {
-                        msg_sender(Some(M::from(EventWrapper::ReceiveGameEvent(event))));
-                    }
-                    Res::Sync(sync) => {
-                        msg_sender(Some(M::from(EventWrapper::InitGameState(sync))));
-                    }
-                    Res::UserUpdate(map) => {
-                        msg_sender(Some(M::from(EventWrapper::UserUpdate(map))));
-                    }
-                }
-            });
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum EventWrapper<S: State> {
-    WebSocketOpened,
-    CloseWebSocket,
-    WebSocketClosed(CloseEvent),
-    WebSocketFailed,
-    ReconnectWebSocket(usize),
-    SendGameEvent(S::ClientEvent),
-    ReceiveGameEvent(EventData<S>),
-    InitGameState(SyncData<S>),
-    UserUpdate(CustomMap<S::UserId, S::UserData>),
-}
+pub use extensions::Extensions;
+#[cfg(feature = "leptos")]
+pub use leptos_client::{create_game_state, GameState};
+#[cfg(feature = "seed")]
+pub use multi_client::{MultiClientState, MultiEventWrapper, MultiMsg};
+#[cfg(feature = "native")]
+pub use native::{NativeClient, NativeError};
+pub use observe::{ObserverId, Observers};
+#[cfg(feature = "seed")]
+pub use replay::ReplayTransport;
+#[cfg(feature = "seed")]
+pub use seed_client::{ClientBuilder, ClientState, EventWrapper, Msg};
+#[cfg(feature = "seed")]
+pub use transport::{ClientTransport, MockTransport, TransportError, WebSocketTransport};
+#[cfg(feature = "worker")]
+pub use worker::{run_state_worker, StateWorker};
+#[cfg(feature = "yew")]
+pub use yew_client::{use_game_state, UseGameStateHandle};
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use engine_shared::{GameId, State};
+use seed::{app::OrdersProxy, prelude::*};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ClientState, ClientTransport, EventWrapper, Msg, WebSocketTransport};
+
+// A `ClientState` routes its messages through the proxy of a
+// `MultiClientState`, which adds the game they belong to.
+impl<S: State> Msg<S> for EventWrapper<S> {}
+
+#[derive(Debug, Clone)]
+pub struct MultiEventWrapper<S: State> {
+    pub game_id: GameId,
+    pub event: EventWrapper<S>,
+}
+
+pub trait MultiMsg<S: State>: 'static + From<MultiEventWrapper<S>> {
+    fn send_event(game_id: GameId, event: S::ClientEvent) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(MultiEventWrapper {
+            game_id,
+            event: EventWrapper::SendGameEvent(event),
+        })
+    }
+
+    fn send_events(game_id: GameId, events: Vec<S::ClientEvent>) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(MultiEventWrapper {
+            game_id,
+            event: EventWrapper::SendGameEvents(events),
+        })
+    }
+
+    fn undo(game_id: GameId) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(MultiEventWrapper {
+            game_id,
+            event: EventWrapper::Undo,
+        })
+    }
+}
+
+// Connections to several games at once, e.g. to show the maps of allied
+// worlds next to the own one.
+pub struct MultiClientState<S: State, T: ClientTransport<S> = WebSocketTransport> {
+    clients: HashMap<GameId, ClientState<S, T>>,
+}
+
+impl<S: State, T: ClientTransport<S>> Default for MultiClientState<S, T> {
+    fn default() -> Self {
+        MultiClientState {
+            clients: HashMap::new(),
+        }
+    }
+}
+
+impl<S: State> MultiClientState<S> {
+    pub fn connect<M: MultiMsg<S>>(
+        &mut self,
+        orders: &mut impl Orders<M>,
+        game_id: GameId,
+        ws_path: String,
+    ) -> &mut ClientState<S>
+    where
+        S: DeserializeOwned,
+    {
+        let client = ClientState::init(&mut Self::proxy(orders, game_id), ws_path);
+        self.insert(game_id, client)
+    }
+}
+
+impl<S: State, T: ClientTransport<S>> MultiClientState<S, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The orders to build a client for the given game with, e.g. to connect
+    // with another transport or options.
+    pub fn proxy<'a, M: MultiMsg<S>, O: Orders<M>>(
+        orders: &'a mut O,
+        game_id: GameId,
+    ) -> OrdersProxy<'a, EventWrapper<S>, O::AppMs, O::Mdl, O::INodes> {
+        orders.proxy(move |event| M::from(MultiEventWrapper { game_id, event }))
+    }
+
+    // Replaces the client of the game if there already is one.
+    pub fn insert(&mut self, game_id: GameId, client: ClientState<S, T>) -> &mut ClientState<S, T> {
+        self.clients.insert(game_id, client);
+        self.clients.get_mut(&game_id).unwrap()
+    }
+
+    pub fn disconnect<M: MultiMsg<S>>(&mut self, orders: &mut impl Orders<M>, game_id: GameId)
+    where
+        S: DeserializeOwned + Serialize,
+    {
+        if let Some(mut client) = self.clients.remove(&game_id) {
+            client.update(
+                EventWrapper::CloseWebSocket,
+                &mut Self::proxy(orders, game_id),
+            );
+        }
+    }
+
+    pub fn get(&self, game_id: GameId) -> Option<&ClientState<S, T>> {
+        self.clients.get(&game_id)
+    }
+
+    pub fn get_mut(&mut self, game_id: GameId) -> Option<&mut ClientState<S, T>> {
+        self.clients.get_mut(&game_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GameId, &ClientState<S, T>)> {
+        self.clients
+            .iter()
+            .map(|(game_id, client)| (*game_id, client))
+    }
+
+    pub fn update<M: MultiMsg<S>>(
+        &mut self,
+        MultiEventWrapper { game_id, event }: MultiEventWrapper<S>,
+        orders: &mut impl Orders<M>,
+    ) where
+        S: DeserializeOwned + Serialize,
+    {
+        // Messages of disconnected games can still arrive.
+        if let Some(client) = self.clients.get_mut(&game_id) {
+            client.update(event, &mut Self::proxy(orders, game_id));
+        }
+    }
+}
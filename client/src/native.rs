@@ -0,0 +1,311 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use engine_shared::{Req, Res, SchemaVersion, State};
+use futures_util::{SinkExt, StreamExt};
+use i18n::Locale;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::core::{
+    decode, encode, mismatched_sync, ClientCore, ConnectionStatus, DivergenceReport, FeedEntry,
+    Outdated, ResyncPolicy,
+};
+
+#[derive(Debug)]
+pub enum NativeError {
+    Connection(tokio_tungstenite::tungstenite::Error),
+    Decode(rmp_serde::decode::Error),
+    SchemaVersion {
+        client: SchemaVersion,
+        server: SchemaVersion,
+    },
+    Spectating,
+    Rejected(String),
+    Timeout,
+    // The state kept diverging from the server, see
+    // `ConnectionStatus::OutOfDate`.
+    OutOfDate,
+}
+
+impl fmt::Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NativeError::Connection(err) => write!(f, "connection error: {err}"),
+            NativeError::Decode(err) => write!(f, "invalid message: {err}"),
+            NativeError::SchemaVersion { client, server } => Outdated {
+                client: *client,
+                server: *server,
+            }
+            .fmt(f),
+            NativeError::Spectating => write!(f, "spectators can't send events"),
+            NativeError::Rejected(reason) => write!(f, "event was rejected: {reason}"),
+            NativeError::Timeout => write!(f, "server didn't answer ping"),
+            NativeError::OutOfDate => write!(f, "state keeps diverging from the server"),
+        }
+    }
+}
+
+impl std::error::Error for NativeError {}
+
+impl From<tokio_tungstenite::tungstenite::Error> for NativeError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        NativeError::Connection(err)
+    }
+}
+
+impl From<Outdated> for NativeError {
+    fn from(Outdated { client, server }: Outdated) -> Self {
+        NativeError::SchemaVersion { client, server }
+    }
+}
+
+// The client for ordinary Rust programs such as bots, admin tools and
+// integration tests. It mirrors the state like `ClientState` does in the
+// browser.
+pub struct NativeClient<S: State> {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    core: ClientCore<S>,
+}
+
+impl<S: State + Serialize + DeserializeOwned> NativeClient<S> {
+    pub async fn connect(url: &str) -> Result<Self, NativeError> {
+        Self::open(url, false).await
+    }
+
+    // Connects without joining the game, so no events can be sent.
+    pub async fn spectate(url: &str) -> Result<Self, NativeError> {
+        Self::open(url, true).await
+    }
+
+    async fn open(url: &str, spectator: bool) -> Result<Self, NativeError> {
+        let (socket, _) = connect_async(url).await?;
+        let mut client = NativeClient {
+            socket,
+            core: ClientCore::new(
+                |_| {},
+                || {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                    now.unwrap_or_default().as_secs_f64() * 1000.0
+                },
+            ),
+        };
+        client.core.set_spectator(spectator);
+
+        for req in client.core.opened() {
+            client.request(&req).await?;
+        }
+
+        Ok(client)
+    }
+
+    pub fn set_optimistic(&mut self, optimistic: bool) {
+        self.core.set_optimistic(optimistic);
+    }
+
+    pub fn set_acknowledged(&mut self, acknowledged: bool) {
+        self.core.set_acknowledged(acknowledged);
+    }
+
+    pub fn set_resync_policy(&mut self, resync_policy: ResyncPolicy) {
+        self.core.set_resync_policy(resync_policy);
+    }
+
+    pub fn set_validate(&mut self, validate: bool) {
+        self.core.set_validate(validate);
+    }
+
+    pub fn get_state(&self) -> Option<&S> {
+        self.core.get_state()
+    }
+
+    pub fn get_confirmed_state(&self) -> Option<&S> {
+        self.core.get_confirmed_state()
+    }
+
+    pub fn get_user_id(&self) -> Option<&S::UserId> {
+        self.core.get_user_id()
+    }
+
+    pub fn get_user_data(&self, user_id: &S::UserId) -> Option<&S::UserData> {
+        self.core.get_user_data(user_id)
+    }
+
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.core.take_messages()
+    }
+
+    pub fn take_rejections(&mut self) -> Vec<(S::ClientEvent, String)> {
+        self.core.take_rejections()
+    }
+
+    // Extension messages with their kinds and encoded payloads. They're also
+    // returned by `poll`.
+    pub fn take_extensions(&mut self) -> Vec<(String, Vec<u8>)> {
+        self.core.take_extensions()
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.core.is_online()
+    }
+
+    pub fn divergence_report(&self) -> Option<&DivergenceReport> {
+        self.core.divergence_report()
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        self.core.latency()
+    }
+
+    pub fn server_now(&self) -> Option<SystemTime> {
+        self.core.server_now()
+    }
+
+    pub fn feed(&self) -> impl DoubleEndedIterator<Item = &FeedEntry<S>> {
+        self.core.feed()
+    }
+
+    pub fn set_feed_len(&mut self, feed_len: usize) {
+        self.core.set_feed_len(feed_len);
+    }
+
+    pub fn status(&self) -> &ConnectionStatus {
+        self.core.status()
+    }
+
+    pub fn queued_events(&self) -> impl Iterator<Item = &S::ClientEvent> {
+        self.core.queued_events()
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.core.is_spectator()
+    }
+
+    pub async fn send_event(&mut self, event: S::ClientEvent) -> Result<(), NativeError> {
+        if self.core.is_spectator() {
+            return Err(NativeError::Spectating);
+        }
+        self.core.validate(&event).map_err(NativeError::Rejected)?;
+        match self.core.send_event(event) {
+            Some(req) => self.request(&req).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn send_events(&mut self, events: Vec<S::ClientEvent>) -> Result<(), NativeError> {
+        if self.core.is_spectator() {
+            return Err(NativeError::Spectating);
+        }
+        self.core
+            .validate_events(&events)
+            .map_err(|(_, reason)| NativeError::Rejected(reason))?;
+        for req in self.core.send_events(events) {
+            self.request(&req).await?;
+        }
+        Ok(())
+    }
+
+    // Undoes the last own event that isn't confirmed yet, see
+    // `ClientCore::undo`.
+    pub async fn undo(&mut self) -> Result<Option<S::ClientEvent>, NativeError> {
+        let Some((event, req)) = self.core.undo() else {
+            return Ok(None);
+        };
+        if let Some(req) = req {
+            self.request(&req).await?;
+        }
+        Ok(Some(event))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.core.can_undo()
+    }
+
+    // Sends a ping if one is due. Called periodically next to `poll`, e.g.
+    // with a tokio interval, it notices when the connection is dead.
+    pub async fn heartbeat(&mut self) -> Result<(), NativeError> {
+        match self.core.heartbeat() {
+            Some(req) => self.request(&req).await,
+            None if self.core.take_reconnect() => Err(NativeError::Timeout),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn set_locales(&mut self, locales: Vec<Locale>) -> Result<(), NativeError> {
+        let req = self.core.set_locales(locales);
+        self.request(&req).await
+    }
+
+    async fn request(&mut self, req: &Req<S>) -> Result<(), NativeError> {
+        self.socket.send(Message::Binary(encode(req))).await?;
+        Ok(())
+    }
+
+    // Waits for the next response of the server and applies it to the state,
+    // or returns `None` once the connection is closed.
+    pub async fn poll(&mut self) -> Result<Option<Res<S>>, NativeError> {
+        loop {
+            let bytes = match self.socket.next().await {
+                Some(Ok(Message::Binary(bytes))) => bytes,
+                Some(Ok(Message::Close(frame))) => {
+                    let reason = frame.map(|frame| frame.reason.into_owned());
+                    self.core.closed(reason.unwrap_or_default());
+                    return Ok(None);
+                }
+                None => {
+                    self.core.closed("connection lost".to_owned());
+                    return Ok(None);
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => {
+                    self.core.closed(err.to_string());
+                    return Err(err.into());
+                }
+            };
+            let res: Res<S> = match decode(&bytes) {
+                Ok(res) => res,
+                Err(err) => {
+                    if let Some(outdated) = mismatched_sync::<S>(&bytes) {
+                        return Err(outdated.into());
+                    }
+                    // Resyncs, so polling can go on after the error.
+                    for req in self.core.decode_failed(&err) {
+                        self.request(&req).await?;
+                    }
+                    if self.core.take_reconnect() {
+                        self.socket.close(None).await?;
+                    }
+                    if self.core.is_out_of_date() {
+                        self.socket.close(None).await?;
+                        return Err(NativeError::OutOfDate);
+                    }
+                    return Err(NativeError::Decode(err));
+                }
+            };
+
+            for req in self.core.receive(res.clone())? {
+                self.request(&req).await?;
+            }
+            // The state keeps diverging, so it's up to the caller to
+            // connect again.
+            if self.core.take_reconnect() {
+                self.socket.close(None).await?;
+                return Ok(None);
+            }
+            if self.core.is_out_of_date() {
+                self.socket.close(None).await?;
+                return Err(NativeError::OutOfDate);
+            }
+
+            return Ok(Some(res));
+        }
+    }
+
+    pub async fn close(mut self) -> Result<(), NativeError> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
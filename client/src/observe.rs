@@ -0,0 +1,68 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+// Returns whether the selection changed.
+type Observer<S> = Box<dyn FnMut(&S) -> bool>;
+
+// Views that only depend on parts of the state, e.g. a resource counter,
+// register a selector for their part and are told whenever it changes, so
+// they don't have to be redrawn on every tick.
+pub struct Observers<S> {
+    next_id: usize,
+    observers: Vec<(ObserverId, Observer<S>)>,
+}
+
+impl<S> Default for Observers<S> {
+    fn default() -> Self {
+        Observers {
+            next_id: 0,
+            observers: Vec::new(),
+        }
+    }
+}
+
+impl<S> Observers<S> {
+    // `on_change` is called with the selection the first time the state is
+    // known, and then whenever the selection changed.
+    pub fn observe<V, F, C>(&mut self, selector: F, mut on_change: C) -> ObserverId
+    where
+        V: PartialEq + 'static,
+        F: Fn(&S) -> V + 'static,
+        C: FnMut(&V) + 'static,
+    {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+
+        let mut selection = None;
+        self.observers.push((
+            id,
+            Box::new(move |state| {
+                let selected = selector(state);
+                if selection.as_ref() == Some(&selected) {
+                    return false;
+                }
+                on_change(&selected);
+                selection = Some(selected);
+                true
+            }),
+        ));
+        id
+    }
+
+    pub fn unobserve(&mut self, id: ObserverId) {
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    // Returns whether any selection changed.
+    pub fn notify(&mut self, state: &S) -> bool {
+        let mut changed = false;
+        for (_, observer) in &mut self.observers {
+            changed |= observer(state);
+        }
+        changed
+    }
+}
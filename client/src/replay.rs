@@ -0,0 +1,41 @@
+use engine_shared::{replay::ReplayPlayer, State};
+use seed::prelude::*;
+use serde::de::DeserializeOwned;
+
+use crate::{ClientTransport, Msg, TransportError};
+
+// Stands in for the connection of a client that plays a replay, see
+// `ClientState::replay`. Requests go nowhere.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayTransport;
+
+impl<S: State> ClientTransport<S> for ReplayTransport {
+    fn send(&self, _bytes: &[u8]) -> Result<(), TransportError> {
+        Ok(())
+    }
+
+    fn close(&self, _reason: &str) -> Result<(), TransportError> {
+        Ok(())
+    }
+
+    fn reconnect<M: Msg<S>>(&mut self, _orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+    }
+
+    fn reconnect_to<M: Msg<S>>(&mut self, _ws_path: String, _orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+    }
+}
+
+pub(crate) struct Playback<S: State> {
+    pub(crate) player: ReplayPlayer<S>,
+    // The user whose view of the game is shown.
+    pub(crate) user_id: S::UserId,
+    pub(crate) speed: f64,
+    // The next step while playing.
+    pub(crate) timer: Option<CmdHandle>,
+}
@@ -0,0 +1,1020 @@
+use std::{
+    convert::TryFrom,
+    time::{Duration, SystemTime},
+};
+
+use engine_shared::{
+    replay::{Replay, ReplayEntry, ReplayPlayer},
+    utils::custom_map::MapDiff,
+    EventData, GameId, Req, Res, State, SyncData,
+};
+use i18n::Locale;
+use seed::{prelude::*, *};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "devtools")]
+use crate::devtools;
+#[cfg(feature = "indexed-db")]
+use crate::indexed_db;
+use crate::{
+    core::{
+        decode, encode, is_tick, mismatched_sync, should_reconnect, BufferSizes, ClientCore,
+        ConnectionStatus, DivergenceReport, FeedEntry, Outdated, ReconnectPolicy, ResyncPolicy,
+        HEARTBEAT_INTERVAL_MS,
+    },
+    replay::Playback,
+    snapshot, ClientTransport, Extensions, ObserverId, Observers, ReplayTransport,
+    WebSocketTransport,
+};
+
+const SNAPSHOT_INTERVAL_MS: f64 = 10_000.0;
+
+pub struct ClientState<S: State, T: ClientTransport<S> = WebSocketTransport> {
+    transport: T,
+    web_socket_reconnector: Option<CmdHandle>,
+    tick_predictor: Option<CmdHandle>,
+    retries: usize,
+    reconnect_policy: ReconnectPolicy,
+    on_give_up: Option<Box<dyn Fn()>>,
+    core: ClientCore<S>,
+    observers: Observers<S>,
+    extensions: Extensions,
+    snapshot_key: Option<String>,
+    last_snapshot: f64,
+    #[cfg(feature = "indexed-db")]
+    indexed_db: Option<indexed_db::Cache>,
+    // The game of the last `join`, if any, which is part of the snapshot keys.
+    game_id: Option<GameId>,
+    playback: Option<Playback<S>>,
+    #[cfg(feature = "devtools")]
+    devtools: bool,
+}
+
+pub trait Msg<S: State>: 'static + From<EventWrapper<S>> {
+    fn send_event(event: S::ClientEvent) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(EventWrapper::SendGameEvent(event))
+    }
+
+    fn send_events(events: Vec<S::ClientEvent>) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(EventWrapper::SendGameEvents(events))
+    }
+
+    // Undoes the last own event that isn't confirmed yet, see
+    // `ClientCore::undo`.
+    fn undo() -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(EventWrapper::Undo)
+    }
+
+    fn set_locales(locales: Vec<Locale>) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from(EventWrapper::SetLocales(locales))
+    }
+}
+
+// Configures a client before it connects, see `ClientState::builder`.
+// Browsers don't allow headers on web sockets, so e.g. tokens go into the path
+// or a protocol.
+pub struct ClientBuilder<S: State> {
+    ws_path: String,
+    protocols: Vec<String>,
+    #[cfg(feature = "compression")]
+    compression: bool,
+    // Applied with the setters of `ClientState` once it's built.
+    options: Vec<ClientOption<S>>,
+    cache: Option<SnapshotCache>,
+}
+
+type ClientOption<S> = Box<dyn FnOnce(ClientState<S>) -> ClientState<S>>;
+
+enum SnapshotCache {
+    LocalStorage(String),
+    #[cfg(feature = "indexed-db")]
+    IndexedDb(String),
+}
+
+impl<S: State> ClientBuilder<S> {
+    pub fn protocols(mut self, protocols: Vec<String>) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    // Offers `engine_shared::compression::PROTOCOL`, so the server can
+    // compress large frames such as syncs. The server has to accept it.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn buffer_sizes(self, buffer_sizes: BufferSizes) -> Self {
+        self.with(move |client| client.buffer_sizes(buffer_sizes))
+    }
+
+    pub fn reconnect_policy(self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.with(move |client| client.reconnect_policy(reconnect_policy))
+    }
+
+    pub fn on_give_up(self, on_give_up: impl Fn() + 'static) -> Self {
+        self.with(move |client| client.on_give_up(on_give_up))
+    }
+
+    pub fn resync_policy(self, resync_policy: ResyncPolicy) -> Self {
+        self.with(move |client| client.resync_policy(resync_policy))
+    }
+
+    pub fn acknowledged(self, acknowledged: bool) -> Self {
+        self.with(move |client| client.acknowledged(acknowledged))
+    }
+
+    pub fn optimistic(self, optimistic: bool) -> Self {
+        self.with(move |client| client.optimistic(optimistic))
+    }
+
+    pub fn predict_ticks(self, predict_ticks: bool) -> Self {
+        self.with(move |client| client.predict_ticks(predict_ticks))
+    }
+
+    pub fn spectator(self, spectator: bool) -> Self {
+        self.with(move |client| client.spectator(spectator))
+    }
+
+    pub fn validate_events(self, validate: bool) -> Self {
+        self.with(move |client| client.validate_events(validate))
+    }
+
+    pub fn feed_len(self, feed_len: usize) -> Self {
+        self.with(move |mut client| {
+            client.set_feed_len(feed_len);
+            client
+        })
+    }
+
+    // See `ClientState::cache_snapshots`.
+    pub fn cache_snapshots(mut self, key: impl Into<String>) -> Self {
+        self.cache = Some(SnapshotCache::LocalStorage(key.into()));
+        self
+    }
+
+    // See `ClientState::cache_snapshots_in_indexed_db`.
+    #[cfg(feature = "indexed-db")]
+    pub fn cache_snapshots_in_indexed_db(mut self, key: impl Into<String>) -> Self {
+        self.cache = Some(SnapshotCache::IndexedDb(key.into()));
+        self
+    }
+
+    fn with(mut self, option: impl FnOnce(ClientState<S>) -> ClientState<S> + 'static) -> Self {
+        self.options.push(Box::new(option));
+        self
+    }
+
+    pub fn build<M: Msg<S>>(self, orders: &mut impl Orders<M>) -> ClientState<S>
+    where
+        S: DeserializeOwned,
+    {
+        #[cfg(feature = "devtools")]
+        orders.stream(streams::window_event(Ev::KeyDown, |event| {
+            let event: web_sys::KeyboardEvent = event.unchecked_into();
+            (event.key() == devtools::TOGGLE_KEY)
+                .then(|| M::from(EventWrapper::<S>::ToggleDevtools))
+        }));
+        orders.stream(streams::interval(HEARTBEAT_INTERVAL_MS, || {
+            M::from(EventWrapper::<S>::Heartbeat)
+        }));
+        orders.stream(streams::document_event(Ev::VisibilityChange, |_| {
+            M::from(EventWrapper::<S>::VisibilityChanged(document().hidden()))
+        }));
+
+        let protocols = self.protocols;
+        #[cfg(feature = "compression")]
+        let protocols = {
+            let mut protocols = protocols;
+            if self.compression {
+                protocols.push(engine_shared::compression::PROTOCOL.to_owned());
+            }
+            protocols
+        };
+        let transport =
+            WebSocketTransport::connect_with_protocols::<S, M>(orders, self.ws_path, protocols);
+        let client = self
+            .options
+            .into_iter()
+            .fold(ClientState::with_transport(transport), |client, option| {
+                option(client)
+            });
+        match self.cache {
+            Some(SnapshotCache::LocalStorage(key)) => client.cache_snapshots(key),
+            #[cfg(feature = "indexed-db")]
+            Some(SnapshotCache::IndexedDb(key)) => {
+                client.cache_snapshots_in_indexed_db(orders, key)
+            }
+            None => client,
+        }
+    }
+}
+
+impl<S: State> ClientState<S> {
+    // Shorthand for a client with the default configuration.
+    pub fn init<M: Msg<S>>(orders: &mut impl Orders<M>, ws_path: String) -> Self
+    where
+        S: DeserializeOwned,
+    {
+        Self::builder(ws_path).build(orders)
+    }
+
+    pub fn builder(ws_path: impl Into<String>) -> ClientBuilder<S> {
+        ClientBuilder {
+            ws_path: ws_path.into(),
+            protocols: Vec::new(),
+            #[cfg(feature = "compression")]
+            compression: false,
+            options: Vec::new(),
+            cache: None,
+        }
+    }
+}
+
+impl<S: State> ClientState<S, ReplayTransport> {
+    // Plays a recorded match instead of connecting to a server, as seen by
+    // the given user. It's paused at the start until `play` is called.
+    pub fn replay<M: Msg<S>>(
+        orders: &mut impl Orders<M>,
+        replay: Replay<S>,
+        user_id: S::UserId,
+    ) -> Self
+    where
+        S: Serialize,
+    {
+        let mut client = Self::with_transport(ReplayTransport).spectator(true);
+        client.playback = Some(Playback {
+            player: ReplayPlayer::new(replay),
+            user_id,
+            speed: 1.0,
+            timer: None,
+        });
+        client.sync_playback(orders);
+        client
+    }
+}
+
+impl<S: State, T: ClientTransport<S>> ClientState<S, T> {
+    pub fn with_transport(transport: T) -> Self {
+        ClientState {
+            transport,
+            web_socket_reconnector: None,
+            tick_predictor: None,
+            retries: 0,
+            reconnect_policy: ReconnectPolicy::default(),
+            on_give_up: None,
+            core: new_core(),
+            observers: Observers::default(),
+            extensions: Extensions::default(),
+            snapshot_key: None,
+            last_snapshot: 0.0,
+            #[cfg(feature = "indexed-db")]
+            indexed_db: None,
+            game_id: None,
+            playback: None,
+            #[cfg(feature = "devtools")]
+            devtools: false,
+        }
+    }
+
+    pub fn buffer_sizes(mut self, buffer_sizes: BufferSizes) -> Self {
+        self.core.set_buffer_sizes(buffer_sizes);
+        self
+    }
+
+    pub fn reconnect_policy(mut self, reconnect_policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
+
+    // Called once the reconnect policy ran out of retries, e.g. to send a
+    // message with `orders.msg_sender()`.
+    pub fn on_give_up(mut self, on_give_up: impl Fn() + 'static) -> Self {
+        self.on_give_up = Some(Box::new(on_give_up));
+        self
+    }
+
+    pub fn resync_policy(mut self, resync_policy: ResyncPolicy) -> Self {
+        self.core.set_resync_policy(resync_policy);
+        self
+    }
+
+    // Events are sent again once after reconnecting if the server didn't
+    // acknowledge them, instead of being lost.
+    pub fn acknowledged(mut self, acknowledged: bool) -> Self {
+        self.core.set_acknowledged(acknowledged);
+        self
+    }
+
+    // Own events are applied to a predicted state right away, and ticks are
+    // simulated between the server's ticks if `predict_ticks` is set too.
+    pub fn optimistic(mut self, optimistic: bool) -> Self {
+        self.core.set_optimistic(optimistic);
+        self
+    }
+
+    // Watches the game without joining it. Events aren't sent.
+    pub fn spectator(mut self, spectator: bool) -> Self {
+        self.core.set_spectator(spectator);
+        self
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.core.is_spectator()
+    }
+
+    // Simulates ticks locally between the server's ticks, so timers and
+    // resource counters don't stutter with network jitter.
+    pub fn predict_ticks(mut self, predict_ticks: bool) -> Self {
+        self.core.set_predict_ticks(predict_ticks);
+        self
+    }
+
+    // Events that `State::validate` rejects aren't sent, but reported as
+    // `EventWrapper::EventRejected`.
+    pub fn validate_events(mut self, validate: bool) -> Self {
+        self.core.set_validate(validate);
+        self
+    }
+
+    // Saves the synced state in the local storage under the given key every
+    // few seconds. On the next page load, it's shown right away until the
+    // server syncs.
+    pub fn cache_snapshots(mut self, key: impl Into<String>) -> Self
+    where
+        S: DeserializeOwned,
+    {
+        let key = key.into();
+        if let Some(sync_data) = snapshot::load(&key) {
+            self.core.restore(sync_data);
+        }
+        self.snapshot_key = Some(key);
+        self
+    }
+
+    // Like `cache_snapshots`, but for states that are too large for the
+    // local storage. The snapshot is loaded asynchronously, and the events
+    // since it was saved are applied to it, so the state is shown as it was
+    // when the page was left.
+    #[cfg(feature = "indexed-db")]
+    pub fn cache_snapshots_in_indexed_db<M: Msg<S>>(
+        mut self,
+        orders: &mut impl Orders<M>,
+        key: impl Into<String>,
+    ) -> Self
+    where
+        S: DeserializeOwned,
+    {
+        let cache = indexed_db::Cache::new(key.into());
+        let loaded = cache.open::<S>();
+        orders.perform_cmd(async move {
+            let (sync_data, tail) = loaded.await?;
+            Some(M::from(EventWrapper::RestoreSnapshot(
+                Box::new(sync_data),
+                tail,
+            )))
+        });
+        self.indexed_db = Some(cache);
+        self
+    }
+
+    // Leaves the current game and connects to the given one at the path
+    // instead. The state is cleared until the new game is synced, or shows
+    // the game's own snapshot if snapshots are cached.
+    pub fn join<M: Msg<S>>(&mut self, game_id: GameId, ws_path: String, orders: &mut impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+        self.web_socket_reconnector = None;
+        self.retries = 0;
+        self.last_snapshot = 0.0;
+        self.game_id = Some(game_id);
+        self.core.reset();
+        if let Some(sync_data) = self.snapshot_key().and_then(|key| snapshot::load(&key)) {
+            self.core.restore(sync_data);
+        }
+        #[cfg(feature = "indexed-db")]
+        if let Some(cache) = &mut self.indexed_db {
+            cache.join(game_id);
+            let loaded = cache.load::<S>();
+            orders.perform_cmd(async move {
+                let (sync_data, tail) = loaded.await?;
+                Some(M::from(EventWrapper::RestoreSnapshot(
+                    Box::new(sync_data),
+                    tail,
+                )))
+            });
+        }
+        self.transport.reconnect_to(ws_path, orders);
+        orders.notify(self.core.status().clone());
+    }
+
+    // Snapshots of different games are kept apart.
+    fn snapshot_key(&self) -> Option<String> {
+        let key = self.snapshot_key.as_ref()?;
+        Some(match self.game_id {
+            Some(game_id) => format!("{key}/{game_id}"),
+            None => key.clone(),
+        })
+    }
+
+    // Whether the state is a snapshot of an earlier visit that wasn't synced
+    // yet.
+    pub fn is_stale(&self) -> bool {
+        self.core.is_stale()
+    }
+
+    pub fn get_state(&self) -> Option<&S> {
+        self.core.get_state()
+    }
+
+    pub fn get_confirmed_state(&self) -> Option<&S> {
+        self.core.get_confirmed_state()
+    }
+
+    pub fn get_user_id(&self) -> Option<&S::UserId> {
+        self.core.get_user_id()
+    }
+
+    pub fn get_user_data(&self, user_id: &S::UserId) -> Option<&S::UserData> {
+        self.core.get_user_data(user_id)
+    }
+
+    // Messages from the server, already rendered in the user's language.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.core.take_messages()
+    }
+
+    // Handles the extension messages of the kind, see `Extensions`. The
+    // handler can't use the client, so it usually sends a message to the
+    // app, e.g. with `orders.msg_sender()`.
+    pub fn on_extension<P, F>(&mut self, kind: impl Into<String>, handler: F)
+    where
+        P: DeserializeOwned,
+        F: FnMut(P) + 'static,
+    {
+        self.extensions.on(kind, handler);
+    }
+
+    pub fn remove_extension(&mut self, kind: &str) {
+        self.extensions.remove(kind);
+    }
+
+    // Events are queued while the client is offline and sent once it's
+    // synced again.
+    pub fn is_online(&self) -> bool {
+        self.core.is_online()
+    }
+
+    // Whether `Msg::undo` would undo anything, e.g. to enable its button.
+    pub fn can_undo(&self) -> bool {
+        self.core.can_undo()
+    }
+
+    // Changes are also sent as a notification, so apps can subscribe to them
+    // with `orders.subscribe(|status: ConnectionStatus| ..)`.
+    pub fn status(&self) -> &ConnectionStatus {
+        self.core.status()
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        self.core.latency()
+    }
+
+    pub fn divergence_report(&self) -> Option<&DivergenceReport> {
+        self.core.divergence_report()
+    }
+
+    pub fn server_now(&self) -> Option<SystemTime> {
+        self.core.server_now()
+    }
+
+    // The last applied events with who sent them, e.g. to render an activity
+    // feed. Oldest first.
+    pub fn feed(&self) -> impl DoubleEndedIterator<Item = &FeedEntry<S>> {
+        self.core.feed()
+    }
+
+    pub fn set_feed_len(&mut self, feed_len: usize) {
+        self.core.set_feed_len(feed_len);
+    }
+
+    // Steps through the replay a tick at a time, as fast as the game ran
+    // times the replay speed.
+    pub fn play<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>)
+    where
+        S: Serialize,
+    {
+        if let Some(playback) = &mut self.playback {
+            if playback.timer.is_none() && !playback.player.is_finished() {
+                self.schedule_playback(orders);
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.timer = None;
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback
+            .as_ref()
+            .is_some_and(|playback| playback.timer.is_some())
+    }
+
+    // E.g. 2.0 plays twice as fast as the game ran.
+    pub fn set_replay_speed(&mut self, speed: f64) {
+        if let Some(playback) = &mut self.playback {
+            playback.speed = speed;
+        }
+    }
+
+    // The number of entries of the replay that were played, and of all.
+    pub fn replay_position(&self) -> Option<(usize, usize)>
+    where
+        S: Serialize,
+    {
+        let player = &self.playback.as_ref()?.player;
+        Some((player.position(), player.len()))
+    }
+
+    // Jumps to the state after the given number of entries.
+    pub fn seek<M: Msg<S>>(&mut self, position: usize, orders: &mut impl Orders<M>)
+    where
+        S: Serialize,
+    {
+        if let Some(playback) = &mut self.playback {
+            if let Err(err) = playback.player.seek(position) {
+                log!("couldn't seek replay:", err.to_string());
+            }
+            self.sync_playback(orders);
+        }
+    }
+
+    // Plays the entries up to and including the next tick.
+    pub fn step_replay<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>)
+    where
+        S: Serialize,
+    {
+        while let Some(playback) = &mut self.playback {
+            let entry = match playback.player.step() {
+                Ok(Some(entry)) => entry.clone(),
+                Ok(None) => break,
+                Err(err) => {
+                    log!("replay doesn't match the game:", err.to_string());
+                    playback.timer = None;
+                    break;
+                }
+            };
+            let (res, tick) = match entry {
+                ReplayEntry::Event(event) => {
+                    let tick = is_tick(&event.event);
+                    (Res::Event(event), tick)
+                }
+                ReplayEntry::UserUpdate(diff) => (Res::UserUpdate(diff), false),
+            };
+            self.receive(res, orders);
+            if tick {
+                break;
+            }
+        }
+    }
+
+    fn sync_playback<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>)
+    where
+        S: Serialize,
+    {
+        if let Some(Playback {
+            player, user_id, ..
+        }) = &self.playback
+        {
+            let sync_data = SyncData::new(user_id.clone(), player.state().clone());
+            self.receive(Res::Sync(sync_data), orders);
+        }
+    }
+
+    fn schedule_playback<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>) {
+        if let Some(playback) = &mut self.playback {
+            let ms = S::DURATION_PER_TICK.as_secs_f64() * 1000.0 / playback.speed;
+            let ms = u32::try_from(ms as u64).unwrap_or(u32::MAX);
+            playback.timer = Some(orders.perform_cmd_with_handle(cmds::timeout(ms, || {
+                M::from(EventWrapper::<S>::PlayReplay)
+            })));
+        }
+    }
+
+    // Once something is observed, ticks only redraw the app if one of the
+    // observed selections changed.
+    pub fn observe<V, F, C>(&mut self, selector: F, on_change: C) -> ObserverId
+    where
+        V: PartialEq + 'static,
+        F: Fn(&S) -> V + 'static,
+        C: FnMut(&V) + 'static,
+    {
+        let id = self.observers.observe(selector, on_change);
+        self.notify_observers();
+        id
+    }
+
+    pub fn unobserve(&mut self, id: ObserverId) {
+        self.observers.unobserve(id);
+    }
+
+    // Returns whether the app has to be redrawn.
+    fn notify_observers(&mut self) -> bool {
+        match self.core.get_state() {
+            Some(state) => self.observers.notify(state) || self.observers.is_empty(),
+            None => true,
+        }
+    }
+
+    // An overlay with debug information, toggled with F2. It's empty while
+    // it's hidden.
+    #[cfg(feature = "devtools")]
+    pub fn view_devtools<M: 'static>(&self) -> Node<M>
+    where
+        S: Serialize,
+    {
+        if self.devtools {
+            devtools::view(&self.core)
+        } else {
+            empty![]
+        }
+    }
+
+    fn send(&mut self, req: Req<S>)
+    where
+        S: Serialize,
+    {
+        if let Err(err) = self.transport.send(&encode(&req)) {
+            log!("couldn't send request:", err.to_string());
+            self.core.send_failed(req);
+        }
+    }
+
+    fn schedule_reconnect<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>) {
+        if self.core.is_out_of_date() {
+            return;
+        }
+        if self.web_socket_reconnector.is_some() {
+            // Already scheduled, e.g. after an error that's followed by a
+            // close.
+            self.core.reconnecting(self.retries);
+            return;
+        }
+        self.retries += 1;
+        let retries = self.retries;
+        match self.reconnect_policy.delay(retries, js_sys::Math::random()) {
+            Some(delay) => {
+                self.core.reconnecting(retries);
+                let ms = u32::try_from(delay.as_millis()).unwrap_or(u32::MAX);
+                self.web_socket_reconnector =
+                    Some(orders.perform_cmd_with_handle(cmds::timeout(ms, move || {
+                        M::from(EventWrapper::<S>::ReconnectWebSocket(retries))
+                    })));
+            }
+            None => {
+                let reason = format!("gave up reconnecting after {} attempts", retries - 1);
+                log!(reason);
+                self.core.closed(reason);
+                if let Some(on_give_up) = &self.on_give_up {
+                    on_give_up();
+                }
+            }
+        }
+    }
+
+    // Replaces the scheduled prediction, as the next tick is due a tick
+    // after the last one.
+    fn schedule_tick_prediction<M: Msg<S>>(&mut self, orders: &mut impl Orders<M>) {
+        self.tick_predictor = self.core.next_tick_in().map(|delay| {
+            let ms = u32::try_from(delay.as_millis()).unwrap_or(u32::MAX);
+            orders.perform_cmd_with_handle(cmds::timeout(ms, || {
+                M::from(EventWrapper::<S>::PredictTick)
+            }))
+        });
+    }
+
+    fn receive<M: Msg<S>>(&mut self, res: Res<S>, orders: &mut impl Orders<M>) -> bool
+    where
+        S: Serialize,
+    {
+        #[cfg(feature = "indexed-db")]
+        let synced = matches!(res, Res::Sync(_));
+        #[cfg(feature = "indexed-db")]
+        let tail = matches!(res, Res::Event(_) | Res::UserUpdate(_)).then(|| res.clone());
+        match self.core.receive(res) {
+            Ok(requests) => {
+                for req in requests {
+                    self.send(req);
+                }
+            }
+            Err(outdated) => {
+                orders.send_msg(M::from(EventWrapper::VersionMismatch(outdated)));
+            }
+        }
+        self.save_snapshot();
+        #[cfg(feature = "indexed-db")]
+        if let Some(cache) = &mut self.indexed_db {
+            cache.received(synced, tail, self.core.snapshot());
+        }
+        self.notify_observers()
+    }
+
+    fn save_snapshot(&mut self)
+    where
+        S: Serialize,
+    {
+        let now = js_sys::Date::now();
+        if let (Some(key), Some(sync_data)) = (self.snapshot_key(), self.core.snapshot()) {
+            if now - self.last_snapshot >= SNAPSHOT_INTERVAL_MS {
+                self.last_snapshot = now;
+                if let Err(err) = snapshot::save(&key, sync_data) {
+                    log!("couldn't save snapshot:", err);
+                }
+            }
+        }
+    }
+
+    pub fn update<M: Msg<S>>(&mut self, msg: EventWrapper<S>, orders: &mut impl Orders<M>)
+    where
+        S: DeserializeOwned + Serialize,
+    {
+        let status = self.core.status().clone();
+        self.handle(msg, orders);
+        // Syncing didn't help or the connection is dead, so it's replaced.
+        if self.core.take_reconnect() {
+            self.transport.reconnect(orders);
+        }
+        if self.core.status() != &status {
+            if self.core.is_out_of_date() {
+                self.transport.close("client is out of date").ok();
+            }
+            orders.notify(self.core.status().clone()).render();
+        }
+    }
+
+    fn handle<M: Msg<S>>(&mut self, msg: EventWrapper<S>, orders: &mut impl Orders<M>)
+    where
+        S: DeserializeOwned + Serialize,
+    {
+        match msg {
+            EventWrapper::WebSocketOpened => {
+                self.web_socket_reconnector = None;
+                self.retries = 0;
+                log!("WebSocket connection is open now");
+
+                for req in self.core.opened() {
+                    self.send(req);
+                }
+            }
+            EventWrapper::CloseWebSocket => {
+                self.web_socket_reconnector = None;
+                if let Err(err) = self.transport.close("user clicked close button") {
+                    log!("couldn't close WebSocket:", err.to_string());
+                }
+            }
+            EventWrapper::WebSocketClosed(close_event) => {
+                self.core.closed(close_event.reason());
+                log!(
+                    "WebSocket connection was closed, reason:",
+                    close_event.reason()
+                );
+
+                if should_reconnect(close_event.was_clean(), close_event.code()) {
+                    self.schedule_reconnect(orders);
+                }
+            }
+            EventWrapper::WebSocketFailed => {
+                self.core.closed("WebSocket failed".to_owned());
+                log!("WebSocket failed");
+                self.schedule_reconnect(orders);
+            }
+            EventWrapper::ReconnectWebSocket(retries) => {
+                // The next failure schedules the next attempt.
+                self.web_socket_reconnector = None;
+                log!("Reconnect attempt:", retries);
+                self.transport.reconnect(orders);
+            }
+            EventWrapper::SendGameEvent(event) => {
+                if let Err(reason) = self.core.validate(&event) {
+                    orders.send_msg(M::from(EventWrapper::EventRejected(event, reason)));
+                    return;
+                }
+                if let Some(req) = self.core.send_event(event) {
+                    self.send(req);
+                }
+                // The prediction may have changed.
+                self.notify_observers();
+            }
+            EventWrapper::SendGameEvents(events) => {
+                // Nothing is sent if any event of the batch is rejected.
+                if let Err((event, reason)) = self.core.validate_events(&events) {
+                    orders.send_msg(M::from(EventWrapper::EventRejected(event, reason)));
+                    return;
+                }
+                for req in self.core.send_events(events) {
+                    self.send(req);
+                }
+                self.notify_observers();
+            }
+            EventWrapper::Undo => {
+                if let Some((_, req)) = self.core.undo() {
+                    if let Some(req) = req {
+                        self.send(req);
+                    }
+                    self.notify_observers();
+                }
+            }
+            EventWrapper::InitGameState(sync_data) => {
+                self.receive(Res::Sync(sync_data), orders);
+            }
+            EventWrapper::ReceiveGameEvent(event) => {
+                let changed = self.receive(Res::Event(event), orders);
+                self.schedule_tick_prediction(orders);
+                if !changed {
+                    orders.skip();
+                }
+            }
+            EventWrapper::Heartbeat => {
+                if let Some(req) = self.core.heartbeat() {
+                    self.send(req);
+                }
+                orders.skip();
+            }
+            EventWrapper::VisibilityChanged(true) => {
+                self.core.hidden();
+                orders.skip();
+            }
+            EventWrapper::VisibilityChanged(false) => {
+                for req in self.core.visible() {
+                    self.send(req);
+                }
+                self.schedule_tick_prediction(orders);
+                self.notify_observers();
+            }
+            EventWrapper::PlayReplay => {
+                self.step_replay(orders);
+                let playing = self.playback.as_ref().is_some_and(|playback| {
+                    playback.timer.is_some() && !playback.player.is_finished()
+                });
+                if playing {
+                    self.schedule_playback(orders);
+                } else {
+                    self.pause();
+                }
+            }
+            EventWrapper::PredictTick => {
+                let changed = self.core.predict_tick() && self.notify_observers();
+                self.schedule_tick_prediction(orders);
+                if !changed {
+                    orders.skip();
+                }
+            }
+            EventWrapper::UserUpdate(diff) => {
+                self.receive(Res::UserUpdate(diff), orders);
+            }
+            #[cfg(feature = "indexed-db")]
+            EventWrapper::RestoreSnapshot(sync_data, tail) => {
+                self.core.restore(*sync_data);
+                self.core.restore_tail(tail);
+                self.notify_observers();
+            }
+            EventWrapper::ReceiveMessage(message) => {
+                self.receive(Res::Message(message), orders);
+            }
+            EventWrapper::ReceivePong(id, server_time) => {
+                self.receive(Res::Pong(id, server_time), orders);
+            }
+            EventWrapper::ReceiveAck(id) => {
+                self.receive(Res::Ack(id), orders);
+            }
+            EventWrapper::ReceiveExtension(kind, payload) => {
+                self.receive(Res::Extension(kind, payload), orders);
+                for (kind, payload) in self.core.take_extensions() {
+                    if let Err(err) = self.extensions.dispatch(&kind, &payload) {
+                        log!(err);
+                    }
+                }
+            }
+            EventWrapper::SetLocales(locales) => {
+                // Sent again once the connection is open.
+                let req = self.core.set_locales(locales);
+                self.send(req);
+                orders.render();
+            }
+            EventWrapper::ReloadCatalog(locale, source) => {
+                self.receive(Res::Catalog(locale, source), orders);
+            }
+            EventWrapper::DecodeFailed(err) => {
+                for req in self.core.decode_failed(err) {
+                    self.send(req);
+                }
+            }
+            EventWrapper::ReceiveRejection(event, reason) => {
+                self.receive(Res::Rejected(event, reason), orders);
+                for (event, reason) in self.core.take_rejections() {
+                    orders.send_msg(M::from(EventWrapper::EventRejected(event, reason)));
+                }
+            }
+            EventWrapper::EventRejected(event, reason) => {
+                log!("rejected", format!("{event:?}:"), reason);
+            }
+            EventWrapper::VersionMismatch(outdated) => {
+                log!(outdated.to_string(), ", reloading");
+                if let Err(err) = window().location().reload() {
+                    log!("couldn't reload:", err);
+                }
+            }
+            #[cfg(feature = "devtools")]
+            EventWrapper::ToggleDevtools => self.devtools = !self.devtools,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EventWrapper<S: State> {
+    WebSocketOpened,
+    CloseWebSocket,
+    WebSocketClosed(CloseEvent),
+    WebSocketFailed,
+    ReconnectWebSocket(usize),
+    SendGameEvent(S::ClientEvent),
+    SendGameEvents(Vec<S::ClientEvent>),
+    Undo,
+    ReceiveGameEvent(EventData<S>),
+    InitGameState(SyncData<S>),
+    UserUpdate(MapDiff<S::UserId, S::UserData>),
+    #[cfg(feature = "indexed-db")]
+    RestoreSnapshot(Box<SyncData<S>>, Vec<Res<S>>),
+    ReceiveMessage(String),
+    ReceivePong(u32, u64),
+    ReceiveAck(u64),
+    ReceiveExtension(String, Vec<u8>),
+    Heartbeat,
+    // Whether the page is hidden now.
+    VisibilityChanged(bool),
+    PredictTick,
+    PlayReplay,
+    ReloadCatalog(Locale, String),
+    SetLocales(Vec<Locale>),
+    // A message of the server couldn't be decoded, so the state is synced
+    // again.
+    DecodeFailed(String),
+    ReceiveRejection(S::ClientEvent, String),
+    // An event wasn't sent or the server didn't apply it, as it's illegal in
+    // the current state. Apps can handle this to tell the user why.
+    EventRejected(S::ClientEvent, String),
+    // The server runs another version of the game. The page is reloaded
+    // unless the app handles this itself, e.g. to ask the user first.
+    VersionMismatch(Outdated),
+    #[cfg(feature = "devtools")]
+    ToggleDevtools,
+}
+
+impl<S: State> EventWrapper<S> {
+    pub fn decode(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error>
+    where
+        S: DeserializeOwned,
+    {
+        let res = match decode(bytes) {
+            Ok(res) => res,
+            Err(err) => {
+                return match mismatched_sync::<S>(bytes) {
+                    Some(outdated) => Ok(EventWrapper::VersionMismatch(outdated)),
+                    None => Err(err),
+                }
+            }
+        };
+        Ok(match res {
+            Res::Event(event) => EventWrapper::ReceiveGameEvent(event),
+            Res::Sync(sync) => EventWrapper::InitGameState(sync),
+            Res::UserUpdate(diff) => EventWrapper::UserUpdate(diff),
+            Res::Message(message) => EventWrapper::ReceiveMessage(message),
+            Res::Pong(id, server_time) => EventWrapper::ReceivePong(id, server_time),
+            Res::Ack(id) => EventWrapper::ReceiveAck(id),
+            Res::Extension(kind, payload) => EventWrapper::ReceiveExtension(kind, payload),
+            Res::Rejected(event, reason) => EventWrapper::ReceiveRejection(event, reason),
+            Res::Catalog(locale, source) => EventWrapper::ReloadCatalog(locale, source),
+        })
+    }
+}
+
+fn new_core<S: State>() -> ClientCore<S> {
+    ClientCore::new(|message| log!(message), js_sys::Date::now)
+}
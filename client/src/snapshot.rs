@@ -0,0 +1,38 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use engine_shared::{State, SyncData};
+use seed::prelude::{web_sys, JsValue};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Snapshots are saved as base64 encoded MessagePack, as the local storage
+// only holds strings.
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+pub(crate) fn load<S: State + DeserializeOwned>(key: &str) -> Option<SyncData<S>> {
+    let storage = local_storage()?;
+    let encoded = storage.get_item(key).ok()??;
+    let sync_data = STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| rmp_serde::from_slice::<SyncData<S>>(&bytes).ok());
+    match sync_data {
+        Some(sync_data) if sync_data.schema_version == S::SCHEMA_VERSION => Some(sync_data),
+        // Snapshots of other versions are outdated once the server syncs
+        // anyway.
+        _ => {
+            storage.remove_item(key).ok();
+            None
+        }
+    }
+}
+
+pub(crate) fn save<S: State + Serialize>(
+    key: &str,
+    sync_data: &SyncData<S>,
+) -> Result<(), JsValue> {
+    let storage = local_storage().ok_or("local storage isn't available")?;
+    let bytes = rmp_serde::to_vec(sync_data).unwrap();
+    storage.set_item(key, &STANDARD.encode(bytes))
+}
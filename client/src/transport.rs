@@ -0,0 +1,243 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::Rc,
+};
+
+use engine_shared::{Req, State};
+use seed::{prelude::*, *};
+use serde::de::DeserializeOwned;
+
+use crate::{EventWrapper, Msg};
+
+#[derive(Debug, Clone)]
+pub struct TransportError(pub String);
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transport error: {}", self.0)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+// The connection to the server. Received bytes are passed to the app as
+// `EventWrapper::decode(bytes)`.
+pub trait ClientTransport<S: State>: 'static {
+    fn send(&self, bytes: &[u8]) -> Result<(), TransportError>;
+
+    fn close(&self, reason: &str) -> Result<(), TransportError>;
+
+    // Opens a new connection after the previous one was lost.
+    fn reconnect<M: Msg<S>>(&mut self, orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned;
+
+    // Replaces the connection with one to another path, e.g. to join
+    // another game.
+    fn reconnect_to<M: Msg<S>>(&mut self, ws_path: String, orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned;
+}
+
+pub struct WebSocketTransport {
+    // `None` if the web socket couldn't be built, e.g. because of an
+    // invalid path.
+    web_socket: Option<WebSocket>,
+    ws_path: String,
+    protocols: Vec<String>,
+}
+
+impl WebSocketTransport {
+    pub fn connect<S, M>(orders: &impl Orders<M>, ws_path: String) -> Self
+    where
+        S: State + DeserializeOwned,
+        M: Msg<S>,
+    {
+        Self::connect_with_protocols::<S, M>(orders, ws_path, Vec::new())
+    }
+
+    // The server has to accept one of the protocols, otherwise the browser
+    // closes the connection.
+    pub fn connect_with_protocols<S, M>(
+        orders: &impl Orders<M>,
+        ws_path: String,
+        protocols: Vec<String>,
+    ) -> Self
+    where
+        S: State + DeserializeOwned,
+        M: Msg<S>,
+    {
+        WebSocketTransport {
+            web_socket: Self::create_websocket::<S, M>(orders, &ws_path, &protocols),
+            ws_path,
+            protocols,
+        }
+    }
+
+    fn create_websocket<S, M>(
+        orders: &impl Orders<M>,
+        ws_path: &str,
+        protocols: &[String],
+    ) -> Option<WebSocket>
+    where
+        S: State + DeserializeOwned,
+        M: Msg<S>,
+    {
+        let msg_sender = orders.msg_sender();
+        let protocols: Vec<&str> = protocols.iter().map(String::as_str).collect();
+
+        let web_socket = WebSocket::builder(ws_path, orders)
+            .protocols(&protocols)
+            .on_open(|| M::from(EventWrapper::<S>::WebSocketOpened))
+            .on_message({
+                let msg_sender = msg_sender.clone();
+                move |msg| Self::decode_message::<S, M>(msg, msg_sender)
+            })
+            .on_close(|evt| M::from(EventWrapper::<S>::WebSocketClosed(evt)))
+            .on_error(|| M::from(EventWrapper::<S>::WebSocketFailed))
+            .build_and_open();
+
+        match web_socket {
+            Ok(web_socket) => Some(web_socket),
+            Err(err) => {
+                log!("couldn't build WebSocket:", err);
+                // Reconnects like after any other failure, but only after
+                // the current message is handled.
+                spawn_local(async move {
+                    msg_sender(Some(M::from(EventWrapper::<S>::WebSocketFailed)));
+                });
+                None
+            }
+        }
+    }
+
+    fn decode_message<S, M>(message: WebSocketMessage, msg_sender: Rc<dyn Fn(Option<M>)>)
+    where
+        S: State + DeserializeOwned,
+        M: Msg<S>,
+    {
+        if message.contains_text() {
+            msg_sender(Some(M::from(EventWrapper::<S>::DecodeFailed(
+                "unexpected text message".to_owned(),
+            ))));
+        } else {
+            spawn_local(async move {
+                let msg = match message.bytes().await {
+                    Ok(bytes) => EventWrapper::<S>::decode(&bytes)
+                        .unwrap_or_else(|err| EventWrapper::DecodeFailed(err.to_string())),
+                    Err(err) => EventWrapper::DecodeFailed(format!("{err:?}")),
+                };
+                msg_sender(Some(M::from(msg)));
+            });
+        }
+    }
+}
+
+impl<S: State> ClientTransport<S> for WebSocketTransport {
+    fn send(&self, bytes: &[u8]) -> Result<(), TransportError> {
+        self.web_socket
+            .as_ref()
+            .ok_or_else(|| TransportError("WebSocket isn't open".to_owned()))?
+            .send_bytes(bytes)
+            .map_err(|err| TransportError(format!("{err:?}")))
+    }
+
+    fn close(&self, reason: &str) -> Result<(), TransportError> {
+        match &self.web_socket {
+            Some(web_socket) => web_socket
+                .close(None, Some(reason))
+                .map_err(|err| TransportError(format!("{err:?}"))),
+            None => Ok(()),
+        }
+    }
+
+    fn reconnect<M: Msg<S>>(&mut self, orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+        self.web_socket = Self::create_websocket::<S, M>(orders, &self.ws_path, &self.protocols);
+    }
+
+    fn reconnect_to<M: Msg<S>>(&mut self, ws_path: String, orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+        // Dropping the previous web socket closes it without reporting it.
+        self.ws_path = ws_path;
+        <Self as ClientTransport<S>>::reconnect(self, orders);
+    }
+}
+
+// An in-memory transport to drive a client without a server, e.g. in tests.
+// Clones share their state, so one clone can be kept to inspect what the
+// client sent.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    sent: Rc<RefCell<Vec<Vec<u8>>>>,
+    closed: Rc<Cell<bool>>,
+    reconnects: Rc<Cell<usize>>,
+    ws_path: Rc<RefCell<Option<String>>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The requests sent since the last call.
+    pub fn take_sent<S: State>(&self) -> Vec<Req<S>>
+    where
+        Req<S>: DeserializeOwned,
+    {
+        self.sent
+            .borrow_mut()
+            .drain(..)
+            .map(|bytes| rmp_serde::from_slice(&bytes).unwrap())
+            .collect()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    pub fn reconnects(&self) -> usize {
+        self.reconnects.get()
+    }
+
+    // The path of the last `reconnect_to`.
+    pub fn ws_path(&self) -> Option<String> {
+        self.ws_path.borrow().clone()
+    }
+}
+
+impl<S: State> ClientTransport<S> for MockTransport {
+    fn send(&self, bytes: &[u8]) -> Result<(), TransportError> {
+        if self.closed.get() {
+            return Err(TransportError("closed".to_owned()));
+        }
+        self.sent.borrow_mut().push(bytes.to_vec());
+        Ok(())
+    }
+
+    fn close(&self, _reason: &str) -> Result<(), TransportError> {
+        self.closed.set(true);
+        Ok(())
+    }
+
+    fn reconnect<M: Msg<S>>(&mut self, _orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+        self.closed.set(false);
+        self.reconnects.set(self.reconnects.get() + 1);
+    }
+
+    fn reconnect_to<M: Msg<S>>(&mut self, ws_path: String, orders: &impl Orders<M>)
+    where
+        S: DeserializeOwned,
+    {
+        *self.ws_path.borrow_mut() = Some(ws_path);
+        <Self as ClientTransport<S>>::reconnect(self, orders);
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    cell::{Ref, RefCell},
+    rc::Rc,
+};
+
+use engine_shared::{Req, State};
+use i18n::Locale;
+use js_sys::{Array, Uint8Array};
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent, Worker};
+
+use crate::{
+    browser::{log, reload, ReconnectingWebSocket, SocketEvents},
+    core::{decode, encode, mismatched_sync, ClientCore, ReconnectPolicy},
+    Extensions,
+};
+
+// The page and the worker exchange arrays of a kind and MessagePack encoded
+// bytes, which are structured-cloned by `postMessage`.
+
+fn message(kind: &str, bytes: &[u8]) -> JsValue {
+    let message = Array::new();
+    message.push(&kind.into());
+    message.push(&Uint8Array::from(bytes));
+    message.into()
+}
+
+fn parse(event: JsValue) -> Option<(String, Vec<u8>)> {
+    let event: MessageEvent = event.unchecked_into();
+    let message: Array = event.data().dyn_into().ok()?;
+    let kind = message.get(0).as_string()?;
+    let bytes = message.get(1).dyn_into::<Uint8Array>().ok()?.to_vec();
+    Some((kind, bytes))
+}
+
+// The state mirror inside the worker. Applying events and computing
+// checksums happens here, the page only gets the views.
+struct Mirror<S: State, F> {
+    scope: DedicatedWorkerGlobalScope,
+    core: ClientCore<S>,
+    view: F,
+    last_view: Option<Vec<u8>>,
+}
+
+impl<S, V, F> Mirror<S, F>
+where
+    S: State + Serialize + DeserializeOwned,
+    V: Serialize,
+    F: Fn(&ClientCore<S>) -> V,
+{
+    fn post(&self, kind: &str, bytes: &[u8]) {
+        if let Err(err) = self.scope.post_message(&message(kind, bytes)) {
+            log(&format!("couldn't post {kind} to the page: {err:?}"));
+        }
+    }
+
+    fn request(&self, req: &Req<S>) {
+        self.post("request", &encode(req));
+    }
+
+    fn reject(&self, event: S::ClientEvent, reason: String) {
+        self.post("rejected", &rmp_serde::to_vec(&(event, reason)).unwrap());
+    }
+
+    fn received(&mut self, kind: &str, bytes: Vec<u8>) {
+        match kind {
+            "opened" => {
+                // The worker has its own settings, so the locales of the page
+                // are sent along for the handshake.
+                if let Ok(locales) = rmp_serde::from_slice::<Vec<Locale>>(&bytes) {
+                    i18n::set_locales(&locales);
+                }
+                for req in self.core.opened() {
+                    self.request(&req);
+                }
+            }
+            "received" => self.receive(&bytes),
+            "closed" => self
+                .core
+                .closed(String::from_utf8_lossy(&bytes).into_owned()),
+            "reconnecting" => {
+                if let Ok(attempt) = rmp_serde::from_slice(&bytes) {
+                    self.core.reconnecting(attempt);
+                }
+            }
+            "heartbeat" => {
+                if let Some(req) = self.core.heartbeat() {
+                    self.request(&req);
+                }
+                if self.core.take_reconnect() {
+                    self.post("dead", &[]);
+                }
+            }
+            "hidden" => self.core.hidden(),
+            "visible" => {
+                for req in self.core.visible() {
+                    self.request(&req);
+                }
+            }
+            "event" => {
+                if let Ok(event) = rmp_serde::from_slice::<S::ClientEvent>(&bytes) {
+                    match self.core.validate(&event) {
+                        Ok(()) => {
+                            if let Some(req) = self.core.send_event(event) {
+                                self.request(&req);
+                            }
+                        }
+                        Err(reason) => self.reject(event, reason),
+                    }
+                }
+            }
+            "events" => {
+                if let Ok(events) = rmp_serde::from_slice::<Vec<S::ClientEvent>>(&bytes) {
+                    match self.core.validate_events(&events) {
+                        Ok(()) => {
+                            for req in self.core.send_events(events) {
+                                self.request(&req);
+                            }
+                        }
+                        Err((event, reason)) => self.reject(event, reason),
+                    }
+                }
+            }
+            "undo" => {
+                if let Some((_, Some(req))) = self.core.undo() {
+                    self.request(&req);
+                }
+            }
+            "send_failed" => {
+                if let Ok(req) = rmp_serde::from_slice::<Req<S>>(&bytes) {
+                    self.core.send_failed(req);
+                }
+            }
+            _ => log(&format!("unknown message from the page: {kind}")),
+        }
+
+        for (event, reason) in self.core.take_rejections() {
+            self.reject(event, reason);
+        }
+        self.update_view();
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        let res = match decode(bytes) {
+            Ok(res) => res,
+            Err(err) => {
+                if let Some(outdated) = mismatched_sync::<S>(bytes) {
+                    log(&format!("{outdated}, reloading"));
+                    self.post("reload", &[]);
+                    return;
+                }
+                for req in self.core.decode_failed(err) {
+                    self.request(&req);
+                }
+                if self.core.take_reconnect() {
+                    self.post("restart", b"state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.post("out-of-date", &[]);
+                }
+                return;
+            }
+        };
+        match self.core.receive(res) {
+            Ok(requests) => {
+                for req in requests {
+                    self.request(&req);
+                }
+                if self.core.take_reconnect() {
+                    self.post("restart", b"state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.post("out-of-date", &[]);
+                }
+                for extension in self.core.take_extensions() {
+                    self.post("extension", &rmp_serde::to_vec(&extension).unwrap());
+                }
+            }
+            Err(outdated) => {
+                log(&format!("{outdated}, reloading"));
+                self.post("reload", &[]);
+            }
+        }
+    }
+
+    // Views are only posted when they changed, so ticks that don't change
+    // what the page shows aren't cloned.
+    fn update_view(&mut self) {
+        let bytes = rmp_serde::to_vec(&(self.view)(&self.core)).unwrap();
+        if self.last_view.as_ref() != Some(&bytes) {
+            self.post("view", &bytes);
+            self.last_view = Some(bytes);
+        }
+    }
+}
+
+// Runs the state mirror of a `StateWorker`. Called by the entry point of the
+// worker, with the same state type as the page. `view` picks what the page
+// needs, e.g. only the visible part of a large map.
+pub fn run_state_worker<S, V, F>(configure: impl FnOnce(&mut ClientCore<S>), view: F)
+where
+    S: State + Serialize + DeserializeOwned,
+    V: Serialize,
+    F: Fn(&ClientCore<S>) -> V + 'static,
+{
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let mut core = ClientCore::new(log, js_sys::Date::now);
+    configure(&mut core);
+    let mut mirror = Mirror {
+        scope: scope.clone(),
+        core,
+        view,
+        last_view: None,
+    };
+
+    let on_message = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+        if let Some((kind, bytes)) = parse(event) {
+            mirror.received(&kind, bytes);
+        }
+    });
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The worker runs as long as the page that started it.
+    on_message.forget();
+}
+
+struct Connection<S: State, V> {
+    socket: ReconnectingWebSocket,
+    worker: Worker,
+    view: Option<V>,
+    rejections: Vec<(S::ClientEvent, String)>,
+    // The worker noticed that the server stopped answering pings, so the
+    // next heartbeat replaces the web socket.
+    dead: bool,
+    on_view: Option<Rc<dyn Fn()>>,
+    extensions: Rc<RefCell<Extensions>>,
+    // Extension messages that arrived, dispatched once the connection isn't
+    // borrowed anymore.
+    received_extensions: Vec<(String, Vec<u8>)>,
+    // Keeps the message handler of the worker alive.
+    on_message: Option<Closure<dyn FnMut(JsValue)>>,
+}
+
+impl<S: State, V: DeserializeOwned> Connection<S, V> {
+    // Handles a message of the worker. Returns whether the page has to be
+    // updated.
+    fn handle_message(&mut self, kind: &str, bytes: Vec<u8>) -> bool {
+        match kind {
+            "request" => {
+                if let Err(err) = self.socket.send(&bytes) {
+                    log(&format!("couldn't send request: {err:?}"));
+                    self.post("send_failed", &bytes);
+                }
+            }
+            "view" => match rmp_serde::from_slice(&bytes) {
+                Ok(view) => {
+                    self.view = Some(view);
+                    return true;
+                }
+                Err(err) => log(&format!("couldn't decode view: {err}")),
+            },
+            "rejected" => {
+                if let Ok(rejection) = rmp_serde::from_slice(&bytes) {
+                    self.rejections.push(rejection);
+                    return true;
+                }
+            }
+            "extension" => match rmp_serde::from_slice(&bytes) {
+                Ok(extension) => self.received_extensions.push(extension),
+                Err(err) => log(&format!("couldn't decode extension: {err}")),
+            },
+            "restart" => self.socket.restart(&String::from_utf8_lossy(&bytes)),
+            "out-of-date" => self.socket.close(),
+            "dead" => self.dead = true,
+            "reload" => reload(),
+            _ => log(&format!("unknown message from the worker: {kind}")),
+        }
+        false
+    }
+}
+
+impl<S: State, V: 'static> SocketEvents for Connection<S, V> {
+    fn socket(&mut self) -> &mut ReconnectingWebSocket {
+        &mut self.socket
+    }
+
+    fn opened(&mut self) {
+        let locales = i18n::get_global_locales().to_vec();
+        self.post("opened", &rmp_serde::to_vec(&locales).unwrap());
+    }
+
+    fn received(&mut self, bytes: Vec<u8>) {
+        self.post("received", &bytes);
+    }
+
+    fn closed(&mut self, reason: String) {
+        self.post("closed", reason.as_bytes());
+    }
+
+    fn reconnecting(&mut self, attempt: usize) {
+        self.post("reconnecting", &rmp_serde::to_vec(&attempt).unwrap());
+    }
+
+    fn heartbeat(&mut self) -> bool {
+        self.post("heartbeat", &[]);
+        std::mem::take(&mut self.dead)
+    }
+
+    fn visibility_changed(&mut self, hidden: bool) {
+        self.post(if hidden { "hidden" } else { "visible" }, &[]);
+    }
+}
+
+impl<S: State, V> Connection<S, V> {
+    fn post(&self, kind: &str, bytes: &[u8]) {
+        if let Err(err) = self.worker.post_message(&message(kind, bytes)) {
+            log(&format!("couldn't post {kind} to the worker: {err:?}"));
+        }
+    }
+}
+
+// A connection whose state mirror runs in a dedicated worker, for states that
+// are too large to update on the main thread. The page only holds the latest
+// view of the state that the worker sent.
+pub struct StateWorker<S: State, V> {
+    connection: Rc<RefCell<Connection<S, V>>>,
+}
+
+impl<S: State, V> Clone for StateWorker<S, V> {
+    fn clone(&self) -> Self {
+        StateWorker {
+            connection: self.connection.clone(),
+        }
+    }
+}
+
+impl<S: State, V: DeserializeOwned + 'static> StateWorker<S, V> {
+    // The worker has to call `run_state_worker`. Apps start it themselves,
+    // as its script depends on how they're bundled.
+    pub fn new(worker: Worker, ws_path: String) -> Self {
+        let connection = Rc::new(RefCell::new(Connection {
+            socket: ReconnectingWebSocket::new(ws_path),
+            worker,
+            view: None,
+            rejections: Vec::new(),
+            dead: false,
+            on_view: None,
+            extensions: Rc::default(),
+            received_extensions: Vec::new(),
+            on_message: None,
+        }));
+
+        let weak = Rc::downgrade(&connection);
+        let on_message = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            let (Some((kind, bytes)), Some(connection)) = (parse(event), weak.upgrade()) else {
+                return;
+            };
+            let changed = connection.borrow_mut().handle_message(&kind, bytes);
+            let (on_view, extensions, received) = {
+                let mut connection = connection.borrow_mut();
+                let received = std::mem::take(&mut connection.received_extensions);
+                (
+                    connection.on_view.clone(),
+                    connection.extensions.clone(),
+                    received,
+                )
+            };
+            // Called without a borrow, as they use the handle.
+            for (kind, payload) in received {
+                if let Err(err) = extensions.borrow_mut().dispatch(&kind, &payload) {
+                    log(&err);
+                }
+            }
+            if let (true, Some(on_view)) = (changed, on_view) {
+                on_view();
+            }
+        });
+        {
+            let mut connection = connection.borrow_mut();
+            connection
+                .worker
+                .set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            connection.on_message = Some(on_message);
+        }
+
+        ReconnectingWebSocket::open(&connection);
+        StateWorker { connection }
+    }
+}
+
+impl<S: State, V> StateWorker<S, V> {
+    // Called whenever a new view or rejection arrived, e.g. to re-render.
+    pub fn on_view(&self, on_view: impl Fn() + 'static) {
+        self.connection.borrow_mut().on_view = Some(Rc::new(on_view));
+    }
+
+    // Called with the payloads of the extension messages of the kind, see
+    // `Extensions`.
+    pub fn on_extension<T, F>(&self, kind: impl Into<String>, handler: F)
+    where
+        T: DeserializeOwned,
+        F: FnMut(T) + 'static,
+    {
+        let connection = self.connection.borrow();
+        let mut extensions = connection.extensions.borrow_mut();
+        extensions.on(kind, handler);
+    }
+
+    pub fn view(&self) -> Option<Ref<'_, V>> {
+        Ref::filter_map(self.connection.borrow(), |connection| {
+            connection.view.as_ref()
+        })
+        .ok()
+    }
+
+    // Own events that weren't sent or that the server didn't apply, with the
+    // reasons.
+    pub fn take_rejections(&self) -> Vec<(S::ClientEvent, String)> {
+        std::mem::take(&mut self.connection.borrow_mut().rejections)
+    }
+
+    pub fn send_event(&self, event: S::ClientEvent) {
+        let bytes = rmp_serde::to_vec(&event).unwrap();
+        self.connection.borrow().post("event", &bytes);
+    }
+
+    pub fn send_events(&self, events: Vec<S::ClientEvent>) {
+        let bytes = rmp_serde::to_vec(&events).unwrap();
+        self.connection.borrow().post("events", &bytes);
+    }
+
+    // Undoes the last own event that isn't confirmed yet, see
+    // `ClientCore::undo`. The view can tell whether there's one with
+    // `ClientCore::can_undo`.
+    pub fn undo(&self) {
+        self.connection.borrow().post("undo", &[]);
+    }
+
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        self.connection.borrow_mut().socket.set_policy(policy);
+    }
+
+    // Called once the reconnect policy ran out of retries.
+    pub fn on_give_up(&self, on_give_up: impl Fn() + 'static) {
+        self.connection
+            .borrow_mut()
+            .socket
+            .set_on_give_up(on_give_up);
+    }
+
+    pub fn close(&self) {
+        let mut connection = self.connection.borrow_mut();
+        connection.socket.close();
+        connection.worker.terminate();
+    }
+}
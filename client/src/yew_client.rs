@@ -0,0 +1,345 @@
+use std::{
+    cell::{Ref, RefCell},
+    rc::Rc,
+    time::{Duration, SystemTime},
+};
+
+use engine_shared::{Req, State};
+use i18n::Locale;
+use serde::{de::DeserializeOwned, Serialize};
+use yew::prelude::*;
+
+use crate::{
+    browser::{log, reload, ReconnectingWebSocket, SocketEvents},
+    core::{
+        decode, encode, mismatched_sync, ClientCore, ConnectionStatus, FeedEntry, ReconnectPolicy,
+        ResyncPolicy,
+    },
+    Extensions,
+};
+
+struct Connection<S: State> {
+    socket: ReconnectingWebSocket,
+    core: ClientCore<S>,
+    rerender: Option<UseForceUpdateHandle>,
+    extensions: Rc<RefCell<Extensions>>,
+}
+
+impl<S: State + Serialize + DeserializeOwned> Connection<S> {
+    fn new(ws_path: String) -> Self {
+        Connection {
+            socket: ReconnectingWebSocket::new(ws_path),
+            core: ClientCore::new(log, js_sys::Date::now),
+            rerender: None,
+            extensions: Rc::default(),
+        }
+    }
+
+    fn request(&mut self, req: Req<S>) {
+        if let Err(err) = self.socket.send(&encode(&req)) {
+            log(&format!("couldn't send request: {err:?}"));
+            self.core.send_failed(req);
+        }
+    }
+
+    fn rerender(&self) {
+        if let Some(rerender) = &self.rerender {
+            rerender.force_update();
+        }
+    }
+
+    // Handlers may use the handle, so they're called once the connection
+    // isn't borrowed anymore.
+    fn dispatch_extensions(&mut self) {
+        let received = self.core.take_extensions();
+        if received.is_empty() {
+            return;
+        }
+        let extensions = self.extensions.clone();
+        yew::platform::spawn_local(async move {
+            for (kind, payload) in received {
+                if let Err(err) = extensions.borrow_mut().dispatch(&kind, &payload) {
+                    log(&err);
+                }
+            }
+        });
+    }
+}
+
+impl<S: State + Serialize + DeserializeOwned> SocketEvents for Connection<S> {
+    fn socket(&mut self) -> &mut ReconnectingWebSocket {
+        &mut self.socket
+    }
+
+    fn opened(&mut self) {
+        for req in self.core.opened() {
+            self.request(req);
+        }
+        self.rerender();
+    }
+
+    fn closed(&mut self, reason: String) {
+        self.core.closed(reason);
+        self.rerender();
+    }
+
+    fn reconnecting(&mut self, attempt: usize) {
+        self.core.reconnecting(attempt);
+        self.rerender();
+    }
+
+    fn heartbeat(&mut self) -> bool {
+        if let Some(req) = self.core.heartbeat() {
+            self.request(req);
+        }
+        let dead = self.core.take_reconnect();
+        if dead {
+            self.rerender();
+        }
+        dead
+    }
+
+    fn visibility_changed(&mut self, hidden: bool) {
+        if hidden {
+            self.core.hidden();
+            return;
+        }
+        for req in self.core.visible() {
+            self.request(req);
+        }
+        self.rerender();
+    }
+
+    fn received(&mut self, bytes: Vec<u8>) {
+        let res = match decode(&bytes) {
+            Ok(res) => res,
+            Err(err) => {
+                if let Some(outdated) = mismatched_sync::<S>(&bytes) {
+                    log(&format!("{outdated}, reloading"));
+                    reload();
+                    return;
+                }
+                for req in self.core.decode_failed(err) {
+                    self.request(req);
+                }
+                if self.core.take_reconnect() {
+                    self.socket.restart("state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.socket.close();
+                }
+                self.rerender();
+                return;
+            }
+        };
+        match self.core.receive(res) {
+            Ok(requests) => {
+                for req in requests {
+                    self.request(req);
+                }
+                if self.core.take_reconnect() {
+                    self.socket.restart("state keeps diverging");
+                } else if self.core.is_out_of_date() {
+                    self.socket.close();
+                }
+                self.dispatch_extensions();
+            }
+            Err(outdated) => {
+                log(&format!("{outdated}, reloading"));
+                reload();
+                return;
+            }
+        }
+        self.rerender();
+    }
+}
+
+// Returned by `use_game_state`. The component re-renders whenever the server
+// changes the state.
+pub struct UseGameStateHandle<S: State> {
+    connection: Rc<RefCell<Connection<S>>>,
+}
+
+impl<S: State> Clone for UseGameStateHandle<S> {
+    fn clone(&self) -> Self {
+        UseGameStateHandle {
+            connection: self.connection.clone(),
+        }
+    }
+}
+
+impl<S: State> PartialEq for UseGameStateHandle<S> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.connection, &other.connection)
+    }
+}
+
+impl<S: State + Serialize + DeserializeOwned> UseGameStateHandle<S> {
+    pub fn get_state(&self) -> Option<Ref<'_, S>> {
+        Ref::filter_map(self.connection.borrow(), |connection| {
+            connection.core.get_state()
+        })
+        .ok()
+    }
+
+    pub fn get_user_id(&self) -> Option<S::UserId> {
+        self.connection.borrow().core.get_user_id().cloned()
+    }
+
+    pub fn get_user_data(&self, user_id: &S::UserId) -> Option<Ref<'_, S::UserData>> {
+        Ref::filter_map(self.connection.borrow(), |connection| {
+            connection.core.get_user_data(user_id)
+        })
+        .ok()
+    }
+
+    // Messages from the server, already rendered in the user's language.
+    pub fn take_messages(&self) -> Vec<String> {
+        self.connection.borrow_mut().core.take_messages()
+    }
+
+    // Own events the server didn't apply, with the reasons.
+    pub fn take_rejections(&self) -> Vec<(S::ClientEvent, String)> {
+        self.connection.borrow_mut().core.take_rejections()
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.connection.borrow().core.is_online()
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        self.connection.borrow().core.latency()
+    }
+
+    pub fn server_now(&self) -> Option<SystemTime> {
+        self.connection.borrow().core.server_now()
+    }
+
+    // The last applied events, oldest first.
+    pub fn feed(&self) -> Vec<FeedEntry<S>> {
+        self.connection.borrow().core.feed().cloned().collect()
+    }
+
+    pub fn set_feed_len(&self, feed_len: usize) {
+        self.connection.borrow_mut().core.set_feed_len(feed_len);
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.connection.borrow().core.status().clone()
+    }
+
+    pub fn send_event(&self, event: S::ClientEvent) {
+        let mut connection = self.connection.borrow_mut();
+        if let Some(req) = connection.core.send_event(event) {
+            connection.request(req);
+        }
+        connection.rerender();
+    }
+
+    pub fn send_events(&self, events: Vec<S::ClientEvent>) {
+        let mut connection = self.connection.borrow_mut();
+        for req in connection.core.send_events(events) {
+            connection.request(req);
+        }
+        connection.rerender();
+    }
+
+    // Undoes the last own event that isn't confirmed yet, see
+    // `ClientCore::undo`.
+    pub fn undo(&self) -> Option<S::ClientEvent> {
+        let mut connection = self.connection.borrow_mut();
+        let (event, req) = connection.core.undo()?;
+        if let Some(req) = req {
+            connection.request(req);
+        }
+        connection.rerender();
+        Some(event)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.connection.borrow().core.can_undo()
+    }
+
+    // Watches the game without joining it. Events aren't sent.
+    pub fn set_spectator(&self, spectator: bool) {
+        self.connection.borrow_mut().core.set_spectator(spectator);
+    }
+
+    pub fn is_spectator(&self) -> bool {
+        self.connection.borrow().core.is_spectator()
+    }
+
+    pub fn set_optimistic(&self, optimistic: bool) {
+        self.connection.borrow_mut().core.set_optimistic(optimistic);
+    }
+
+    pub fn set_acknowledged(&self, acknowledged: bool) {
+        self.connection
+            .borrow_mut()
+            .core
+            .set_acknowledged(acknowledged);
+    }
+
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        self.connection.borrow_mut().socket.set_policy(policy);
+    }
+
+    pub fn set_resync_policy(&self, policy: ResyncPolicy) {
+        self.connection.borrow_mut().core.set_resync_policy(policy);
+    }
+
+    // Emitted once the reconnect policy ran out of retries.
+    pub fn on_give_up(&self, on_give_up: Callback<()>) {
+        self.connection
+            .borrow_mut()
+            .socket
+            .set_on_give_up(move || on_give_up.emit(()));
+    }
+
+    // Emitted with the payloads of the extension messages of the kind, see
+    // `Extensions`.
+    pub fn on_extension<T: DeserializeOwned + 'static>(
+        &self,
+        kind: impl Into<String>,
+        callback: Callback<T>,
+    ) {
+        let connection = self.connection.borrow();
+        let mut extensions = connection.extensions.borrow_mut();
+        extensions.on(kind, move |payload| callback.emit(payload));
+    }
+
+    pub fn dispatch(&self) -> Callback<S::ClientEvent> {
+        let handle = self.clone();
+        Callback::from(move |event| handle.send_event(event))
+    }
+
+    pub fn set_locales(&self, locales: Vec<Locale>) {
+        let mut connection = self.connection.borrow_mut();
+        let req = connection.core.set_locales(locales);
+        connection.request(req);
+        connection.rerender();
+    }
+
+    pub fn close(&self) {
+        self.connection.borrow_mut().socket.close();
+    }
+}
+
+#[hook]
+pub fn use_game_state<S>(ws_path: &str) -> UseGameStateHandle<S>
+where
+    S: State + Serialize + DeserializeOwned,
+{
+    let rerender = use_force_update();
+    let connection = use_mut_ref(|| Connection::<S>::new(ws_path.to_owned()));
+    connection.borrow_mut().rerender = Some(rerender);
+
+    {
+        let connection = connection.clone();
+        use_effect_with((), move |_| {
+            ReconnectingWebSocket::open(&connection);
+            move || connection.borrow_mut().socket.close()
+        });
+    }
+
+    UseGameStateHandle { connection }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Locale;
+
+#[derive(Debug)]
+pub enum BuildError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    InvalidLocale(PathBuf),
+    UnknownVariant(PathBuf, String),
+    MissingDefault(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io(path, err) => write!(f, "{}: {err}", path.display()),
+            BuildError::Parse(path, err) => write!(f, "{}: {err}", path.display()),
+            BuildError::InvalidLocale(path) => {
+                write!(f, "{}: file name is not a locale", path.display())
+            }
+            BuildError::UnknownVariant(path, variant) => {
+                write!(f, "{}: unknown variant {variant}", path.display())
+            }
+            BuildError::MissingDefault(variant) => {
+                write!(f, "no translation for {variant} in the default locale")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+pub struct Translations {
+    name: String,
+    default_locale: Locale,
+    variants: Vec<(String, Vec<(String, String)>)>,
+    files: Vec<(PathBuf, Locale, BTreeMap<String, String>)>,
+}
+
+impl Translations {
+    pub fn new(name: &str, default_locale: Locale) -> Self {
+        Translations {
+            name: name.to_owned(),
+            default_locale,
+            variants: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn variant(mut self, name: &str, args: &[(&str, &str)]) -> Self {
+        self.variants.push((
+            name.to_owned(),
+            args.iter()
+                .map(|(name, ty)| (name.to_string(), ty.to_string()))
+                .collect(),
+        ));
+        self
+    }
+
+    // Loads every `<locale>.toml` and `<locale>.json` file in the directory,
+    // for example `en.toml` or `de-CH.json`.
+    pub fn load_dir(mut self, dir: impl AsRef<Path>) -> Result<Self, BuildError> {
+        let dir = dir.as_ref();
+        println!("cargo:rerun-if-changed={}", dir.display());
+
+        let entries = fs::read_dir(dir).map_err(|err| BuildError::Io(dir.to_owned(), err))?;
+        let mut paths = Vec::new();
+        for entry in entries {
+            paths.push(
+                entry
+                    .map_err(|err| BuildError::Io(dir.to_owned(), err))?
+                    .path(),
+            );
+        }
+        paths.sort();
+
+        for path in paths {
+            let parse: fn(&str) -> Result<BTreeMap<String, String>, String> =
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("toml") => |source| toml::from_str(source).map_err(|err| err.to_string()),
+                    Some("json") => {
+                        |source| serde_json::from_str(source).map_err(|err| err.to_string())
+                    }
+                    _ => continue,
+                };
+
+            println!("cargo:rerun-if-changed={}", path.display());
+            let locale = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(Locale::from_str)
+                .ok_or_else(|| BuildError::InvalidLocale(path.clone()))?;
+            let source =
+                fs::read_to_string(&path).map_err(|err| BuildError::Io(path.clone(), err))?;
+            let translations =
+                parse(&source).map_err(|err| BuildError::Parse(path.clone(), err))?;
+
+            if let Some(unknown) = translations
+                .keys()
+                .find(|key| !self.variants.iter().any(|(name, _)| name == *key))
+            {
+                return Err(BuildError::UnknownVariant(path, unknown.clone()));
+            }
+            self.files.push((path, locale, translations));
+        }
+
+        Ok(self)
+    }
+
+    pub fn generate(&self) -> Result<String, BuildError> {
+        // Locales with a country have to be matched before the language alone.
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by_key(|(_, Locale(_, country), _)| country.is_none());
+
+        let mut code = format!("i18n::localize! {{\n    pub enum {} {{\n", self.name);
+        for (variant, args) in &self.variants {
+            code.push_str(&format!("        {variant}"));
+            if !args.is_empty() {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|(name, ty)| format!("{name}: {ty}"))
+                    .collect();
+                code.push_str(&format!("({})", args.join(", ")));
+            }
+            code.push_str(" {\n");
+
+            let mut default = None;
+            for (_, locale, translations) in &files {
+                let Some(translation) = translations.get(variant) else {
+                    continue;
+                };
+                if *locale == self.default_locale {
+                    default = Some(translation);
+                }
+                code.push_str(&format!(
+                    "            {} => {translation:?},\n",
+                    pattern(*locale)
+                ));
+            }
+            let default = default.ok_or_else(|| BuildError::MissingDefault(variant.clone()))?;
+            code.push_str(&format!("            _ => {default:?},\n        }},\n"));
+        }
+        code.push_str("    }\n}\n");
+
+        Ok(code)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), BuildError> {
+        let path = path.as_ref();
+        fs::write(path, self.generate()?).map_err(|err| BuildError::Io(path.to_owned(), err))
+    }
+}
+
+fn pattern(Locale(language, country): Locale) -> String {
+    match country {
+        Some(country) => format!("Locale(Language::{language:?}, Some(Country::{country:?}))"),
+        None => format!("Locale(Language::{language:?}, _)"),
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fmt,
+    sync::{OnceLock, RwLock},
+};
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+use crate::{get_locales, Locale, Localized, Number};
+
+type Catalogs = Vec<(Locale, FluentBundle<FluentResource>)>;
+
+static CATALOGS: OnceLock<RwLock<Catalogs>> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct CatalogError(pub Vec<String>);
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid translation catalog: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    String(String),
+    Number(f64),
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg::String(value.to_owned())
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::String(value)
+    }
+}
+
+impl From<i64> for Arg {
+    fn from(value: i64) -> Self {
+        Arg::Number(value as f64)
+    }
+}
+
+impl From<f64> for Arg {
+    fn from(value: f64) -> Self {
+        Arg::Number(value)
+    }
+}
+
+impl From<Number> for Arg {
+    fn from(value: Number) -> Self {
+        Arg::String(value.to_string())
+    }
+}
+
+fn language_identifier(Locale(language, country): Locale) -> LanguageIdentifier {
+    let tag = match country {
+        Some(country) => format!("{language}-{country}"),
+        None => language.to_string(),
+    };
+    tag.parse()
+        .expect("languages and countries are valid subtags")
+}
+
+// Later catalogs for the same locale override messages of earlier ones.
+pub fn load_catalog(locale: Locale, source: &str) -> Result<(), CatalogError> {
+    let resource = FluentResource::try_new(source.to_owned())
+        .map_err(|(_, errors)| CatalogError(errors.iter().map(ToString::to_string).collect()))?;
+
+    let mut catalogs = CATALOGS
+        .get_or_init(|| RwLock::new(Vec::new()))
+        .write()
+        .unwrap();
+    let index = match catalogs.iter().position(|(loaded, _)| *loaded == locale) {
+        Some(index) => index,
+        None => {
+            let mut bundle = FluentBundle::new_concurrent(vec![language_identifier(locale)]);
+            bundle.set_use_isolating(false);
+            catalogs.push((locale, bundle));
+            catalogs.len() - 1
+        }
+    };
+    catalogs[index].1.add_resource_overriding(resource);
+    Ok(())
+}
+
+pub fn load_catalog_file(
+    locale: Locale,
+    path: impl AsRef<std::path::Path>,
+) -> Result<(), CatalogError> {
+    let source = std::fs::read_to_string(path.as_ref())
+        .map_err(|err| CatalogError(vec![format!("{}: {err}", path.as_ref().display())]))?;
+    load_catalog(locale, &source)
+}
+
+impl Localized {
+    pub fn from_key(key: &str, args: &[(&str, Arg)]) -> Localized {
+        Self::from_key_with(key, args, get_locales().as_slice())
+    }
+
+    pub fn from_key_with(key: &str, args: &[(&str, Arg)], locales: &[Locale]) -> Localized {
+        let Some(catalogs) = CATALOGS.get() else {
+            return Localized::from(key);
+        };
+        let catalogs = catalogs.read().unwrap();
+
+        let mut fluent_args = FluentArgs::new();
+        for (name, arg) in args {
+            let value = match arg {
+                Arg::String(value) => FluentValue::from(value.as_str()),
+                Arg::Number(value) => FluentValue::from(*value),
+            };
+            fluent_args.set(*name, value);
+        }
+
+        for locale in locales {
+            let Some((_, bundle)) = catalogs.iter().find(|(loaded, _)| loaded == locale) else {
+                continue;
+            };
+            let Some(pattern) = bundle.get_message(key).and_then(|message| message.value()) else {
+                continue;
+            };
+            let mut errors = Vec::new();
+            let value = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+            return Localized::from(value.into_owned());
+        }
+
+        Localized::from(key)
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+
+use crate::{Language, Locale};
+
+// A collation element: the base letter with a rank for letters that a
+// language sorts as separate letters after it, the accent and the case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Element {
+    primary: (char, u8),
+    accent: u8,
+    upper: bool,
+}
+
+// Compares two strings the way a dictionary of the locale would: accents and
+// case only decide between otherwise equal strings.
+pub fn collate(locale: Locale, a: &str, b: &str) -> Ordering {
+    let a = elements(locale, a);
+    let b = elements(locale, b);
+
+    let primary = |elements: &[Element]| elements.iter().map(|e| e.primary).collect::<Vec<_>>();
+    let accent = |elements: &[Element]| elements.iter().map(|e| e.accent).collect::<Vec<_>>();
+    let case = |elements: &[Element]| elements.iter().map(|e| e.upper).collect::<Vec<_>>();
+
+    primary(&a)
+        .cmp(&primary(&b))
+        .then_with(|| accent(&a).cmp(&accent(&b)))
+        .then_with(|| case(&a).cmp(&case(&b)))
+}
+
+pub fn sort_localized<T: AsRef<str>>(locale: Locale, items: &mut [T]) {
+    items.sort_by(|a, b| collate(locale, a.as_ref(), b.as_ref()));
+}
+
+pub fn sort_localized_by_key<T, K: AsRef<str>>(
+    locale: Locale,
+    items: &mut [T],
+    mut key: impl FnMut(&T) -> K,
+) {
+    items.sort_by_cached_key(|item| SortKey(locale, key(item).as_ref().to_owned()));
+}
+
+struct SortKey(Locale, String);
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey {}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        collate(self.0, &self.1, &other.1)
+    }
+}
+
+fn elements(Locale(language, _): Locale, string: &str) -> Vec<Element> {
+    let mut elements = Vec::with_capacity(string.len());
+    for c in string.chars() {
+        let upper = c.is_uppercase();
+        let lower = c.to_lowercase().next().unwrap_or(c);
+
+        if let Some(primary) = tailoring(language, lower) {
+            elements.push(Element {
+                primary,
+                accent: 0,
+                upper,
+            });
+            continue;
+        }
+
+        let (base, expansion, accent) = fold(lower);
+        for base in std::iter::once(base).chain(expansion) {
+            elements.push(Element {
+                primary: (base, 0),
+                accent,
+                upper,
+            });
+        }
+    }
+    elements
+}
+
+// Letters that the language sorts as their own letter instead of as an
+// accented variant.
+fn tailoring(language: Language, c: char) -> Option<(char, u8)> {
+    match (language, c) {
+        #[cfg(feature = "all-languages")]
+        (Language::Es, 'ñ') => Some(('n', 1)),
+        #[cfg(feature = "all-languages")]
+        (Language::Sv | Language::Fi, 'å') => Some(('z', 1)),
+        #[cfg(feature = "all-languages")]
+        (Language::Sv | Language::Fi, 'ä') => Some(('z', 2)),
+        #[cfg(feature = "all-languages")]
+        (Language::Sv | Language::Fi, 'ö') => Some(('z', 3)),
+        #[cfg(feature = "all-languages")]
+        (Language::Da | Language::Nb | Language::Nn | Language::No, 'æ') => Some(('z', 1)),
+        #[cfg(feature = "all-languages")]
+        (Language::Da | Language::Nb | Language::Nn | Language::No, 'ø') => Some(('z', 2)),
+        #[cfg(feature = "all-languages")]
+        (Language::Da | Language::Nb | Language::Nn | Language::No, 'å') => Some(('z', 3)),
+        _ => None,
+    }
+}
+
+// Splits a lowercase letter into its base letters and an accent rank.
+fn fold(c: char) -> (char, Option<char>, u8) {
+    match c {
+        'ß' => ('s', Some('s'), 1),
+        'æ' => ('a', Some('e'), 1),
+        'œ' => ('o', Some('e'), 1),
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => ('a', None, accent(c)),
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => ('c', None, accent(c)),
+        'ď' | 'đ' => ('d', None, accent(c)),
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => ('e', None, accent(c)),
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => ('g', None, accent(c)),
+        'ĥ' | 'ħ' => ('h', None, accent(c)),
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => ('i', None, accent(c)),
+        'ĵ' => ('j', None, accent(c)),
+        'ķ' => ('k', None, accent(c)),
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => ('l', None, accent(c)),
+        'ñ' | 'ń' | 'ņ' | 'ň' => ('n', None, accent(c)),
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => ('o', None, accent(c)),
+        'ŕ' | 'ŗ' | 'ř' => ('r', None, accent(c)),
+        'ś' | 'ŝ' | 'ş' | 'š' => ('s', None, accent(c)),
+        'ţ' | 'ť' | 'ŧ' => ('t', None, accent(c)),
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => {
+            ('u', None, accent(c))
+        }
+        'ŵ' => ('w', None, accent(c)),
+        'ý' | 'ÿ' | 'ŷ' => ('y', None, accent(c)),
+        'ź' | 'ż' | 'ž' => ('z', None, accent(c)),
+        _ => (c, None, 0),
+    }
+}
+
+// Orders the accents of a letter, so that e.g. "é" sorts before "è".
+fn accent(c: char) -> u8 {
+    match c {
+        'á' | 'é' | 'í' | 'ó' | 'ú' | 'ý' | 'ć' | 'ĺ' | 'ń' | 'ŕ' | 'ś' | 'ź' => 1,
+        'à' | 'è' | 'ì' | 'ò' | 'ù' => 2,
+        'ă' | 'ĕ' | 'ĭ' | 'ŏ' | 'ŭ' | 'ğ' => 3,
+        'â' | 'ê' | 'î' | 'ô' | 'û' | 'ĉ' | 'ĝ' | 'ĥ' | 'ĵ' | 'ŝ' | 'ŵ' | 'ŷ' => 4,
+        'č' | 'ď' | 'ě' | 'ľ' | 'ň' | 'ř' | 'š' | 'ť' | 'ž' => 5,
+        'å' | 'ů' => 6,
+        'ä' | 'ë' | 'ï' | 'ö' | 'ü' | 'ÿ' => 7,
+        'ő' | 'ű' => 8,
+        'ã' | 'ñ' | 'õ' | 'ĩ' | 'ũ' => 9,
+        'ç' | 'ģ' | 'ķ' | 'ļ' | 'ņ' | 'ŗ' | 'ş' | 'ţ' => 10,
+        'ą' | 'ę' | 'į' | 'ų' => 11,
+        'ċ' | 'ė' | 'ġ' | 'ż' | 'ŀ' => 12,
+        'ā' | 'ē' | 'ī' | 'ō' | 'ū' => 13,
+        'đ' | 'ħ' | 'ł' | 'ø' | 'ŧ' | 'ı' => 14,
+        _ => 0,
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use smallvec::SmallVec;
+
+use crate::{Locale, Settings, SETTINGS};
+
+// Implemented by every `localize!` enum.
+pub trait Translated {
+    const NAME: &'static str;
+
+    fn missing_translations(locales: &[Locale]) -> Vec<(&'static str, Locale)>;
+}
+
+// Which variants of the checked enums have no translation of their own for
+// the supported locales, e.g. to fail a test before a release with
+// `assert!(coverage.is_complete(), "{coverage}")`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    locales: SmallVec<[Locale; 8]>,
+    missing: Vec<(Locale, String)>,
+}
+
+impl Coverage {
+    // The fallback locale is usually translated by the catch-all arms, so it
+    // isn't checked.
+    pub fn new(locales: &[Locale]) -> Self {
+        let fallback_locale = SETTINGS
+            .get()
+            .map(|settings| settings.read().unwrap().fallback_locale)
+            .unwrap_or_else(|| Settings::default().fallback_locale);
+        Coverage {
+            locales: locales
+                .iter()
+                .copied()
+                .filter(|locale| *locale != fallback_locale)
+                .collect(),
+            missing: Vec::new(),
+        }
+    }
+
+    pub fn check<T: Translated>(mut self) -> Self {
+        for (variant, locale) in T::missing_translations(&self.locales) {
+            self.missing
+                .push((locale, format!("{}::{}", T::NAME, variant)));
+        }
+        self
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn missing(&self, locale: Locale) -> impl Iterator<Item = &str> {
+        self.missing
+            .iter()
+            .filter(move |(missing, _)| *missing == locale)
+            .map(|(_, variant)| variant.as_str())
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &locale in &self.locales {
+            let missing: Vec<&str> = self.missing(locale).collect();
+            if !missing.is_empty() {
+                writeln!(f, "{locale}: {}", missing.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{current_locale, Language, Localizable, Localized};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Gender {
+    Masculine,
+    Feminine,
+    Neuter,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Case {
+    Nominative,
+    Accusative,
+    Dative,
+    Genitive,
+}
+
+// Implemented by nouns that are interpolated into translations, so that arms
+// can branch on them with guards, for example
+// `Locale(Language::De, _) if item.gender(Language::De) == Gender::Feminine => "Die {item} ..."`.
+pub trait Grammatical: Localizable {
+    fn gender(&self, language: Language) -> Gender;
+
+    // The noun in the given case, in the locale of the surrounding translation.
+    fn inflect(self, case: Case) -> Localized {
+        let _ = case;
+        self.localize_with(&[current_locale()])
+    }
+}
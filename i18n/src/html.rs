@@ -0,0 +1,182 @@
+use crate::Localized;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Markup {
+    Text(String),
+    Element(String, Vec<Markup>),
+}
+
+// A translation with placeholder tags such as `Click <link>here</link>` or
+// `<icon/>`. The text is never interpreted as HTML, each tag is rendered by a
+// callback of the caller instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalizedHtml(Vec<Markup>);
+
+impl LocalizedHtml {
+    // Tags that aren't well-formed or don't match are kept as text.
+    pub fn parse(source: &str) -> Self {
+        let mut stack: Vec<(String, Vec<Markup>)> = vec![(String::new(), Vec::new())];
+        let mut rest = source;
+
+        while let Some(start) = rest.find('<') {
+            push_text(&mut stack.last_mut().unwrap().1, &rest[..start]);
+            rest = &rest[start..];
+
+            let (markup, len) = match tag(rest) {
+                Some((Tag::Open(name), len)) => {
+                    stack.push((name.to_owned(), Vec::new()));
+                    rest = &rest[len..];
+                    continue;
+                }
+                Some((Tag::SelfClosing(name), len)) => {
+                    (Markup::Element(name.to_owned(), Vec::new()), len)
+                }
+                Some((Tag::Close(name), len))
+                    if stack.len() > 1 && stack.last().unwrap().0 == name =>
+                {
+                    let (name, children) = stack.pop().unwrap();
+                    (Markup::Element(name, children), len)
+                }
+                _ => (Markup::Text("<".to_owned()), 1),
+            };
+            match markup {
+                Markup::Text(text) => push_text(&mut stack.last_mut().unwrap().1, &text),
+                element => stack.last_mut().unwrap().1.push(element),
+            }
+            rest = &rest[len..];
+        }
+        push_text(&mut stack.last_mut().unwrap().1, rest);
+
+        while stack.len() > 1 {
+            let (name, children) = stack.pop().unwrap();
+            let parent = &mut stack.last_mut().unwrap().1;
+            push_text(parent, &format!("<{name}>"));
+            for child in children {
+                match child {
+                    Markup::Text(text) => push_text(parent, &text),
+                    element => parent.push(element),
+                }
+            }
+        }
+
+        LocalizedHtml(stack.pop().unwrap().1)
+    }
+
+    pub fn markup(&self) -> &[Markup] {
+        &self.0
+    }
+
+    // Renders the markup as an HTML string, with all text escaped. The
+    // callback gets the tag name and the already rendered children.
+    pub fn to_html_string(&self, render: impl Fn(&str, String) -> String) -> String {
+        fn walk(markup: &[Markup], render: &impl Fn(&str, String) -> String) -> String {
+            markup
+                .iter()
+                .map(|markup| match markup {
+                    Markup::Text(text) => escape_html(text),
+                    Markup::Element(name, children) => render(name, walk(children, render)),
+                })
+                .collect()
+        }
+        walk(&self.0, &render)
+    }
+
+    #[cfg(feature = "seed")]
+    pub fn to_nodes<Ms>(
+        &self,
+        render: impl Fn(&str, Vec<seed::virtual_dom::Node<Ms>>) -> seed::virtual_dom::Node<Ms>,
+    ) -> Vec<seed::virtual_dom::Node<Ms>> {
+        fn walk<Ms>(
+            markup: &[Markup],
+            render: &impl Fn(&str, Vec<seed::virtual_dom::Node<Ms>>) -> seed::virtual_dom::Node<Ms>,
+        ) -> Vec<seed::virtual_dom::Node<Ms>> {
+            markup
+                .iter()
+                .map(|markup| match markup {
+                    Markup::Text(text) => {
+                        seed::virtual_dom::Node::Text(seed::virtual_dom::Text::new(text.clone()))
+                    }
+                    Markup::Element(name, children) => render(name, walk(children, render)),
+                })
+                .collect()
+        }
+        walk(&self.0, &render)
+    }
+
+    #[cfg(feature = "yew")]
+    pub fn to_html(&self, render: impl Fn(&str, yew::Html) -> yew::Html) -> yew::Html {
+        fn walk(markup: &[Markup], render: &impl Fn(&str, yew::Html) -> yew::Html) -> yew::Html {
+            markup
+                .iter()
+                .map(|markup| match markup {
+                    Markup::Text(text) => yew::Html::from(text.clone()),
+                    Markup::Element(name, children) => render(name, walk(children, render)),
+                })
+                .collect()
+        }
+        walk(&self.0, &render)
+    }
+}
+
+impl From<Localized> for LocalizedHtml {
+    fn from(value: Localized) -> Self {
+        LocalizedHtml::parse(&value.0)
+    }
+}
+
+impl Localized {
+    pub fn html(self) -> LocalizedHtml {
+        LocalizedHtml::from(self)
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+enum Tag<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    SelfClosing(&'a str),
+}
+
+// Parses a tag at the start of the string and returns it with its length.
+fn tag(source: &str) -> Option<(Tag<'_>, usize)> {
+    let end = source.find('>')?;
+    let inner = &source[1..end];
+    let tag = if let Some(name) = inner.strip_prefix('/') {
+        Tag::Close(name)
+    } else if let Some(name) = inner.strip_suffix('/') {
+        Tag::SelfClosing(name)
+    } else {
+        Tag::Open(inner)
+    };
+
+    let (Tag::Open(name) | Tag::Close(name) | Tag::SelfClosing(name)) = tag;
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some((tag, end + 1))
+}
+
+fn push_text(markup: &mut Vec<Markup>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match markup.last_mut() {
+        Some(Markup::Text(last)) => last.push_str(text),
+        _ => markup.push(Markup::Text(text.to_owned())),
+    }
+}
@@ -1,4 +1,27 @@
+#[cfg(feature = "build")]
+pub mod build;
+#[cfg(feature = "fluent")]
+mod catalog;
+mod collation;
+mod coverage;
+mod grammar;
+mod html;
+mod list;
+mod ordinal;
+mod time;
+
+#[cfg(feature = "fluent")]
+pub use catalog::{load_catalog, load_catalog_file, Arg, CatalogError};
+pub use collation::{collate, sort_localized, sort_localized_by_key};
+pub use coverage::{Coverage, Translated};
+pub use grammar::{Case, Gender, Grammatical};
+pub use html::{escape_html, LocalizedHtml, Markup};
+pub use list::join_localized;
+pub use ordinal::{Ordinal, Spelled};
+pub use time::RelativeTime;
+
 use std::{
+    cell::RefCell,
     fmt::Display,
     str::FromStr,
     sync::{Arc, OnceLock, RwLock},
@@ -6,11 +29,13 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use strum::{Display, EnumString};
+pub use strum::IntoEnumIterator;
+use strum::{Display, EnumIter, EnumString};
 
 struct Settings {
     fallback_locale: Locale,
     locales: SmallVec<[Locale; 8]>,
+    supported_locales: SmallVec<[Locale; 8]>,
 }
 
 impl Default for Settings {
@@ -18,6 +43,7 @@ impl Default for Settings {
         Self {
             fallback_locale: Locale(Language::En, None),
             locales: SmallVec::new(),
+            supported_locales: SmallVec::new(),
         }
     }
 }
@@ -40,23 +66,489 @@ pub fn set_locales(locales: &[Locale]) {
         .locales = SmallVec::from_slice(locales);
 }
 
-fn get_locales() -> SmallVec<[Locale; 8]> {
-    let settings = SETTINGS
+pub fn set_supported_locales(locales: &[Locale]) {
+    SETTINGS
+        .get_or_init(|| Arc::new(RwLock::new(Settings::default())))
+        .write()
+        .unwrap()
+        .supported_locales = SmallVec::from_slice(locales);
+}
+
+pub fn get_supported_locales() -> SmallVec<[Locale; 8]> {
+    SETTINGS
         .get_or_init(|| Arc::new(RwLock::new(Settings::default())))
         .read()
-        .unwrap();
-    let mut locales = settings.locales.clone();
-    locales.push(settings.fallback_locale);
-    locales
+        .unwrap()
+        .supported_locales
+        .clone()
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Display, EnumString, PartialEq, Eq)]
+// The locales set with `set_locales`, without the fallback.
+pub fn get_global_locales() -> SmallVec<[Locale; 8]> {
+    SETTINGS
+        .get_or_init(|| Arc::new(RwLock::new(Settings::default())))
+        .read()
+        .unwrap()
+        .locales
+        .clone()
+}
+
+fn get_locales() -> SmallVec<[Locale; 8]> {
+    if let Some(context) = CURRENT_CONTEXT.with(|current| current.borrow().clone()) {
+        return context.locales;
+    }
+    #[cfg(feature = "tokio")]
+    if let Ok(context) = TASK_CONTEXT.try_with(LocaleContext::clone) {
+        return context.locales;
+    }
+    LocaleContext::global().locales
+}
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<LocaleContext>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "tokio")]
+tokio::task_local! {
+    static TASK_CONTEXT: LocaleContext;
+}
+
+// Restores the previous context when a scope ends, even if it panics.
+struct ScopeGuard(Option<LocaleContext>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT_CONTEXT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+// The locales of a single user, so that e.g. a server can render strings for
+// many connections concurrently without touching the global settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleContext {
+    locales: SmallVec<[Locale; 8]>,
+}
+
+impl LocaleContext {
+    pub fn new(locales: &[Locale]) -> Self {
+        let settings = SETTINGS
+            .get_or_init(|| Arc::new(RwLock::new(Settings::default())))
+            .read()
+            .unwrap();
+        let mut locales = SmallVec::from_slice(locales);
+        locales.push(settings.fallback_locale);
+        LocaleContext { locales }
+    }
+
+    pub fn global() -> Self {
+        let settings = SETTINGS
+            .get_or_init(|| Arc::new(RwLock::new(Settings::default())))
+            .read()
+            .unwrap();
+        let mut locales = settings.locales.clone();
+        locales.push(settings.fallback_locale);
+        LocaleContext { locales }
+    }
+
+    pub fn current() -> Self {
+        LocaleContext {
+            locales: get_locales(),
+        }
+    }
+
+    pub fn locales(&self) -> &[Locale] {
+        &self.locales
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locales[0]
+    }
+
+    pub fn localize(&self, localizable: impl Localizable) -> Localized {
+        localizable.localize_with(&self.locales)
+    }
+
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT_CONTEXT.with(|current| current.replace(Some(self.clone())));
+        let _guard = ScopeGuard(previous);
+        f()
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn scope_task<F: std::future::Future>(self, future: F) -> F::Output {
+        TASK_CONTEXT.scope(self, future).await
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Display, EnumString, EnumIter, PartialEq, Eq,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum Language {
     En,
     Fr,
     It,
     De,
+    // The remaining ISO 639-1 codes.
+    #[cfg(feature = "all-languages")]
+    Aa,
+    #[cfg(feature = "all-languages")]
+    Ab,
+    #[cfg(feature = "all-languages")]
+    Ae,
+    #[cfg(feature = "all-languages")]
+    Af,
+    #[cfg(feature = "all-languages")]
+    Ak,
+    #[cfg(feature = "all-languages")]
+    Am,
+    #[cfg(feature = "all-languages")]
+    An,
+    #[cfg(feature = "all-languages")]
+    Ar,
+    #[cfg(feature = "all-languages")]
+    As,
+    #[cfg(feature = "all-languages")]
+    Av,
+    #[cfg(feature = "all-languages")]
+    Ay,
+    #[cfg(feature = "all-languages")]
+    Az,
+    #[cfg(feature = "all-languages")]
+    Ba,
+    #[cfg(feature = "all-languages")]
+    Be,
+    #[cfg(feature = "all-languages")]
+    Bg,
+    #[cfg(feature = "all-languages")]
+    Bi,
+    #[cfg(feature = "all-languages")]
+    Bm,
+    #[cfg(feature = "all-languages")]
+    Bn,
+    #[cfg(feature = "all-languages")]
+    Bo,
+    #[cfg(feature = "all-languages")]
+    Br,
+    #[cfg(feature = "all-languages")]
+    Bs,
+    #[cfg(feature = "all-languages")]
+    Ca,
+    #[cfg(feature = "all-languages")]
+    Ce,
+    #[cfg(feature = "all-languages")]
+    Ch,
+    #[cfg(feature = "all-languages")]
+    Co,
+    #[cfg(feature = "all-languages")]
+    Cr,
+    #[cfg(feature = "all-languages")]
+    Cs,
+    #[cfg(feature = "all-languages")]
+    Cu,
+    #[cfg(feature = "all-languages")]
+    Cv,
+    #[cfg(feature = "all-languages")]
+    Cy,
+    #[cfg(feature = "all-languages")]
+    Da,
+    #[cfg(feature = "all-languages")]
+    Dv,
+    #[cfg(feature = "all-languages")]
+    Dz,
+    #[cfg(feature = "all-languages")]
+    Ee,
+    #[cfg(feature = "all-languages")]
+    El,
+    #[cfg(feature = "all-languages")]
+    Eo,
+    #[cfg(feature = "all-languages")]
+    Es,
+    #[cfg(feature = "all-languages")]
+    Et,
+    #[cfg(feature = "all-languages")]
+    Eu,
+    #[cfg(feature = "all-languages")]
+    Fa,
+    #[cfg(feature = "all-languages")]
+    Ff,
+    #[cfg(feature = "all-languages")]
+    Fi,
+    #[cfg(feature = "all-languages")]
+    Fj,
+    #[cfg(feature = "all-languages")]
+    Fo,
+    #[cfg(feature = "all-languages")]
+    Fy,
+    #[cfg(feature = "all-languages")]
+    Ga,
+    #[cfg(feature = "all-languages")]
+    Gd,
+    #[cfg(feature = "all-languages")]
+    Gl,
+    #[cfg(feature = "all-languages")]
+    Gn,
+    #[cfg(feature = "all-languages")]
+    Gu,
+    #[cfg(feature = "all-languages")]
+    Gv,
+    #[cfg(feature = "all-languages")]
+    Ha,
+    #[cfg(feature = "all-languages")]
+    He,
+    #[cfg(feature = "all-languages")]
+    Hi,
+    #[cfg(feature = "all-languages")]
+    Ho,
+    #[cfg(feature = "all-languages")]
+    Hr,
+    #[cfg(feature = "all-languages")]
+    Ht,
+    #[cfg(feature = "all-languages")]
+    Hu,
+    #[cfg(feature = "all-languages")]
+    Hy,
+    #[cfg(feature = "all-languages")]
+    Hz,
+    #[cfg(feature = "all-languages")]
+    Ia,
+    #[cfg(feature = "all-languages")]
+    Id,
+    #[cfg(feature = "all-languages")]
+    Ie,
+    #[cfg(feature = "all-languages")]
+    Ig,
+    #[cfg(feature = "all-languages")]
+    Ii,
+    #[cfg(feature = "all-languages")]
+    Ik,
+    #[cfg(feature = "all-languages")]
+    Io,
+    #[cfg(feature = "all-languages")]
+    Is,
+    #[cfg(feature = "all-languages")]
+    Iu,
+    #[cfg(feature = "all-languages")]
+    Ja,
+    #[cfg(feature = "all-languages")]
+    Jv,
+    #[cfg(feature = "all-languages")]
+    Ka,
+    #[cfg(feature = "all-languages")]
+    Kg,
+    #[cfg(feature = "all-languages")]
+    Ki,
+    #[cfg(feature = "all-languages")]
+    Kj,
+    #[cfg(feature = "all-languages")]
+    Kk,
+    #[cfg(feature = "all-languages")]
+    Kl,
+    #[cfg(feature = "all-languages")]
+    Km,
+    #[cfg(feature = "all-languages")]
+    Kn,
+    #[cfg(feature = "all-languages")]
+    Ko,
+    #[cfg(feature = "all-languages")]
+    Kr,
+    #[cfg(feature = "all-languages")]
+    Ks,
+    #[cfg(feature = "all-languages")]
+    Ku,
+    #[cfg(feature = "all-languages")]
+    Kv,
+    #[cfg(feature = "all-languages")]
+    Kw,
+    #[cfg(feature = "all-languages")]
+    Ky,
+    #[cfg(feature = "all-languages")]
+    La,
+    #[cfg(feature = "all-languages")]
+    Lb,
+    #[cfg(feature = "all-languages")]
+    Lg,
+    #[cfg(feature = "all-languages")]
+    Li,
+    #[cfg(feature = "all-languages")]
+    Ln,
+    #[cfg(feature = "all-languages")]
+    Lo,
+    #[cfg(feature = "all-languages")]
+    Lt,
+    #[cfg(feature = "all-languages")]
+    Lu,
+    #[cfg(feature = "all-languages")]
+    Lv,
+    #[cfg(feature = "all-languages")]
+    Mg,
+    #[cfg(feature = "all-languages")]
+    Mh,
+    #[cfg(feature = "all-languages")]
+    Mi,
+    #[cfg(feature = "all-languages")]
+    Mk,
+    #[cfg(feature = "all-languages")]
+    Ml,
+    #[cfg(feature = "all-languages")]
+    Mn,
+    #[cfg(feature = "all-languages")]
+    Mr,
+    #[cfg(feature = "all-languages")]
+    Ms,
+    #[cfg(feature = "all-languages")]
+    Mt,
+    #[cfg(feature = "all-languages")]
+    My,
+    #[cfg(feature = "all-languages")]
+    Na,
+    #[cfg(feature = "all-languages")]
+    Nb,
+    #[cfg(feature = "all-languages")]
+    Nd,
+    #[cfg(feature = "all-languages")]
+    Ne,
+    #[cfg(feature = "all-languages")]
+    Ng,
+    #[cfg(feature = "all-languages")]
+    Nl,
+    #[cfg(feature = "all-languages")]
+    Nn,
+    #[cfg(feature = "all-languages")]
+    No,
+    #[cfg(feature = "all-languages")]
+    Nr,
+    #[cfg(feature = "all-languages")]
+    Nv,
+    #[cfg(feature = "all-languages")]
+    Ny,
+    #[cfg(feature = "all-languages")]
+    Oc,
+    #[cfg(feature = "all-languages")]
+    Oj,
+    #[cfg(feature = "all-languages")]
+    Om,
+    #[cfg(feature = "all-languages")]
+    Or,
+    #[cfg(feature = "all-languages")]
+    Os,
+    #[cfg(feature = "all-languages")]
+    Pa,
+    #[cfg(feature = "all-languages")]
+    Pi,
+    #[cfg(feature = "all-languages")]
+    Pl,
+    #[cfg(feature = "all-languages")]
+    Ps,
+    #[cfg(feature = "all-languages")]
+    Pt,
+    #[cfg(feature = "all-languages")]
+    Qu,
+    #[cfg(feature = "all-languages")]
+    Rm,
+    #[cfg(feature = "all-languages")]
+    Rn,
+    #[cfg(feature = "all-languages")]
+    Ro,
+    #[cfg(feature = "all-languages")]
+    Ru,
+    #[cfg(feature = "all-languages")]
+    Rw,
+    #[cfg(feature = "all-languages")]
+    Sa,
+    #[cfg(feature = "all-languages")]
+    Sc,
+    #[cfg(feature = "all-languages")]
+    Sd,
+    #[cfg(feature = "all-languages")]
+    Se,
+    #[cfg(feature = "all-languages")]
+    Sg,
+    #[cfg(feature = "all-languages")]
+    Si,
+    #[cfg(feature = "all-languages")]
+    Sk,
+    #[cfg(feature = "all-languages")]
+    Sl,
+    #[cfg(feature = "all-languages")]
+    Sm,
+    #[cfg(feature = "all-languages")]
+    Sn,
+    #[cfg(feature = "all-languages")]
+    So,
+    #[cfg(feature = "all-languages")]
+    Sq,
+    #[cfg(feature = "all-languages")]
+    Sr,
+    #[cfg(feature = "all-languages")]
+    Ss,
+    #[cfg(feature = "all-languages")]
+    St,
+    #[cfg(feature = "all-languages")]
+    Su,
+    #[cfg(feature = "all-languages")]
+    Sv,
+    #[cfg(feature = "all-languages")]
+    Sw,
+    #[cfg(feature = "all-languages")]
+    Ta,
+    #[cfg(feature = "all-languages")]
+    Te,
+    #[cfg(feature = "all-languages")]
+    Tg,
+    #[cfg(feature = "all-languages")]
+    Th,
+    #[cfg(feature = "all-languages")]
+    Ti,
+    #[cfg(feature = "all-languages")]
+    Tk,
+    #[cfg(feature = "all-languages")]
+    Tl,
+    #[cfg(feature = "all-languages")]
+    Tn,
+    #[cfg(feature = "all-languages")]
+    To,
+    #[cfg(feature = "all-languages")]
+    Tr,
+    #[cfg(feature = "all-languages")]
+    Ts,
+    #[cfg(feature = "all-languages")]
+    Tt,
+    #[cfg(feature = "all-languages")]
+    Tw,
+    #[cfg(feature = "all-languages")]
+    Ty,
+    #[cfg(feature = "all-languages")]
+    Ug,
+    #[cfg(feature = "all-languages")]
+    Uk,
+    #[cfg(feature = "all-languages")]
+    Ur,
+    #[cfg(feature = "all-languages")]
+    Uz,
+    #[cfg(feature = "all-languages")]
+    Ve,
+    #[cfg(feature = "all-languages")]
+    Vi,
+    #[cfg(feature = "all-languages")]
+    Vo,
+    #[cfg(feature = "all-languages")]
+    Wa,
+    #[cfg(feature = "all-languages")]
+    Wo,
+    #[cfg(feature = "all-languages")]
+    Xh,
+    #[cfg(feature = "all-languages")]
+    Yi,
+    #[cfg(feature = "all-languages")]
+    Yo,
+    #[cfg(feature = "all-languages")]
+    Za,
+    #[cfg(feature = "all-languages")]
+    Zh,
+    #[cfg(feature = "all-languages")]
+    Zu,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Display, EnumString, PartialEq, Eq)]
@@ -66,12 +558,507 @@ pub enum Country {
     De,
     Gb,
     Us,
+    // The remaining ISO 3166-1 alpha-2 codes.
+    #[cfg(feature = "all-countries")]
+    Ad,
+    #[cfg(feature = "all-countries")]
+    Ae,
+    #[cfg(feature = "all-countries")]
+    Af,
+    #[cfg(feature = "all-countries")]
+    Ag,
+    #[cfg(feature = "all-countries")]
+    Ai,
+    #[cfg(feature = "all-countries")]
+    Al,
+    #[cfg(feature = "all-countries")]
+    Am,
+    #[cfg(feature = "all-countries")]
+    Ao,
+    #[cfg(feature = "all-countries")]
+    Aq,
+    #[cfg(feature = "all-countries")]
+    Ar,
+    #[cfg(feature = "all-countries")]
+    As,
+    #[cfg(feature = "all-countries")]
+    At,
+    #[cfg(feature = "all-countries")]
+    Au,
+    #[cfg(feature = "all-countries")]
+    Aw,
+    #[cfg(feature = "all-countries")]
+    Ax,
+    #[cfg(feature = "all-countries")]
+    Az,
+    #[cfg(feature = "all-countries")]
+    Ba,
+    #[cfg(feature = "all-countries")]
+    Bb,
+    #[cfg(feature = "all-countries")]
+    Bd,
+    #[cfg(feature = "all-countries")]
+    Be,
+    #[cfg(feature = "all-countries")]
+    Bf,
+    #[cfg(feature = "all-countries")]
+    Bg,
+    #[cfg(feature = "all-countries")]
+    Bh,
+    #[cfg(feature = "all-countries")]
+    Bi,
+    #[cfg(feature = "all-countries")]
+    Bj,
+    #[cfg(feature = "all-countries")]
+    Bl,
+    #[cfg(feature = "all-countries")]
+    Bm,
+    #[cfg(feature = "all-countries")]
+    Bn,
+    #[cfg(feature = "all-countries")]
+    Bo,
+    #[cfg(feature = "all-countries")]
+    Bq,
+    #[cfg(feature = "all-countries")]
+    Br,
+    #[cfg(feature = "all-countries")]
+    Bs,
+    #[cfg(feature = "all-countries")]
+    Bt,
+    #[cfg(feature = "all-countries")]
+    Bv,
+    #[cfg(feature = "all-countries")]
+    Bw,
+    #[cfg(feature = "all-countries")]
+    By,
+    #[cfg(feature = "all-countries")]
+    Bz,
+    #[cfg(feature = "all-countries")]
+    Ca,
+    #[cfg(feature = "all-countries")]
+    Cc,
+    #[cfg(feature = "all-countries")]
+    Cd,
+    #[cfg(feature = "all-countries")]
+    Cf,
+    #[cfg(feature = "all-countries")]
+    Cg,
+    #[cfg(feature = "all-countries")]
+    Ci,
+    #[cfg(feature = "all-countries")]
+    Ck,
+    #[cfg(feature = "all-countries")]
+    Cl,
+    #[cfg(feature = "all-countries")]
+    Cm,
+    #[cfg(feature = "all-countries")]
+    Cn,
+    #[cfg(feature = "all-countries")]
+    Co,
+    #[cfg(feature = "all-countries")]
+    Cr,
+    #[cfg(feature = "all-countries")]
+    Cu,
+    #[cfg(feature = "all-countries")]
+    Cv,
+    #[cfg(feature = "all-countries")]
+    Cw,
+    #[cfg(feature = "all-countries")]
+    Cx,
+    #[cfg(feature = "all-countries")]
+    Cy,
+    #[cfg(feature = "all-countries")]
+    Cz,
+    #[cfg(feature = "all-countries")]
+    Dj,
+    #[cfg(feature = "all-countries")]
+    Dk,
+    #[cfg(feature = "all-countries")]
+    Dm,
+    #[cfg(feature = "all-countries")]
+    Do,
+    #[cfg(feature = "all-countries")]
+    Dz,
+    #[cfg(feature = "all-countries")]
+    Ec,
+    #[cfg(feature = "all-countries")]
+    Ee,
+    #[cfg(feature = "all-countries")]
+    Eg,
+    #[cfg(feature = "all-countries")]
+    Eh,
+    #[cfg(feature = "all-countries")]
+    Er,
+    #[cfg(feature = "all-countries")]
+    Es,
+    #[cfg(feature = "all-countries")]
+    Et,
+    #[cfg(feature = "all-countries")]
+    Fi,
+    #[cfg(feature = "all-countries")]
+    Fj,
+    #[cfg(feature = "all-countries")]
+    Fk,
+    #[cfg(feature = "all-countries")]
+    Fm,
+    #[cfg(feature = "all-countries")]
+    Fo,
+    #[cfg(feature = "all-countries")]
+    Fr,
+    #[cfg(feature = "all-countries")]
+    Ga,
+    #[cfg(feature = "all-countries")]
+    Gd,
+    #[cfg(feature = "all-countries")]
+    Ge,
+    #[cfg(feature = "all-countries")]
+    Gf,
+    #[cfg(feature = "all-countries")]
+    Gg,
+    #[cfg(feature = "all-countries")]
+    Gh,
+    #[cfg(feature = "all-countries")]
+    Gi,
+    #[cfg(feature = "all-countries")]
+    Gl,
+    #[cfg(feature = "all-countries")]
+    Gm,
+    #[cfg(feature = "all-countries")]
+    Gn,
+    #[cfg(feature = "all-countries")]
+    Gp,
+    #[cfg(feature = "all-countries")]
+    Gq,
+    #[cfg(feature = "all-countries")]
+    Gr,
+    #[cfg(feature = "all-countries")]
+    Gs,
+    #[cfg(feature = "all-countries")]
+    Gt,
+    #[cfg(feature = "all-countries")]
+    Gu,
+    #[cfg(feature = "all-countries")]
+    Gw,
+    #[cfg(feature = "all-countries")]
+    Gy,
+    #[cfg(feature = "all-countries")]
+    Hk,
+    #[cfg(feature = "all-countries")]
+    Hm,
+    #[cfg(feature = "all-countries")]
+    Hn,
+    #[cfg(feature = "all-countries")]
+    Hr,
+    #[cfg(feature = "all-countries")]
+    Ht,
+    #[cfg(feature = "all-countries")]
+    Hu,
+    #[cfg(feature = "all-countries")]
+    Id,
+    #[cfg(feature = "all-countries")]
+    Ie,
+    #[cfg(feature = "all-countries")]
+    Il,
+    #[cfg(feature = "all-countries")]
+    Im,
+    #[cfg(feature = "all-countries")]
+    In,
+    #[cfg(feature = "all-countries")]
+    Io,
+    #[cfg(feature = "all-countries")]
+    Iq,
+    #[cfg(feature = "all-countries")]
+    Ir,
+    #[cfg(feature = "all-countries")]
+    Is,
+    #[cfg(feature = "all-countries")]
+    It,
+    #[cfg(feature = "all-countries")]
+    Je,
+    #[cfg(feature = "all-countries")]
+    Jm,
+    #[cfg(feature = "all-countries")]
+    Jo,
+    #[cfg(feature = "all-countries")]
+    Jp,
+    #[cfg(feature = "all-countries")]
+    Ke,
+    #[cfg(feature = "all-countries")]
+    Kg,
+    #[cfg(feature = "all-countries")]
+    Kh,
+    #[cfg(feature = "all-countries")]
+    Ki,
+    #[cfg(feature = "all-countries")]
+    Km,
+    #[cfg(feature = "all-countries")]
+    Kn,
+    #[cfg(feature = "all-countries")]
+    Kp,
+    #[cfg(feature = "all-countries")]
+    Kr,
+    #[cfg(feature = "all-countries")]
+    Kw,
+    #[cfg(feature = "all-countries")]
+    Ky,
+    #[cfg(feature = "all-countries")]
+    Kz,
+    #[cfg(feature = "all-countries")]
+    La,
+    #[cfg(feature = "all-countries")]
+    Lb,
+    #[cfg(feature = "all-countries")]
+    Lc,
+    #[cfg(feature = "all-countries")]
+    Li,
+    #[cfg(feature = "all-countries")]
+    Lk,
+    #[cfg(feature = "all-countries")]
+    Lr,
+    #[cfg(feature = "all-countries")]
+    Ls,
+    #[cfg(feature = "all-countries")]
+    Lt,
+    #[cfg(feature = "all-countries")]
+    Lu,
+    #[cfg(feature = "all-countries")]
+    Lv,
+    #[cfg(feature = "all-countries")]
+    Ly,
+    #[cfg(feature = "all-countries")]
+    Ma,
+    #[cfg(feature = "all-countries")]
+    Mc,
+    #[cfg(feature = "all-countries")]
+    Md,
+    #[cfg(feature = "all-countries")]
+    Me,
+    #[cfg(feature = "all-countries")]
+    Mf,
+    #[cfg(feature = "all-countries")]
+    Mg,
+    #[cfg(feature = "all-countries")]
+    Mh,
+    #[cfg(feature = "all-countries")]
+    Mk,
+    #[cfg(feature = "all-countries")]
+    Ml,
+    #[cfg(feature = "all-countries")]
+    Mm,
+    #[cfg(feature = "all-countries")]
+    Mn,
+    #[cfg(feature = "all-countries")]
+    Mo,
+    #[cfg(feature = "all-countries")]
+    Mp,
+    #[cfg(feature = "all-countries")]
+    Mq,
+    #[cfg(feature = "all-countries")]
+    Mr,
+    #[cfg(feature = "all-countries")]
+    Ms,
+    #[cfg(feature = "all-countries")]
+    Mt,
+    #[cfg(feature = "all-countries")]
+    Mu,
+    #[cfg(feature = "all-countries")]
+    Mv,
+    #[cfg(feature = "all-countries")]
+    Mw,
+    #[cfg(feature = "all-countries")]
+    Mx,
+    #[cfg(feature = "all-countries")]
+    My,
+    #[cfg(feature = "all-countries")]
+    Mz,
+    #[cfg(feature = "all-countries")]
+    Na,
+    #[cfg(feature = "all-countries")]
+    Nc,
+    #[cfg(feature = "all-countries")]
+    Ne,
+    #[cfg(feature = "all-countries")]
+    Nf,
+    #[cfg(feature = "all-countries")]
+    Ng,
+    #[cfg(feature = "all-countries")]
+    Ni,
+    #[cfg(feature = "all-countries")]
+    Nl,
+    #[cfg(feature = "all-countries")]
+    No,
+    #[cfg(feature = "all-countries")]
+    Np,
+    #[cfg(feature = "all-countries")]
+    Nr,
+    #[cfg(feature = "all-countries")]
+    Nu,
+    #[cfg(feature = "all-countries")]
+    Nz,
+    #[cfg(feature = "all-countries")]
+    Om,
+    #[cfg(feature = "all-countries")]
+    Pa,
+    #[cfg(feature = "all-countries")]
+    Pe,
+    #[cfg(feature = "all-countries")]
+    Pf,
+    #[cfg(feature = "all-countries")]
+    Pg,
+    #[cfg(feature = "all-countries")]
+    Ph,
+    #[cfg(feature = "all-countries")]
+    Pk,
+    #[cfg(feature = "all-countries")]
+    Pl,
+    #[cfg(feature = "all-countries")]
+    Pm,
+    #[cfg(feature = "all-countries")]
+    Pn,
+    #[cfg(feature = "all-countries")]
+    Pr,
+    #[cfg(feature = "all-countries")]
+    Ps,
+    #[cfg(feature = "all-countries")]
+    Pt,
+    #[cfg(feature = "all-countries")]
+    Pw,
+    #[cfg(feature = "all-countries")]
+    Py,
+    #[cfg(feature = "all-countries")]
+    Qa,
+    #[cfg(feature = "all-countries")]
+    Re,
+    #[cfg(feature = "all-countries")]
+    Ro,
+    #[cfg(feature = "all-countries")]
+    Rs,
+    #[cfg(feature = "all-countries")]
+    Ru,
+    #[cfg(feature = "all-countries")]
+    Rw,
+    #[cfg(feature = "all-countries")]
+    Sa,
+    #[cfg(feature = "all-countries")]
+    Sb,
+    #[cfg(feature = "all-countries")]
+    Sc,
+    #[cfg(feature = "all-countries")]
+    Sd,
+    #[cfg(feature = "all-countries")]
+    Se,
+    #[cfg(feature = "all-countries")]
+    Sg,
+    #[cfg(feature = "all-countries")]
+    Sh,
+    #[cfg(feature = "all-countries")]
+    Si,
+    #[cfg(feature = "all-countries")]
+    Sj,
+    #[cfg(feature = "all-countries")]
+    Sk,
+    #[cfg(feature = "all-countries")]
+    Sl,
+    #[cfg(feature = "all-countries")]
+    Sm,
+    #[cfg(feature = "all-countries")]
+    Sn,
+    #[cfg(feature = "all-countries")]
+    So,
+    #[cfg(feature = "all-countries")]
+    Sr,
+    #[cfg(feature = "all-countries")]
+    Ss,
+    #[cfg(feature = "all-countries")]
+    St,
+    #[cfg(feature = "all-countries")]
+    Sv,
+    #[cfg(feature = "all-countries")]
+    Sx,
+    #[cfg(feature = "all-countries")]
+    Sy,
+    #[cfg(feature = "all-countries")]
+    Sz,
+    #[cfg(feature = "all-countries")]
+    Tc,
+    #[cfg(feature = "all-countries")]
+    Td,
+    #[cfg(feature = "all-countries")]
+    Tf,
+    #[cfg(feature = "all-countries")]
+    Tg,
+    #[cfg(feature = "all-countries")]
+    Th,
+    #[cfg(feature = "all-countries")]
+    Tj,
+    #[cfg(feature = "all-countries")]
+    Tk,
+    #[cfg(feature = "all-countries")]
+    Tl,
+    #[cfg(feature = "all-countries")]
+    Tm,
+    #[cfg(feature = "all-countries")]
+    Tn,
+    #[cfg(feature = "all-countries")]
+    To,
+    #[cfg(feature = "all-countries")]
+    Tr,
+    #[cfg(feature = "all-countries")]
+    Tt,
+    #[cfg(feature = "all-countries")]
+    Tv,
+    #[cfg(feature = "all-countries")]
+    Tw,
+    #[cfg(feature = "all-countries")]
+    Tz,
+    #[cfg(feature = "all-countries")]
+    Ua,
+    #[cfg(feature = "all-countries")]
+    Ug,
+    #[cfg(feature = "all-countries")]
+    Um,
+    #[cfg(feature = "all-countries")]
+    Uy,
+    #[cfg(feature = "all-countries")]
+    Uz,
+    #[cfg(feature = "all-countries")]
+    Va,
+    #[cfg(feature = "all-countries")]
+    Vc,
+    #[cfg(feature = "all-countries")]
+    Ve,
+    #[cfg(feature = "all-countries")]
+    Vg,
+    #[cfg(feature = "all-countries")]
+    Vi,
+    #[cfg(feature = "all-countries")]
+    Vn,
+    #[cfg(feature = "all-countries")]
+    Vu,
+    #[cfg(feature = "all-countries")]
+    Wf,
+    #[cfg(feature = "all-countries")]
+    Ws,
+    #[cfg(feature = "all-countries")]
+    Ye,
+    #[cfg(feature = "all-countries")]
+    Yt,
+    #[cfg(feature = "all-countries")]
+    Za,
+    #[cfg(feature = "all-countries")]
+    Zm,
+    #[cfg(feature = "all-countries")]
+    Zw,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Locale(pub Language, pub Option<Country>);
 
+// Name of the cookie and the local storage entry holding the user's choice.
+pub const LOCALE_COOKIE: &str = "locale";
+
 impl Locale {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(string: &str) -> Option<Locale> {
         if let Some((language, country)) = string.split_once('-') {
             return Some(Locale(
@@ -88,63 +1075,220 @@ impl Locale {
         Some(Locale(Language::from_str(string).ok()?, None))
     }
 
-    /*
-    pub fn best_match(user_preferences: &[Locale]) -> Option<Locale> {
+    pub fn from_accept_language(header: &str) -> Vec<Locale> {
+        let mut weighted: Vec<(Locale, f32)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';').map(str::trim);
+                let tag = parts.next()?;
+                let mut quality = 1.0;
+                for param in parts {
+                    if let Some(value) = param.strip_prefix("q=") {
+                        quality = value.parse::<f32>().ok()?;
+                    }
+                }
+                if !(quality > 0.0 && quality <= 1.0) {
+                    return None;
+                }
+                // Unsupported countries still give a usable language.
+                let locale = Locale::from_str(tag).or_else(|| {
+                    let language = tag.split(['-', '_']).next()?;
+                    Some(Locale(Language::from_str(language).ok()?, None))
+                })?;
+                Some((locale, quality))
+            })
+            .collect();
 
-        #[derive(PartialEq, Eq, PartialOrd, Ord)]
-        enum MatchRating {
-            MatchesNothing,
-            MatchesLanguage,
-            MatchesLanguageAndDefinedCountryIsNone,
-            MatchesLanguageAndCountry,
-        }
+        weighted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
 
-        impl MatchRating {
-            fn rate_match(Locale(language, country): Locale, Locale(user_language, user_country): Locale) -> MatchRating {
-                if language == user_language && country == user_country {
-                    MatchRating::MatchesLanguageAndCountry
-                } else if language == user_language && country.is_none() {
-                    MatchRating::MatchesLanguageAndDefinedCountryIsNone
-                } else if language == user_language {
-                    MatchRating::MatchesLanguage
-                } else {
-                    MatchRating::MatchesNothing
-                }
+        let mut locales: Vec<Locale> = Vec::with_capacity(weighted.len());
+        for (locale, _) in weighted {
+            if !locales.contains(&locale) {
+                locales.push(locale);
             }
         }
+        locales
+    }
 
-        let mut best_match = None;
-        let mut best_rating = MatchRating::MatchesNothing;
+    // The locale chosen by the user, from the `Cookie` header of a request, so
+    // that pages rendered on the server use it too.
+    pub fn from_cookie_header(header: &str) -> Option<Locale> {
+        header.split(';').find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            if name != LOCALE_COOKIE {
+                return None;
+            }
+            Locale::from_str(value)
+        })
+    }
 
-        let supported_locales = get_supported_locales();
+    pub fn cookie(&self) -> String {
+        format!("{LOCALE_COOKIE}={self}; Path=/; Max-Age=31536000; SameSite=Lax")
+    }
+
+    pub fn best_match(user_preferences: &[Locale]) -> Option<Locale> {
+        Self::best_match_in(user_preferences, get_supported_locales().as_slice())
+    }
+
+    pub fn best_match_in(
+        user_preferences: &[Locale],
+        supported_locales: &[Locale],
+    ) -> Option<Locale> {
+        let mut best_match = None;
+        let mut best_rating = MatchRating::Nothing;
 
         for &user_locale in user_preferences {
-            for &locale in &supported_locales {
+            for &locale in supported_locales {
                 let rating = MatchRating::rate_match(locale, user_locale);
                 if rating > best_rating {
                     best_match = Some(locale);
                     best_rating = rating;
                 }
             }
-            if best_rating > MatchRating::MatchesNothing {
+            if best_rating > MatchRating::Nothing {
                 break;
             }
         }
 
         best_match
     }
-    */
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Locale(language, country) = self;
+        write!(f, "{}", language.to_string().to_lowercase())?;
+        if let Some(country) = country {
+            write!(f, "-{}", country.to_string().to_uppercase())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchRating {
+    Nothing,
+    Language,
+    LanguageWithoutCountry,
+    LanguageAndCountry,
+}
+
+impl MatchRating {
+    fn rate_match(
+        Locale(language, country): Locale,
+        Locale(user_language, user_country): Locale,
+    ) -> MatchRating {
+        if language == user_language && country == user_country {
+            MatchRating::LanguageAndCountry
+        } else if language == user_language && country.is_none() {
+            MatchRating::LanguageWithoutCountry
+        } else if language == user_language {
+            MatchRating::Language
+        } else {
+            MatchRating::Nothing
+        }
+    }
+}
+
+// Prefers the locale over the current ones, which are still used as fallbacks.
+pub fn with_locale<T>(locale: Locale, f: impl FnOnce() -> T) -> T {
+    let mut context = LocaleContext::current();
+    context.locales.insert(0, locale);
+    context.scope(f)
+}
+
+pub fn current_locale() -> Locale {
+    LocaleContext::current().locale()
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Number {
+    value: i64,
+    decimals: u8,
+}
+
+impl Number {
+    pub fn new(value: i64) -> Self {
+        Number { value, decimals: 0 }
+    }
+
+    pub fn decimal(value: i64, decimals: u8) -> Self {
+        Number { value, decimals }
+    }
+
+    fn separators(Locale(language, country): Locale) -> (&'static str, char) {
+        match (language, country) {
+            (Language::Fr, _) => ("\u{202f}", ','),
+            (_, Some(Country::Ch)) => ("’", '.'),
+            (Language::En, _) => (",", '.'),
+            _ => (".", ','),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Number::new(value)
+    }
+}
+
+impl From<u64> for Number {
+    fn from(value: u64) -> Self {
+        Number::new(value.try_into().unwrap_or(i64::MAX))
+    }
+}
+
+impl From<u32> for Number {
+    fn from(value: u32) -> Self {
+        Number::new(value.into())
+    }
+}
+
+impl From<i32> for Number {
+    fn from(value: i32) -> Self {
+        Number::new(value.into())
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (group, decimal) = Self::separators(current_locale());
+        let scale = 10u64.pow(self.decimals.into());
+        let integer = (self.value.unsigned_abs() / scale).to_string();
+        let fraction = self.value.unsigned_abs() % scale;
+
+        let mut formatted = String::new();
+        if self.value < 0 {
+            formatted.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i).is_multiple_of(3) {
+                formatted.push_str(group);
+            }
+            formatted.push(digit);
+        }
+        if self.decimals > 0 {
+            formatted.push(decimal);
+            formatted.push_str(&format!(
+                "{fraction:0width$}",
+                width = self.decimals as usize
+            ));
+        }
+        f.pad(&formatted)
+    }
 }
 
 pub trait Localizable: Sized {
     fn localize(self) -> Localized {
+        // Nested in a translation, e.g. `{item}`, the locale of that translation
+        // is in front through `with_locale`.
         self.localize_with(get_locales().as_slice())
     }
 
     fn localize_with(self, locale: &[Locale]) -> Localized;
 }
 
-impl<'a> Localizable for &'a str {
+impl Localizable for &str {
     fn localize_with(self, _locale: &[Locale]) -> Localized {
         Localized::from(self)
     }
@@ -176,12 +1320,70 @@ impl Display for Localized {
     }
 }
 
-#[cfg(not(feature = "seed"))]
+#[cfg(feature = "seed")]
+impl<Ms> seed::virtual_dom::UpdateEl<Ms> for Localized {
+    fn update_el(self, el: &mut seed::virtual_dom::El<Ms>) {
+        el.children
+            .push(seed::virtual_dom::Node::Text(seed::virtual_dom::Text::new(self.0)));
+    }
+}
+
+#[cfg(feature = "yew")]
+impl yew::html::ToHtml for Localized {
+    fn to_html(&self) -> yew::Html {
+        yew::Html::from(self.0.clone())
+    }
+
+    fn into_html(self) -> yew::Html {
+        yew::Html::from(self.0)
+    }
+}
+
+#[cfg(feature = "leptos")]
+impl leptos::IntoView for Localized {
+    fn into_view(self) -> leptos::View {
+        leptos::IntoView::into_view(self.0)
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fieldless {
+    ($name:ident :: $variant:ident) => {
+        Some($name::$variant)
+    };
+    ($name:ident :: $variant:ident ( $( $ty:ty ),* )) => {
+        None
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __guarded {
+    () => {
+        false
+    };
+    ($guard:expr) => {
+        true
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __translation {
+    ($locale:expr, $tr:literal) => {
+        $crate::with_locale(*$locale, || $crate::Localized::from(format!($tr)))
+    };
+    ($locale:expr, $tr:expr) => {
+        $crate::with_locale(*$locale, || $crate::Localized::from($tr))
+    };
+}
+
 #[macro_export]
 macro_rules! localize {
     (pub enum $name:ident { $(
         $variant:ident $( ( $( $var_name:ident: $var_ty:ty ),* $(,)? ) )? {
-            $( $pattern:pat => $tr:expr ),+ $(,)?
+            $( $pattern:pat $( if $guard:expr )? => $tr:expr ),+ $(,)?
         } $(,)?
     )* } ) => {
         #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -202,7 +1404,7 @@ macro_rules! localize {
                         Self:: $variant $( ( $( $var_name ),* ) )? => for locale in locales {
                             match locale {
                                 $(
-                                    $pattern => return $crate::Localized::from($tr)
+                                    $pattern $( if $guard )? => return $crate::__translation!(locale, $tr)
                                 ),*
                             }
                         }
@@ -213,57 +1415,70 @@ macro_rules! localize {
             }
         }
 
-        impl std::fmt::Display for $name {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(f, "{}", <Self as $crate::Localizable>::localize(self.clone()).to_string())
-            }
-        }
-    };
-}
-
-#[cfg(feature = "seed")]
-#[macro_export]
-macro_rules! localize {
-    (pub enum $name:ident { $(
-        $variant:ident $( ( $( $var_name:ident: $var_ty:ty ),* $(,)? ) )? {
-            $( $pattern:pat => $tr:expr ),+ $(,)?
-        } $(,)?
-    )* } ) => {
-        #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
-        pub enum $name {
-            $(
-                $variant $( ( $( $var_ty ),* ) )? ,
-            )*
-        }
-
-        impl $crate::Localizable for $name {
-            fn localize_with(self, locales: &[$crate::Locale]) -> $crate::Localized {
+        impl $name {
+            // Lists every variant that has no translation of its own for one of
+            // the given locales, and falls back to an arm matching any language.
+            // The fallback locale usually lives in that arm, so leave it out.
+            // Guards can depend on the fields, so arms with one don't count, and a
+            // locale with a guarded arm also needs an arm without one.
+            #[allow(unused_variables, unreachable_patterns)]
+            pub fn missing_translations(locales: &[$crate::Locale]) -> Vec<(&'static str, $crate::Locale)> {
                 use $crate::Locale;
                 use $crate::Language;
                 use $crate::Country;
 
-                match self {
+                let languages: Vec<Locale> = <Language as $crate::IntoEnumIterator>::iter()
+                    .map(|language| Locale(language, None))
+                    .collect();
+
+                let mut missing = Vec::new();
+                for locale in locales {
                     $(
-                        Self:: $variant $( ( $( $var_name ),* ) )? => for locale in locales {
-                            match locale {
-                                $(
-                                    $pattern => return $crate::Localized::from($tr)
-                                ),*
-                            }
+                        let translated = 'arms: {
+                            $(
+                                if matches!(locale, $pattern) && !$crate::__guarded!($( $guard )?) {
+                                    break 'arms !languages.iter().all(|locale| matches!(locale, $pattern));
+                                }
+                            )+
+                            false
+                        };
+                        if !translated {
+                            missing.push((stringify!($variant), *locale));
                         }
-                    ),*
+                    )*
                 }
+                missing
+            }
 
-                $crate::Localized::from(format!("{:?}", self))
+            // Every variant without fields, e.g. to list the options of a setting.
+            pub fn variants() -> impl Iterator<Item = Self> {
+                let variants: Vec<Option<Self>> = vec![
+                    $(
+                        $crate::__fieldless!($name :: $variant $( ( $( $var_ty ),* ) )?)
+                    ),*
+                ];
+                variants.into_iter().flatten()
+            }
+
+            pub fn localize_all(locale: $crate::Locale) -> Vec<(Self, $crate::Localized)> {
+                Self::variants()
+                    .map(|variant| (variant, $crate::Localizable::localize_with(variant, &[locale])))
+                    .collect()
             }
         }
 
-        impl<Ms> seed::virtual_dom::UpdateEl<Ms> for $name {
-            fn update_el(self, el: &mut seed::virtual_dom::El<Ms>) {
-                el.children.push(seed::virtual_dom::Node::Text(seed::virtual_dom::Text::new(<Self as $crate::Localizable>::localize(self.clone()).to_string())));
+        impl $crate::Translated for $name {
+            const NAME: &'static str = stringify!($name);
+
+            fn missing_translations(locales: &[$crate::Locale]) -> Vec<(&'static str, $crate::Locale)> {
+                $name::missing_translations(locales)
             }
         }
 
+        $crate::__seed!($name);
+        $crate::__yew!($name);
+        $crate::__leptos!($name);
+
         impl std::fmt::Display for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 write!(f, "{}", <Self as $crate::Localizable>::localize(self.clone()).to_string())
@@ -272,22 +1487,129 @@ macro_rules! localize {
     };
 }
 
+#[cfg(feature = "seed")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __seed {
+    ($name:ident) => {
+        impl<Ms> seed::virtual_dom::UpdateEl<Ms> for $name {
+            fn update_el(self, el: &mut seed::virtual_dom::El<Ms>) {
+                el.children
+                    .push(seed::virtual_dom::Node::Text(seed::virtual_dom::Text::new(
+                        <Self as $crate::Localizable>::localize(self.clone()).to_string(),
+                    )));
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "seed"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __seed {
+    ($name:ident) => {};
+}
+
+#[cfg(feature = "yew")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __yew {
+    ($name:ident) => {
+        impl yew::html::ToHtml for $name {
+            fn to_html(&self) -> yew::Html {
+                yew::html::ToHtml::into_html(<Self as $crate::Localizable>::localize(*self))
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "yew"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __yew {
+    ($name:ident) => {};
+}
+
+#[cfg(feature = "leptos")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __leptos {
+    ($name:ident) => {
+        impl leptos::IntoView for $name {
+            fn into_view(self) -> leptos::View {
+                leptos::IntoView::into_view(
+                    <Self as $crate::Localizable>::localize(self).to_string(),
+                )
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "leptos"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __leptos {
+    ($name:ident) => {};
+}
+
 #[cfg(feature = "web-sys")]
 pub fn web_sys_set_locales() {
-    let locales = web_sys::window()
-        .unwrap()
-        .navigator()
-        .languages()
-        .iter()
-        .map(|v| v.as_string().unwrap())
+    // An explicit choice of the user takes precedence over the browser.
+    let locales = web_sys_stored_locale()
+        .into_iter()
         .chain(
             web_sys::window()
                 .unwrap()
                 .navigator()
-                .language(),
+                .languages()
+                .iter()
+                .map(|v| v.as_string().unwrap())
+                .chain(
+                    web_sys::window()
+                        .unwrap()
+                        .navigator()
+                        .language(),
+                )
+                .flat_map(|s| Locale::from_str(&s)),
         )
-        .flat_map(|s| Locale::from_str(&s))
         .collect::<Vec<Locale>>();
 
     set_locales(locales.as_slice());
 }
+
+#[cfg(feature = "web-sys")]
+pub fn web_sys_stored_locale() -> Option<Locale> {
+    let stored = web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item(LOCALE_COOKIE)
+        .ok()??;
+    Locale::from_str(&stored)
+}
+
+// Remembers the user's choice across reloads, or forgets it with `None`, and
+// applies it right away.
+#[cfg(feature = "web-sys")]
+pub fn web_sys_store_locale(locale: Option<Locale>) {
+    use web_sys::wasm_bindgen::JsCast;
+
+    let window = web_sys::window().unwrap();
+    if let Ok(Some(storage)) = window.local_storage() {
+        match locale {
+            Some(locale) => storage.set_item(LOCALE_COOKIE, &locale.to_string()).ok(),
+            None => storage.remove_item(LOCALE_COOKIE).ok(),
+        };
+    }
+    if let Some(document) = window
+        .document()
+        .and_then(|document| document.dyn_into::<web_sys::HtmlDocument>().ok())
+    {
+        let cookie = match locale {
+            Some(locale) => locale.cookie(),
+            None => format!("{LOCALE_COOKIE}=; Path=/; Max-Age=0"),
+        };
+        document.set_cookie(&cookie).ok();
+    }
+
+    web_sys_set_locales();
+}
@@ -0,0 +1,30 @@
+use crate::{with_locale, Language, Locale, Localizable, Localized};
+
+// Joins the items the way a sentence of the locale would, e.g. "A, B and C"
+// or "A, B und C".
+pub fn join_localized<I>(locale: Locale, items: I) -> Localized
+where
+    I: IntoIterator,
+    I::Item: Localizable,
+{
+    let Locale(language, _) = locale;
+    let conjunction = match language {
+        Language::De => "und",
+        Language::Fr => "et",
+        Language::It => "e",
+        _ => "and",
+    };
+
+    let mut items: Vec<String> = with_locale(locale, || {
+        items
+            .into_iter()
+            .map(|item| item.localize_with(&[locale]).to_string())
+            .collect()
+    });
+    let joined = match items.pop() {
+        Some(last) if !items.is_empty() => format!("{} {conjunction} {last}", items.join(", ")),
+        Some(last) => last,
+        None => String::new(),
+    };
+    Localized::from(joined)
+}
@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+use crate::{current_locale, with_locale, Language, Locale, Localizable, Localized, Number};
+
+// A rank or round, e.g. "1st", "2." or "1er".
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ordinal(u64);
+
+impl Ordinal {
+    pub fn new(value: u64) -> Self {
+        Ordinal(value)
+    }
+
+    fn format(&self, Locale(language, _): Locale) -> String {
+        let n = Number::from(self.0);
+        match language {
+            Language::De => format!("{n}."),
+            Language::Fr if self.0 == 1 => format!("{n}er"),
+            Language::Fr => format!("{n}e"),
+            Language::It => format!("{n}º"),
+            _ => {
+                let suffix = match (self.0 % 10, self.0 % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                format!("{n}{suffix}")
+            }
+        }
+    }
+}
+
+impl From<u64> for Ordinal {
+    fn from(value: u64) -> Self {
+        Ordinal::new(value)
+    }
+}
+
+impl Localizable for Ordinal {
+    fn localize_with(self, locales: &[Locale]) -> Localized {
+        let locale = locales.first().copied().unwrap_or_else(current_locale);
+        Localized::from(with_locale(locale, || self.format(locale)))
+    }
+}
+
+impl Display for Ordinal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.format(current_locale()))
+    }
+}
+
+// A number spelled out in words up to twelve, e.g. "three" or "drei", and
+// written in digits above.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Spelled(u64);
+
+impl Spelled {
+    pub fn new(value: u64) -> Self {
+        Spelled(value)
+    }
+
+    fn format(&self, Locale(language, _): Locale) -> String {
+        let words: [&str; 13] = match language {
+            Language::De => [
+                "null", "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun",
+                "zehn", "elf", "zwölf",
+            ],
+            Language::Fr => [
+                "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+                "dix", "onze", "douze",
+            ],
+            Language::It => [
+                "zero", "uno", "due", "tre", "quattro", "cinque", "sei", "sette", "otto", "nove",
+                "dieci", "undici", "dodici",
+            ],
+            _ => [
+                "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+                "ten", "eleven", "twelve",
+            ],
+        };
+        match words.get(self.0 as usize) {
+            Some(word) => (*word).to_owned(),
+            None => Number::from(self.0).to_string(),
+        }
+    }
+}
+
+impl From<u64> for Spelled {
+    fn from(value: u64) -> Self {
+        Spelled::new(value)
+    }
+}
+
+impl Localizable for Spelled {
+    fn localize_with(self, locales: &[Locale]) -> Localized {
+        let locale = locales.first().copied().unwrap_or_else(current_locale);
+        Localized::from(with_locale(locale, || self.format(locale)))
+    }
+}
+
+impl Display for Spelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.format(current_locale()))
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime},
+};
+
+use crate::{current_locale, with_locale, Language, Locale, Localizable, Localized, Number};
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+}
+
+// A point in time relative to now, e.g. "in 5 minutes" or "vor 2 Stunden".
+// Positive seconds lie in the future, negative ones in the past.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RelativeTime {
+    seconds: i64,
+}
+
+impl RelativeTime {
+    pub fn from_seconds(seconds: i64) -> Self {
+        RelativeTime { seconds }
+    }
+
+    pub fn future(duration: Duration) -> Self {
+        Self::from_seconds(duration.as_secs().try_into().unwrap_or(i64::MAX))
+    }
+
+    pub fn past(duration: Duration) -> Self {
+        Self::from_seconds(-Self::future(duration).seconds)
+    }
+
+    pub fn between(now: SystemTime, time: SystemTime) -> Self {
+        match time.duration_since(now) {
+            Ok(duration) => Self::future(duration),
+            Err(err) => Self::past(err.duration()),
+        }
+    }
+
+    pub fn until(time: SystemTime) -> Self {
+        Self::between(SystemTime::now(), time)
+    }
+
+    fn unit(&self) -> (u64, Unit) {
+        let seconds = self.seconds.unsigned_abs();
+        match seconds {
+            0..=59 => (seconds, Unit::Second),
+            60..=3599 => (seconds / 60, Unit::Minute),
+            3600..=86399 => (seconds / 3600, Unit::Hour),
+            _ => (seconds / 86400, Unit::Day),
+        }
+    }
+
+    fn format(&self, Locale(language, _): Locale) -> String {
+        let (count, unit) = self.unit();
+        let one = count == 1;
+        let future = self.seconds > 0;
+        let n = Number::from(count);
+
+        match language {
+            Language::De => {
+                if count == 0 {
+                    return "jetzt".to_owned();
+                }
+                let unit = match (unit, one) {
+                    (Unit::Second, true) => "Sekunde",
+                    (Unit::Second, false) => "Sekunden",
+                    (Unit::Minute, true) => "Minute",
+                    (Unit::Minute, false) => "Minuten",
+                    (Unit::Hour, true) => "Stunde",
+                    (Unit::Hour, false) => "Stunden",
+                    (Unit::Day, true) => "Tag",
+                    (Unit::Day, false) => "Tagen",
+                };
+                if future {
+                    format!("in {n} {unit}")
+                } else {
+                    format!("vor {n} {unit}")
+                }
+            }
+            Language::Fr => {
+                if count == 0 {
+                    return "maintenant".to_owned();
+                }
+                let unit = match (unit, one) {
+                    (Unit::Second, true) => "seconde",
+                    (Unit::Second, false) => "secondes",
+                    (Unit::Minute, true) => "minute",
+                    (Unit::Minute, false) => "minutes",
+                    (Unit::Hour, true) => "heure",
+                    (Unit::Hour, false) => "heures",
+                    (Unit::Day, true) => "jour",
+                    (Unit::Day, false) => "jours",
+                };
+                if future {
+                    format!("dans {n} {unit}")
+                } else {
+                    format!("il y a {n} {unit}")
+                }
+            }
+            Language::It => {
+                if count == 0 {
+                    return "adesso".to_owned();
+                }
+                let unit = match (unit, one) {
+                    (Unit::Second, true) => "secondo",
+                    (Unit::Second, false) => "secondi",
+                    (Unit::Minute, true) => "minuto",
+                    (Unit::Minute, false) => "minuti",
+                    (Unit::Hour, true) => "ora",
+                    (Unit::Hour, false) => "ore",
+                    (Unit::Day, true) => "giorno",
+                    (Unit::Day, false) => "giorni",
+                };
+                if future {
+                    format!("tra {n} {unit}")
+                } else {
+                    format!("{n} {unit} fa")
+                }
+            }
+            _ => {
+                if count == 0 {
+                    return "now".to_owned();
+                }
+                let unit = match (unit, one) {
+                    (Unit::Second, true) => "second",
+                    (Unit::Second, false) => "seconds",
+                    (Unit::Minute, true) => "minute",
+                    (Unit::Minute, false) => "minutes",
+                    (Unit::Hour, true) => "hour",
+                    (Unit::Hour, false) => "hours",
+                    (Unit::Day, true) => "day",
+                    (Unit::Day, false) => "days",
+                };
+                if future {
+                    format!("in {n} {unit}")
+                } else {
+                    format!("{n} {unit} ago")
+                }
+            }
+        }
+    }
+}
+
+impl Localizable for RelativeTime {
+    fn localize_with(self, locales: &[Locale]) -> Localized {
+        let locale = locales.first().copied().unwrap_or_else(current_locale);
+        Localized::from(with_locale(locale, || self.format(locale)))
+    }
+}
+
+impl Display for RelativeTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.format(current_locale()))
+    }
+}
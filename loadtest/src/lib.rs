@@ -0,0 +1,383 @@
+use engine_shared::{Event, Req, Res, State, SyncData};
+use futures_util::{SinkExt, StreamExt};
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::VecDeque, fmt, sync::Arc, time::Duration};
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, handshake::client::Request, Message},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unknown argument {0}")]
+    UnknownArgument(String),
+    #[error("missing value for {0}")]
+    MissingValue(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub url: String,
+    pub clients: usize,
+    pub rate: f64,
+    pub duration: Duration,
+    pub ramp_up: Duration,
+    pub seed: u64,
+    // Offers `engine_shared::compression::PROTOCOL`, so the server compresses
+    // large frames like for clients with the feature.
+    #[cfg(feature = "compression")]
+    pub compression: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            url: String::from("ws://localhost:3000/ws?user={client}"),
+            clients: 10,
+            rate: 1.0,
+            duration: Duration::from_secs(60),
+            ramp_up: Duration::from_secs(5),
+            seed: 0,
+            #[cfg(feature = "compression")]
+            compression: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args() -> Result<Self, Error> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| Error::MissingValue(arg.clone()))?;
+            let invalid = || Error::InvalidValue(arg.clone(), value.clone());
+            match arg.as_str() {
+                "--url" => config.url = value.clone(),
+                "--clients" => config.clients = value.parse().map_err(|_| invalid())?,
+                "--rate" => config.rate = value.parse().map_err(|_| invalid())?,
+                "--duration" => {
+                    config.duration = Duration::from_secs(value.parse().map_err(|_| invalid())?)
+                }
+                "--ramp-up" => {
+                    config.ramp_up = Duration::from_secs(value.parse().map_err(|_| invalid())?)
+                }
+                "--seed" => config.seed = value.parse().map_err(|_| invalid())?,
+                #[cfg(feature = "compression")]
+                "--compression" => config.compression = value.parse().map_err(|_| invalid())?,
+                _ => return Err(Error::UnknownArgument(arg)),
+            }
+        }
+        Ok(config)
+    }
+
+    fn request_for(&self, client: usize) -> Option<Request> {
+        let request = self
+            .url
+            .replace("{client}", &client.to_string())
+            .into_client_request()
+            .ok()?;
+        #[cfg(feature = "compression")]
+        let request = {
+            let mut request = request;
+            if self.compression {
+                request.headers_mut().insert(
+                    "Sec-WebSocket-Protocol",
+                    tokio_tungstenite::tungstenite::http::HeaderValue::from_static(
+                        engine_shared::compression::PROTOCOL,
+                    ),
+                );
+            }
+            request
+        };
+        Some(request)
+    }
+}
+
+pub type EventGenerator<S> = Arc<
+    dyn Fn(&mut SmallRng, &S, &<S as State>::UserId) -> Option<<S as State>::ClientEvent>
+        + Send
+        + Sync,
+>;
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub clients: usize,
+    pub connected: usize,
+    pub connection_errors: usize,
+    pub disconnects: usize,
+    pub decode_errors: u64,
+    pub events_sent: u64,
+    pub events_acknowledged: u64,
+    pub syncs: u64,
+    pub checksum_errors: u64,
+    latencies: Vec<Duration>,
+}
+
+impl Report {
+    fn merge(&mut self, other: Report) {
+        self.clients += other.clients;
+        self.connected += other.connected;
+        self.connection_errors += other.connection_errors;
+        self.disconnects += other.disconnects;
+        self.decode_errors += other.decode_errors;
+        self.events_sent += other.events_sent;
+        self.events_acknowledged += other.events_acknowledged;
+        self.syncs += other.syncs;
+        self.checksum_errors += other.checksum_errors;
+        self.latencies.extend(other.latencies);
+    }
+
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    pub fn latency_percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut latencies = self.latencies.clone();
+        latencies.sort_unstable();
+        let last = latencies.len().checked_sub(1)?;
+        let index = (last as f64 * percentile.clamp(0.0, 1.0)).round() as usize;
+        Some(latencies[index])
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "clients: {} connected, {} failed to connect, {} disconnected early",
+            self.connected, self.connection_errors, self.disconnects
+        )?;
+        writeln!(
+            f,
+            "events: {} sent, {} acknowledged",
+            self.events_sent, self.events_acknowledged
+        )?;
+        let percentile = |p| match self.latency_percentile(p) {
+            Some(latency) => format!("{latency:?}"),
+            None => String::from("-"),
+        };
+        writeln!(
+            f,
+            "latency: p50 {}, p95 {}, p99 {}, max {}",
+            percentile(0.5),
+            percentile(0.95),
+            percentile(0.99),
+            percentile(1.0)
+        )?;
+        write!(
+            f,
+            "syncs: {} received, {} checksum errors, {} undecodable messages",
+            self.syncs, self.checksum_errors, self.decode_errors
+        )
+    }
+}
+
+// Games build their bot spawner binary by calling this from their `main` with
+// a generator for their client events, since the bots have to decode the
+// game's state.
+pub fn run_cli<S, F>(generate: F)
+where
+    S: State + Serialize + DeserializeOwned,
+    F: Fn(&mut SmallRng, &S, &S::UserId) -> Option<S::ClientEvent> + Send + Sync + 'static,
+{
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            eprintln!(
+                "usage: [--url ws://host/path?user={{client}}] [--clients N] [--rate EVENTS_PER_SECOND] [--duration SECONDS] [--ramp-up SECONDS] [--seed N]{}",
+                if cfg!(feature = "compression") {
+                    " [--compression true|false]"
+                } else {
+                    ""
+                }
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("couldn't start the tokio runtime");
+    let report = runtime.block_on(run::<S>(&config, Arc::new(generate)));
+    println!("{report}");
+}
+
+pub async fn run<S>(config: &Config, generate: EventGenerator<S>) -> Report
+where
+    S: State + Serialize + DeserializeOwned,
+{
+    let start = Instant::now();
+    let deadline = start + config.ramp_up + config.duration;
+    let ramp_up_step = config.ramp_up / config.clients.max(1) as u32;
+
+    let handles: Vec<_> = (0..config.clients)
+        .map(|client| {
+            let request = config.request_for(client);
+            let rate = config.rate;
+            let seed = config.seed.wrapping_add(client as u64);
+            let generate = generate.clone();
+            let connect_at = start + ramp_up_step * client as u32;
+            tokio::spawn(async move {
+                time::sleep_until(connect_at).await;
+                run_client::<S>(request, rate, seed, generate, deadline).await
+            })
+        })
+        .collect();
+
+    let mut report = Report::default();
+    for handle in handles {
+        match handle.await {
+            Ok(client_report) => report.merge(client_report),
+            Err(_) => {
+                report.clients += 1;
+                report.disconnects += 1;
+            }
+        }
+    }
+    report
+}
+
+async fn run_client<S>(
+    request: Option<Request>,
+    rate: f64,
+    seed: u64,
+    generate: EventGenerator<S>,
+    deadline: Instant,
+) -> Report
+where
+    S: State + Serialize + DeserializeOwned,
+{
+    let mut report = Report {
+        clients: 1,
+        ..Report::default()
+    };
+
+    let connection = match request {
+        Some(request) => connect_async(request).await.ok(),
+        None => None,
+    };
+    let mut socket = match connection {
+        Some((socket, _)) => socket,
+        None => {
+            report.connection_errors += 1;
+            return report;
+        }
+    };
+    report.connected += 1;
+
+    let encode = |req: &Req<S>| Message::Binary(rmp_serde::to_vec(req).unwrap());
+    let init = <S::ClientEvent as engine_shared::ClientEvent>::init();
+    if socket.send(encode(&Req::Sync)).await.is_err()
+        || socket.send(encode(&Req::Event(init))).await.is_err()
+    {
+        report.disconnects += 1;
+        return report;
+    }
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut user_id: Option<S::UserId> = None;
+    let mut state: Option<SyncData<S>> = None;
+    // Sent events are matched with their broadcast by their serialized form,
+    // since the server handles the events of a connection in order.
+    let mut pending: VecDeque<(Vec<u8>, Instant)> = VecDeque::new();
+
+    let mut interval = time::interval(Duration::from_secs_f64(1.0 / rate.max(f64::EPSILON)));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let end = time::sleep_until(deadline);
+    tokio::pin!(end);
+
+    loop {
+        tokio::select! {
+            _ = &mut end => break,
+            _ = interval.tick() => {
+                let Some(sync_data) = &state else {
+                    continue;
+                };
+                let Some(event) = generate(&mut rng, &sync_data.state.state, &sync_data.user_id) else {
+                    continue;
+                };
+                let serialized = rmp_serde::to_vec(&event).unwrap();
+                if socket.send(encode(&Req::Event(event))).await.is_err() {
+                    report.disconnects += 1;
+                    break;
+                }
+                pending.push_back((serialized, Instant::now()));
+                report.events_sent += 1;
+            }
+            message = socket.next() => match message {
+                Some(Ok(Message::Binary(bytes))) => match decode::<S>(&bytes) {
+                    Ok(Res::Sync(sync_data)) => {
+                        report.syncs += 1;
+                        user_id = Some(sync_data.user_id.clone());
+                        state = Some(sync_data);
+                    }
+                    Ok(Res::Event(event_data)) => {
+                        if let Event::ClientEvent(event, sender) = &event_data.event {
+                            if Some(sender) == user_id.as_ref() {
+                                let serialized = rmp_serde::to_vec(event).unwrap();
+                                // Events that were overtaken by a later one got lost.
+                                if let Some(position) = pending.iter().position(|(sent, _)| *sent == serialized) {
+                                    let (_, sent_at) = pending.drain(..=position).next_back().unwrap();
+                                    report.latencies.push(sent_at.elapsed());
+                                    report.events_acknowledged += 1;
+                                }
+                            }
+                        }
+                        let Some(sync_data) = &mut state else {
+                            continue;
+                        };
+                        if let Err(engine_shared::Error::InvalidChecksum { .. }) =
+                            sync_data.state.update_checked(event_data)
+                        {
+                            report.checksum_errors += 1;
+                            state = None;
+                            if socket.send(encode(&Req::Sync)).await.is_err() {
+                                report.disconnects += 1;
+                                break;
+                            }
+                        }
+                    }
+                    Ok(Res::UserUpdate(diff)) => {
+                        if let Some(sync_data) = &mut state {
+                            sync_data.state.users.apply_diff(diff);
+                        }
+                    }
+                    Ok(
+                        Res::Message(_)
+                        | Res::Catalog(..)
+                        | Res::Pong(..)
+                        | Res::Ack(_)
+                        | Res::Rejected(..)
+                        | Res::Extension(..),
+                    ) => {}
+                    Err(_) => report.decode_errors += 1,
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    report.disconnects += 1;
+                    break;
+                }
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    socket.close(None).await.ok();
+    report
+}
+
+fn decode<S: State + DeserializeOwned>(bytes: &[u8]) -> Result<Res<S>, rmp_serde::decode::Error> {
+    // Without the feature, compressed frames are invalid like any other.
+    #[cfg(feature = "compression")]
+    let bytes = &*engine_shared::compression::decompress(bytes)
+        .map_err(|err| rmp_serde::decode::Error::Uncategorized(err.to_string()))?;
+    rmp_serde::from_slice(bytes)
+}
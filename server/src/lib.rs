@@ -1,10 +1,17 @@
+pub mod testing;
+
 use engine_shared::{
-    utils::custom_map::CustomMap, Event, EventData, GameId, Req, Res, Seed, State, StateWrapper,
-    SyncData,
+    utils::custom_map::CustomMap, Event, EventData, GameId, HasLocale, Req, Res, Seed, State,
+    StateWrapper, SyncData,
 };
+use i18n::{Locale, LocaleContext, Localizable};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 use serde::Serialize;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::{
     sync::{broadcast, mpsc, Notify, RwLock},
     task::JoinHandle,
@@ -13,39 +20,116 @@ use tokio::{
 
 pub type GameVersion = i64;
 
-#[derive(Debug, Clone, Copy)]
-pub enum Error {
-    GameNotFound,
+// Ids of acknowledged events that are remembered per user to recognize
+// events that are sent again.
+const MAX_ACKED_EVENTS: usize = 256;
+// They are kept for a while after the user's last connection was dropped, so
+// events that are sent again after reconnecting are still recognized.
+const ACKED_EVENTS_TTL: Duration = Duration::from_secs(60);
+
+type AckedEvents<S> = Arc<std::sync::Mutex<HashMap<<S as State>::UserId, VecDeque<u64>>>>;
+
+type Locales<S> = Arc<std::sync::Mutex<HashMap<<S as State>::UserId, UserLocales>>>;
+
+// Kept while the user has open connections, which set them again when they
+// reconnect.
+#[derive(Debug, Default)]
+struct UserLocales {
+    connections: usize,
+    locales: Option<Vec<Locale>>,
 }
 
-impl std::error::Error for Error {}
+// Shared by the clones of a connection's requests, so the user's locales and
+// acked events are forgotten once the last of their connections is dropped.
+#[derive(Debug)]
+struct Connection<S: State> {
+    user_id: S::UserId,
+    locales: Locales<S>,
+    acked_events: AckedEvents<S>,
+}
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "game not found")
+impl<S: State> Connection<S> {
+    fn open(user_id: S::UserId, locales: Locales<S>, acked_events: AckedEvents<S>) -> Self {
+        locales
+            .lock()
+            .unwrap()
+            .entry(user_id.clone())
+            .or_default()
+            .connections += 1;
+        Connection {
+            user_id,
+            locales,
+            acked_events,
+        }
     }
 }
 
+impl<S: State> Drop for Connection<S> {
+    fn drop(&mut self) {
+        let mut locales = self.locales.lock().unwrap();
+        if let Some(user) = locales.get_mut(&self.user_id) {
+            user.connections -= 1;
+            if user.connections == 0 {
+                locales.remove(&self.user_id);
+                match tokio::runtime::Handle::try_current() {
+                    Ok(handle) => {
+                        let user_id = self.user_id.clone();
+                        let locales = self.locales.clone();
+                        let acked_events = self.acked_events.clone();
+                        handle.spawn(async move {
+                            time::sleep(ACKED_EVENTS_TTL).await;
+                            // The user may have reconnected meanwhile.
+                            if !locales.lock().unwrap().contains_key(&user_id) {
+                                acked_events.lock().unwrap().remove(&user_id);
+                            }
+                        });
+                    }
+                    // Without a runtime, the server is shutting down.
+                    Err(_) => {
+                        self.acked_events.lock().unwrap().remove(&self.user_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("game not found")]
+    GameNotFound,
+    #[error("failed to encode message: {0}")]
+    Encode(String),
+}
+
 struct ServerStateImpl<S: State> {
     state: RwLock<StateWrapper<S>>,
     res_sender: broadcast::Sender<Res<S>>,
-    req_sender: mpsc::UnboundedSender<Event<S>>,
+    req_sender: mpsc::UnboundedSender<Vec<Event<S>>>,
+    message_sender: broadcast::Sender<(S::UserId, String)>,
+    rejection_sender: broadcast::Sender<(S::UserId, S::ClientEvent, String)>,
+    // Sent to all users if there's no user id.
+    extension_sender: broadcast::Sender<(Option<S::UserId>, String, Vec<u8>)>,
 }
 
 pub struct ServerState<S: State, B: BackendStore<S>> {
     update_user_data: Arc<Notify>,
-    updated_user_data: Arc<Notify>,
     games: Arc<RwLock<HashMap<GameId, Arc<ServerStateImpl<S>>>>>,
     store: Arc<B>,
+    catalog_sender: broadcast::Sender<(Locale, String)>,
+    locales: Locales<S>,
+    acked_events: AckedEvents<S>,
 }
 
 impl<S: State, B: BackendStore<S>> Clone for ServerState<S, B> {
     fn clone(&self) -> Self {
         ServerState {
             update_user_data: self.update_user_data.clone(),
-            updated_user_data: self.updated_user_data.clone(),
             games: self.games.clone(),
             store: self.store.clone(),
+            catalog_sender: self.catalog_sender.clone(),
+            locales: self.locales.clone(),
+            acked_events: self.acked_events.clone(),
         }
     }
 }
@@ -53,8 +137,12 @@ impl<S: State, B: BackendStore<S>> Clone for ServerState<S, B> {
 #[derive(Debug, Clone)]
 pub struct ClientConnectionReq<S: State> {
     user_id: S::UserId,
-    req_sender: mpsc::UnboundedSender<Event<S>>,
+    req_sender: mpsc::UnboundedSender<Vec<Event<S>>>,
     sync_state: Arc<Notify>,
+    pong_sender: mpsc::UnboundedSender<u32>,
+    ack_sender: mpsc::UnboundedSender<u64>,
+    connection: Arc<Connection<S>>,
+    acked_events: AckedEvents<S>,
 }
 
 impl<S: State> ClientConnectionReq<S> {
@@ -62,10 +150,51 @@ impl<S: State> ClientConnectionReq<S> {
         match req {
             Req::Event(event) => {
                 self.req_sender
-                    .send(Event::ClientEvent(event, self.user_id.clone()))
+                    .send(vec![Event::ClientEvent(event, self.user_id.clone())])
                     .ok();
             }
+            Req::Events(events) => {
+                if !events.is_empty() {
+                    let events = events
+                        .into_iter()
+                        .map(|event| Event::ClientEvent(event, self.user_id.clone()))
+                        .collect();
+                    self.req_sender.send(events).ok();
+                }
+            }
+            Req::AckedEvent(id, event) => {
+                let duplicate = {
+                    let mut acked_events = self.acked_events.lock().unwrap();
+                    let acked = acked_events.entry(self.user_id.clone()).or_default();
+                    let duplicate = acked.contains(&id);
+                    if !duplicate {
+                        if acked.len() == MAX_ACKED_EVENTS {
+                            acked.pop_front();
+                        }
+                        acked.push_back(id);
+                    }
+                    duplicate
+                };
+                if !duplicate {
+                    self.req_sender
+                        .send(vec![Event::ClientEvent(event, self.user_id.clone())])
+                        .ok();
+                }
+                self.ack_sender.send(id).ok();
+            }
             Req::Sync => self.sync_state.notify_one(),
+            Req::Ping(id) => {
+                self.pong_sender.send(id).ok();
+            }
+            Req::SetLocales(locales) => {
+                self.connection
+                    .locales
+                    .lock()
+                    .unwrap()
+                    .entry(self.user_id.clone())
+                    .or_default()
+                    .locales = Some(locales);
+            }
         }
     }
 }
@@ -87,8 +216,20 @@ pub struct ClientConnectionRes<S: State, B: BackendStore<S>> {
     game_id: GameId,
     state: ServerState<S, B>,
     sync_state: Arc<Notify>,
-    updated_user_data: Arc<Notify>,
+    pong_receiver: mpsc::UnboundedReceiver<u32>,
+    ack_receiver: mpsc::UnboundedReceiver<u64>,
     res_receiver: broadcast::Receiver<Res<S>>,
+    message_receiver: broadcast::Receiver<(S::UserId, String)>,
+    rejection_receiver: broadcast::Receiver<(S::UserId, S::ClientEvent, String)>,
+    extension_receiver: broadcast::Receiver<(Option<S::UserId>, String, Vec<u8>)>,
+    catalog_receiver: broadcast::Receiver<(Locale, String)>,
+}
+
+// Called while holding the state. Changes are sent while holding it for
+// writing, so the ones that are still queued are already part of the sync and
+// would fail to apply after it.
+fn skip_synced<S: State>(res_receiver: &mut broadcast::Receiver<Res<S>>) {
+    while let Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) = res_receiver.try_recv() {}
 }
 
 impl<S: State, B: BackendStore<S>> ClientConnectionRes<S, B> {
@@ -96,31 +237,75 @@ impl<S: State, B: BackendStore<S>> ClientConnectionRes<S, B> {
         let games = self.state.games.read().await;
         let state = &games.get(&self.game_id).ok_or(Error::GameNotFound)?.state;
 
-        tokio::select! {
-            _ = self.sync_state.notified() => {
-                let state_wrapper = state.read().await;
-                Ok(Some(Res::Sync(SyncData {
-                    user_id: self.user_id.clone(),
-                    state: state_wrapper.clone(),
-                })))
-            }
-            _ = self.updated_user_data.notified() => {
-                let state_wrapper = state.read().await;
-                Ok(Some(Res::UserUpdate(state_wrapper.users.clone())))
-            }
-            res = self.res_receiver.recv() => {
-                match res {
-                    Ok(res) => Ok(Some(res)),
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // If receiver lagged, retransmit the whole state.
-                        let state_wrapper = state.read().await;
-                        Ok(Some(Res::Sync(SyncData {
-                            user_id: self.user_id.clone(),
-                            state: state_wrapper.clone(),
-                        })))
+        loop {
+            tokio::select! {
+                _ = self.sync_state.notified() => {
+                    let state_wrapper = state.read().await;
+                    skip_synced(&mut self.res_receiver);
+                    return Ok(Some(Res::Sync(SyncData::new(
+                        self.user_id.clone(),
+                        state_wrapper.clone(),
+                    ))));
+                }
+                Some(id) = self.pong_receiver.recv() => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                    let now = now.unwrap_or_default().as_millis() as u64;
+                    return Ok(Some(Res::Pong(id, now)));
+                }
+                Some(id) = self.ack_receiver.recv() => {
+                    return Ok(Some(Res::Ack(id)));
+                }
+                res = self.res_receiver.recv() => {
+                    return match res {
+                        Ok(res) => Ok(Some(res)),
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            // If receiver lagged, retransmit the whole state.
+                            let state_wrapper = state.read().await;
+                            skip_synced(&mut self.res_receiver);
+                            Ok(Some(Res::Sync(SyncData::new(
+                                self.user_id.clone(),
+                                state_wrapper.clone(),
+                            ))))
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            Ok(None)
+                        }
+                    };
+                }
+                message = self.message_receiver.recv() => {
+                    // Messages aren't part of the state, so lagged ones are dropped.
+                    match message {
+                        Ok((user_id, message)) if user_id == self.user_id => {
+                            return Ok(Some(Res::Message(message)));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => return Ok(None),
+                    }
+                }
+                rejection = self.rejection_receiver.recv() => {
+                    match rejection {
+                        Ok((user_id, event, reason)) if user_id == self.user_id => {
+                            return Ok(Some(Res::Rejected(event, reason)));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => return Ok(None),
                     }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        Ok(None)
+                }
+                extension = self.extension_receiver.recv() => {
+                    // Like messages, lagged extensions are dropped.
+                    match extension {
+                        Ok((user_id, kind, payload))
+                            if user_id.as_ref().is_none_or(|user_id| *user_id == self.user_id) =>
+                        {
+                            return Ok(Some(Res::Extension(kind, payload)));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => return Ok(None),
+                    }
+                }
+                catalog = self.catalog_receiver.recv() => {
+                    if let Ok((locale, source)) = catalog {
+                        return Ok(Some(Res::Catalog(locale, source)));
                     }
                 }
             }
@@ -143,8 +328,10 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
         ServerState {
             games: Arc::new(RwLock::new(HashMap::new())),
             update_user_data: Arc::new(Notify::new()),
-            updated_user_data: Arc::new(Notify::new()),
             store: Arc::new(store),
+            catalog_sender: broadcast::channel(16).0,
+            locales: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            acked_events: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
 
@@ -176,8 +363,11 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
         RwLock<StateWrapper<S>>: Sync,
         B::Error: Send,
     {
-        let (req_sender, mut req_receiver) = mpsc::unbounded_channel::<Event<S>>();
+        let (req_sender, mut req_receiver) = mpsc::unbounded_channel::<Vec<Event<S>>>();
         let (res_sender, _res_receiver) = broadcast::channel::<Res<S>>(128);
+        let (message_sender, _message_receiver) = broadcast::channel(128);
+        let (rejection_sender, _rejection_receiver) = broadcast::channel(128);
+        let (extension_sender, _extension_receiver) = broadcast::channel(128);
         let game_finished = Arc::new(Notify::new());
 
         let req_sender_clone = req_sender.clone();
@@ -193,6 +383,9 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
             state,
             res_sender,
             req_sender,
+            message_sender,
+            rejection_sender,
+            extension_sender,
         });
 
         let join_handle_tick = tokio::spawn(async move {
@@ -202,9 +395,9 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
                 interval.tick().await;
 
                 req_sender_clone
-                    .send(Event::ServerEvent(
+                    .send(vec![Event::ServerEvent(
                         <S::ServerEvent as engine_shared::ServerEvent<S>>::tick(),
-                    ))
+                    )])
                     .ok();
             }
         });
@@ -212,17 +405,23 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
         let game_state_clone = game_state.clone();
         let store_clone = self.store.clone();
         let update_user_data = self.update_user_data.clone();
-        let updated_user_data = self.updated_user_data.clone();
         let join_handle_update_user_data: JoinHandle<Result<(), B::Error>> =
             tokio::spawn(async move {
-                let update_user_data_clone = update_user_data.clone();
-                let updated_user_data_clone = updated_user_data.clone();
-
                 loop {
-                    update_user_data_clone.notified().await;
-                    game_state_clone.state.write().await.users =
-                        store_clone.load_user_data().await?;
-                    updated_user_data_clone.notify_waiters();
+                    update_user_data.notified().await;
+                    let users = store_clone.load_user_data().await?;
+
+                    // Only the changed users are sent, in order with the
+                    // events. The clients apply the changes the same way, so
+                    // the order of the users and with it the checksum match.
+                    let mut state_wrapper = game_state_clone.state.write().await;
+                    let diff = state_wrapper.users.diff_by(&users, |old, new| {
+                        rmp_serde::to_vec(old).ok() == rmp_serde::to_vec(new).ok()
+                    });
+                    if !diff.is_empty() {
+                        state_wrapper.users.apply_diff(diff.clone());
+                        game_state_clone.res_sender.send(Res::UserUpdate(diff)).ok();
+                    }
                 }
             });
 
@@ -231,17 +430,36 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
             let ServerStateImpl {
                 state: game,
                 res_sender,
+                rejection_sender,
                 ..
             } = &*game_state_clone;
 
             let mut rng = SmallRng::from_entropy();
 
-            while let Some(event) = req_receiver.recv().await {
-                {
+            // The events of a batch are applied while holding the lock, so
+            // no other events get in between.
+            while let Some(events) = req_receiver.recv().await {
+                let mut state_wrapper = game.write().await;
+
+                // A batch is applied to a copy first, so that it's rejected as
+                // a whole if one of its events is, like its sender predicted.
+                let mut copy = (events.len() > 1).then(|| state_wrapper.clone());
+                let working = copy.as_mut().unwrap_or(&mut state_wrapper);
+
+                let mut applied = Vec::new();
+                let mut failed = None;
+                for (index, event) in events.iter().cloned().enumerate() {
                     tracing::debug!("handling event: {event:?}");
 
-                    let mut state_wrapper = game.write().await;
-                    let state_checksum = state_wrapper.checksum();
+                    if let Event::ClientEvent(event, user_id) = &event {
+                        if let Err(reason) = working.state.validate(event, user_id) {
+                            tracing::debug!("rejected event: {reason}");
+                            failed = Some((index, reason));
+                            break;
+                        }
+                    }
+
+                    let state_checksum = working.checksum();
                     let seed: Seed = rng.gen();
 
                     let event = EventData {
@@ -250,16 +468,50 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
                         state_checksum,
                     };
 
-                    let res = state_wrapper.update_checked(event.clone());
-                    tracing::debug!("updated state: {state_wrapper:?}");
+                    let res = working.update_checked(event.clone());
+                    tracing::debug!("updated state: {working:?}");
 
                     match res {
                         Ok(()) => {}
-                        Err(engine_shared::Error::WorldClosed) => {}
-                        Err(_) => panic!(),
+                        Err(engine_shared::Error::WorldClosed { .. }) => {}
+                        // The event was applied anyway, and the clients apply
+                        // and report it the same way, so they stay in sync.
+                        Err(err @ engine_shared::Error::InvariantViolated { .. }) => {
+                            tracing::error!("{err}");
+                        }
+                        // Nothing was applied, so the event is rejected.
+                        Err(err) => {
+                            tracing::error!("failed to apply event: {err}");
+                            failed = Some((index, err.to_string()));
+                            break;
+                        }
+                    }
+
+                    applied.push(event);
+                }
+
+                // Rejected events aren't applied, so only the sender learns
+                // about them. The other events of a rejected batch are
+                // rejected along with it.
+                if let Some((failed_index, reason)) = failed {
+                    for (index, event) in events.into_iter().enumerate() {
+                        if let Event::ClientEvent(event, user_id) = event {
+                            let reason = if index == failed_index {
+                                reason.clone()
+                            } else {
+                                format!("rejected with its batch: {reason}")
+                            };
+                            rejection_sender.send((user_id, event, reason)).ok();
+                        }
                     }
+                    continue;
+                }
 
-                    res_sender.send(Res::Event(event.clone())).ok();
+                if let Some(copy) = copy {
+                    *state_wrapper = copy;
+                }
+                for event in applied {
+                    res_sender.send(Res::Event(event)).ok();
                 }
             }
         });
@@ -316,6 +568,8 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
         game_id: GameId,
     ) -> Result<(ClientConnectionReq<S>, ClientConnectionRes<S, B>), Error> {
         let sync_state = Arc::new(Notify::new());
+        let (pong_sender, pong_receiver) = mpsc::unbounded_channel();
+        let (ack_sender, ack_receiver) = mpsc::unbounded_channel();
         let games = self.games.read().await;
         let game = games.get(&game_id).ok_or(Error::GameNotFound)?;
         Ok((
@@ -323,18 +577,196 @@ impl<S: State, B: BackendStore<S>> ServerState<S, B> {
                 user_id: user_id.clone(),
                 req_sender: game.req_sender.clone(),
                 sync_state: sync_state.clone(),
+                pong_sender,
+                ack_sender,
+                connection: Arc::new(Connection::open(
+                    user_id.clone(),
+                    self.locales.clone(),
+                    self.acked_events.clone(),
+                )),
+                acked_events: self.acked_events.clone(),
             },
             ClientConnectionRes {
                 user_id,
                 state: self.clone(),
                 res_receiver: game.res_sender.subscribe(),
+                message_receiver: game.message_sender.subscribe(),
+                rejection_receiver: game.rejection_sender.subscribe(),
+                extension_receiver: game.extension_sender.subscribe(),
+                catalog_receiver: self.catalog_sender.subscribe(),
                 sync_state,
-                updated_user_data: self.updated_user_data.clone(),
+                pong_receiver,
+                ack_receiver,
                 game_id,
             },
         ))
     }
 
+    pub async fn notify_user<M>(
+        &self,
+        game_id: GameId,
+        user_id: S::UserId,
+        message: M,
+    ) -> Result<(), Error>
+    where
+        M: Localizable,
+        S::UserData: HasLocale,
+    {
+        let games = self.games.read().await;
+        let game = games.get(&game_id).ok_or(Error::GameNotFound)?;
+        let context = self.locale_context(&user_id, game.state.read().await.users.get(&user_id));
+        game.message_sender
+            .send((user_id, context.localize(message).to_string()))
+            .ok();
+        Ok(())
+    }
+
+    // Only users with user data receive the message, rendered in their own
+    // locales.
+    pub async fn notify_all<M>(&self, game_id: GameId, message: M) -> Result<(), Error>
+    where
+        M: Localizable + Clone,
+        S::UserData: HasLocale,
+    {
+        let games = self.games.read().await;
+        let game = games.get(&game_id).ok_or(Error::GameNotFound)?;
+        for (user_id, user_data) in game.state.read().await.users.iter() {
+            let message = self
+                .locale_context(user_id, Some(user_data))
+                .localize(message.clone());
+            game.message_sender
+                .send((user_id.clone(), message.to_string()))
+                .ok();
+        }
+        Ok(())
+    }
+
+    // Sends an application-specific message to the user, handled by the
+    // client's handler for the kind.
+    pub async fn send_extension<T: Serialize>(
+        &self,
+        game_id: GameId,
+        user_id: S::UserId,
+        kind: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        self.extension(game_id, Some(user_id), kind, payload).await
+    }
+
+    pub async fn broadcast_extension<T: Serialize>(
+        &self,
+        game_id: GameId,
+        kind: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        self.extension(game_id, None, kind, payload).await
+    }
+
+    async fn extension<T: Serialize>(
+        &self,
+        game_id: GameId,
+        user_id: Option<S::UserId>,
+        kind: &str,
+        payload: &T,
+    ) -> Result<(), Error> {
+        let games = self.games.read().await;
+        let game = games.get(&game_id).ok_or(Error::GameNotFound)?;
+        let payload = rmp_serde::to_vec(payload).map_err(|err| Error::Encode(err.to_string()))?;
+        game.extension_sender
+            .send((user_id, kind.to_owned(), payload))
+            .ok();
+        Ok(())
+    }
+
+    // Reloads every `<locale>.ftl` catalog in the directory whenever it
+    // changes and sends it to all clients, so that translators see their
+    // changes in the running game.
+    #[cfg(feature = "hot-reload")]
+    pub fn watch_catalogs(
+        &self,
+        dir: impl Into<std::path::PathBuf>,
+        period: Duration,
+    ) -> JoinHandle<()> {
+        let dir = dir.into();
+        let catalog_sender = self.catalog_sender.clone();
+
+        tokio::spawn(async move {
+            let mut modified = HashMap::new();
+            let mut interval = time::interval(period);
+
+            loop {
+                interval.tick().await;
+
+                let mut entries = match tokio::fs::read_dir(&dir).await {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        tracing::warn!("failed to read catalogs in {}: {}", dir.display(), err);
+                        continue;
+                    }
+                };
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let path = entry.path();
+                    if path.extension().and_then(|extension| extension.to_str()) != Some("ftl") {
+                        continue;
+                    }
+                    let Some(locale) = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(Locale::from_str)
+                    else {
+                        continue;
+                    };
+                    let Ok(time) = entry
+                        .metadata()
+                        .await
+                        .and_then(|metadata| metadata.modified())
+                    else {
+                        continue;
+                    };
+                    if modified.insert(path.clone(), time) == Some(time) {
+                        continue;
+                    }
+
+                    let result = match tokio::fs::read_to_string(&path).await {
+                        Ok(source) => i18n::load_catalog(locale, &source)
+                            .map(|()| source)
+                            .map_err(|err| err.to_string()),
+                        Err(err) => Err(err.to_string()),
+                    };
+                    match result {
+                        Ok(source) => {
+                            tracing::info!("reloaded catalog {}", path.display());
+                            catalog_sender.send((locale, source)).ok();
+                        }
+                        Err(err) => {
+                            tracing::warn!("failed to reload catalog {}: {}", path.display(), err);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    // Locales chosen by the user in-game take precedence over the user data.
+    fn locale_context(&self, user_id: &S::UserId, user_data: Option<&S::UserData>) -> LocaleContext
+    where
+        S::UserData: HasLocale,
+    {
+        if let Some(locales) = self
+            .locales
+            .lock()
+            .unwrap()
+            .get(user_id)
+            .and_then(|user| user.locales.as_ref())
+        {
+            return LocaleContext::new(locales);
+        }
+        match user_data {
+            Some(user_data) => LocaleContext::new(user_data.locales()),
+            None => LocaleContext::global(),
+        }
+    }
+
     pub async fn new_server_connection(&self) -> ServerConnectionReq<S> {
         ServerConnectionReq {
             update_user_data: self.update_user_data.clone(),
@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use engine_shared::{Req, Res, State};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use tokio::time::{self, Instant};
+
+use crate::{BackendStore, ClientConnectionReq, ClientConnectionRes, Error};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Faults {
+    pub latency: Duration,
+    pub jitter: Duration,
+    pub drop_rate: f64,
+    pub duplicate_rate: f64,
+    pub reorder_rate: f64,
+}
+
+impl Faults {
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+// Messages are delayed in tokio time, so tests running with a paused clock
+// see the same faults and delivery order for a given seed.
+pub struct FaultyLink<T> {
+    faults: Faults,
+    rng: SmallRng,
+    queue: BTreeMap<(Instant, u64), T>,
+    sequence: u64,
+}
+
+impl<T: Clone> FaultyLink<T> {
+    pub fn new(faults: Faults, seed: u64) -> Self {
+        FaultyLink {
+            faults,
+            rng: SmallRng::seed_from_u64(seed),
+            queue: BTreeMap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub fn set_faults(&mut self, faults: Faults) {
+        self.faults = faults;
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn send(&mut self, message: T) {
+        if self.rng.gen_bool(self.faults.drop_rate.clamp(0.0, 1.0)) {
+            return;
+        }
+        if self
+            .rng
+            .gen_bool(self.faults.duplicate_rate.clamp(0.0, 1.0))
+        {
+            self.schedule(message.clone());
+        }
+        self.schedule(message);
+    }
+
+    fn schedule(&mut self, message: T) {
+        let mut delay = self.faults.latency;
+        if !self.faults.jitter.is_zero() {
+            delay += self.rng.gen_range(Duration::ZERO..=self.faults.jitter);
+        }
+        // Holding a message back for another round trip lets the following
+        // messages overtake it.
+        if self.rng.gen_bool(self.faults.reorder_rate.clamp(0.0, 1.0)) {
+            delay += self.faults.latency + self.faults.jitter;
+        }
+
+        self.sequence += 1;
+        self.queue
+            .insert((Instant::now() + delay, self.sequence), message);
+    }
+
+    pub fn next_delivery(&self) -> Option<Instant> {
+        self.queue.keys().next().map(|(instant, _)| *instant)
+    }
+
+    pub fn try_recv(&mut self) -> Option<T> {
+        let entry = self.queue.first_entry()?;
+        if entry.key().0 > Instant::now() {
+            return None;
+        }
+        Some(entry.remove())
+    }
+
+    pub async fn recv(&mut self) -> Option<T> {
+        time::sleep_until(self.next_delivery()?).await;
+        self.try_recv()
+    }
+}
+
+pub struct FaultyConnection<S: State, B: BackendStore<S>> {
+    req: ClientConnectionReq<S>,
+    res: ClientConnectionRes<S, B>,
+    upstream: FaultyLink<Req<S>>,
+    downstream: FaultyLink<Res<S>>,
+    closed: bool,
+}
+
+impl<S: State, B: BackendStore<S>> FaultyConnection<S, B> {
+    pub fn new(
+        req: ClientConnectionReq<S>,
+        res: ClientConnectionRes<S, B>,
+        faults: Faults,
+        seed: u64,
+    ) -> Self {
+        FaultyConnection {
+            req,
+            res,
+            upstream: FaultyLink::new(faults, seed),
+            downstream: FaultyLink::new(faults, seed.wrapping_add(1)),
+            closed: false,
+        }
+    }
+
+    pub fn set_faults(&mut self, faults: Faults) {
+        self.upstream.set_faults(faults);
+        self.downstream.set_faults(faults);
+    }
+
+    pub fn request(&mut self, req: Req<S>) {
+        self.upstream.send(req);
+        self.flush_requests();
+    }
+
+    fn flush_requests(&mut self) {
+        while let Some(req) = self.upstream.try_recv() {
+            self.req.request(req);
+        }
+    }
+
+    pub async fn poll(&mut self) -> Result<Option<Res<S>>, Error> {
+        loop {
+            self.flush_requests();
+            if let Some(res) = self.downstream.try_recv() {
+                return Ok(Some(res));
+            }
+
+            let next_delivery = [
+                self.upstream.next_delivery(),
+                self.downstream.next_delivery(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            let wait = async {
+                match next_delivery {
+                    Some(instant) => time::sleep_until(instant).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            if self.closed {
+                if next_delivery.is_none() {
+                    return Ok(None);
+                }
+                wait.await;
+                continue;
+            }
+
+            tokio::select! {
+                res = self.res.poll() => match res? {
+                    Some(res) => self.downstream.send(res),
+                    None => self.closed = true,
+                },
+                _ = wait => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use engine_shared::{
+    bench::bench_state, utils::custom_map::CustomMap, ClientEvent, Event, ServerEvent, State,
+    StateWrapper, UserData, UserId,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct World {
+    tiles: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Tile {
+    owner: Option<User>,
+    resources: u64,
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum WorldEvent {
+    Tick,
+    Init,
+    Claim(usize),
+}
+
+impl ServerEvent<World> for WorldEvent {
+    fn tick() -> Self {
+        WorldEvent::Tick
+    }
+}
+
+impl ClientEvent for WorldEvent {
+    fn init() -> Self {
+        WorldEvent::Init
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+struct User(u64);
+
+impl UserId for User {}
+impl UserData for User {}
+
+impl State for World {
+    type ServerEvent = WorldEvent;
+    type ClientEvent = WorldEvent;
+    type UserId = User;
+    type UserData = User;
+
+    const DURATION_PER_TICK: Duration = Duration::from_secs(1);
+
+    fn update(
+        &mut self,
+        rng: &mut impl Rng,
+        event: Event<Self>,
+        _user_data: &CustomMap<Self::UserId, Self::UserData>,
+    ) {
+        match event {
+            Event::ServerEvent(_) => {
+                for tile in &mut self.tiles {
+                    tile.resources += rng.gen_range(0..3);
+                }
+            }
+            Event::ClientEvent(WorldEvent::Claim(index), user_id) => {
+                if let Some(tile) = self.tiles.get_mut(index) {
+                    tile.owner = Some(user_id);
+                }
+            }
+            Event::ClientEvent(_, _) => {}
+        }
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+fn world(size: usize) -> StateWrapper<World> {
+    StateWrapper {
+        state: World {
+            tiles: (0..size)
+                .map(|i| Tile {
+                    owner: None,
+                    resources: i as u64,
+                    name: format!("tile {i}"),
+                })
+                .collect(),
+        },
+        users: (0..16).map(|i| (User(i), User(i))).collect(),
+    }
+}
+
+fn benches(c: &mut Criterion) {
+    let events: Vec<Event<World>> = (0..64)
+        .map(|i| {
+            if i % 4 == 0 {
+                Event::ServerEvent(WorldEvent::Tick)
+            } else {
+                Event::ClientEvent(WorldEvent::Claim(i * 7), User(i as u64 % 16))
+            }
+        })
+        .collect();
+
+    for size in [100, 1_000, 10_000] {
+        bench_state(c, "state", size, &world(size), &events);
+    }
+}
+
+criterion_group!(state, benches);
+criterion_main!(state);
@@ -0,0 +1,146 @@
+use std::hash::Hash;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{
+    utils::custom_map::{CustomMap, CustomSet, MapDiff},
+    Event, EventData, Req, Res, State, StateWrapper, SyncData,
+};
+
+impl<'a, K, V> Arbitrary<'a> for CustomMap<K, V>
+where
+    K: Arbitrary<'a> + Eq + Hash,
+    V: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut map = CustomMap::new();
+        for entry in u.arbitrary_iter::<(K, V)>()? {
+            let (key, value) = entry?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<'a, K, V> Arbitrary<'a> for MapDiff<K, V>
+where
+    K: Arbitrary<'a> + Eq + Hash,
+    V: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut removed = CustomSet::new();
+        for key in u.arbitrary_iter::<K>()? {
+            removed.insert(key?);
+        }
+        Ok(MapDiff {
+            inserted: u.arbitrary()?,
+            updated: u.arbitrary()?,
+            removed,
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for Event<S>
+where
+    S: State,
+    S::ServerEvent: Arbitrary<'a>,
+    S::ClientEvent: Arbitrary<'a>,
+    S::UserId: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            Event::ServerEvent(u.arbitrary()?)
+        } else {
+            Event::ClientEvent(u.arbitrary()?, u.arbitrary()?)
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for EventData<S>
+where
+    S: State,
+    Event<S>: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(EventData {
+            event: u.arbitrary()?,
+            seed: u.arbitrary()?,
+            state_checksum: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for Req<S>
+where
+    S: State,
+    S::ClientEvent: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=5)? {
+            0 => Req::Event(u.arbitrary()?),
+            1 => Req::Sync,
+            2 => Req::Ping(u.arbitrary()?),
+            3 => Req::AckedEvent(u.arbitrary()?, u.arbitrary()?),
+            4 => Req::Events(u.arbitrary()?),
+            _ => Req::SetLocales(vec![arbitrary_locale(u)?]),
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for StateWrapper<S>
+where
+    S: State + Arbitrary<'a>,
+    S::UserId: Arbitrary<'a>,
+    S::UserData: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(StateWrapper {
+            state: u.arbitrary()?,
+            users: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for SyncData<S>
+where
+    S: State,
+    S::UserId: Arbitrary<'a>,
+    StateWrapper<S>: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(SyncData {
+            user_id: u.arbitrary()?,
+            schema_version: u.arbitrary()?,
+            state: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for Res<S>
+where
+    S: State,
+    SyncData<S>: Arbitrary<'a>,
+    EventData<S>: Arbitrary<'a>,
+    S::ClientEvent: Arbitrary<'a>,
+    S::UserId: Arbitrary<'a>,
+    S::UserData: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=8)? {
+            0 => Res::Sync(u.arbitrary()?),
+            1 => Res::Event(u.arbitrary()?),
+            2 => Res::UserUpdate(u.arbitrary()?),
+            3 => Res::Message(u.arbitrary()?),
+            4 => Res::Pong(u.arbitrary()?, u.arbitrary()?),
+            5 => Res::Ack(u.arbitrary()?),
+            6 => Res::Rejected(u.arbitrary()?, u.arbitrary()?),
+            7 => Res::Extension(u.arbitrary()?, u.arbitrary()?),
+            _ => Res::Catalog(arbitrary_locale(u)?, u.arbitrary()?),
+        })
+    }
+}
+
+fn arbitrary_locale(u: &mut Unstructured<'_>) -> Result<i18n::Locale> {
+    let languages: Vec<_> = <i18n::Language as i18n::IntoEnumIterator>::iter().collect();
+    Ok(i18n::Locale(*u.choose(&languages)?, None))
+}
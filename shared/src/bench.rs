@@ -0,0 +1,71 @@
+use criterion::{black_box, BatchSize, BenchmarkId, Criterion, Throughput};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Event, EventData, State, StateWrapper};
+
+// Registers checksum, serialization and update benchmarks for `state` under
+// `group/parameter`, so states of different sizes can be compared in one group.
+pub fn bench_state<S>(
+    c: &mut Criterion,
+    group: &str,
+    parameter: impl std::fmt::Display,
+    state: &StateWrapper<S>,
+    events: &[Event<S>],
+) where
+    S: State + Serialize + DeserializeOwned,
+{
+    let serialized = rmp_serde::to_vec(state).unwrap();
+    let parameter = parameter.to_string();
+    let mut group = c.benchmark_group(group);
+
+    group.throughput(Throughput::Bytes(serialized.len() as u64));
+    group.bench_function(BenchmarkId::new("checksum", &parameter), |b| {
+        b.iter(|| black_box(state).checksum())
+    });
+    group.bench_function(BenchmarkId::new("serialize", &parameter), |b| {
+        b.iter(|| rmp_serde::to_vec(black_box(state)).unwrap())
+    });
+    group.bench_function(BenchmarkId::new("deserialize", &parameter), |b| {
+        b.iter(|| rmp_serde::from_slice::<StateWrapper<S>>(black_box(&serialized)).unwrap())
+    });
+
+    group.throughput(Throughput::Elements(events.len() as u64));
+    group.bench_function(BenchmarkId::new("update", &parameter), |b| {
+        b.iter_batched(
+            || state.clone(),
+            |mut state| {
+                let mut rng = ChaCha8Rng::seed_from_u64(0);
+                for event in events {
+                    state.state.update(&mut rng, event.clone(), &state.users);
+                }
+                state
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("update_checked", &parameter), |b| {
+        b.iter_batched(
+            || state.clone(),
+            |mut state| {
+                for (i, event) in events.iter().enumerate() {
+                    let mut seed = [0; 32];
+                    seed[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                    let state_checksum = state.checksum();
+                    state
+                        .update_checked(EventData {
+                            event: event.clone(),
+                            seed,
+                            state_checksum,
+                        })
+                        .unwrap();
+                }
+                state
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
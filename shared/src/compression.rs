@@ -0,0 +1,35 @@
+use std::borrow::Cow;
+
+use crate::Error;
+
+// MessagePack never uses this byte, so it marks compressed frames, and
+// uncompressed ones can still be decoded as they are.
+pub const COMPRESSED: u8 = 0xc1;
+
+// The web socket protocol of clients that can decompress frames, so servers
+// only compress frames for them.
+pub const PROTOCOL: &str = "engine-lz4";
+
+// Smaller frames, e.g. most events, don't get smaller by compressing them.
+const MIN_COMPRESSED_LEN: usize = 256;
+
+// Compresses an encoded response with LZ4 if it's large enough to be worth
+// it, e.g. a sync.
+pub fn compress(bytes: Vec<u8>) -> Vec<u8> {
+    if bytes.len() < MIN_COMPRESSED_LEN {
+        return bytes;
+    }
+    let mut compressed = vec![COMPRESSED];
+    compressed.extend(lz4_flex::compress_prepend_size(&bytes));
+    compressed
+}
+
+// Returns frames that aren't compressed unchanged.
+pub fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    match bytes.split_first() {
+        Some((&COMPRESSED, compressed)) => lz4_flex::decompress_size_prepended(compressed)
+            .map(Cow::Owned)
+            .map_err(|err| Error::Decode(err.to_string())),
+        _ => Ok(Cow::Borrowed(bytes)),
+    }
+}
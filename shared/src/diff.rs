@@ -0,0 +1,341 @@
+use std::{collections::HashMap, fmt, marker::PhantomData};
+
+use rmpv::Value;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PathSegment {
+    Key(Value),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(Value::String(key)) => match key.as_str() {
+                Some(key) => write!(f, ".{key}"),
+                None => write!(f, "[{key}]"),
+            },
+            PathSegment::Key(key) => write!(f, "[{key}]"),
+            PathSegment::Index(index) => write!(f, "[{index}]"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub path: Vec<PathSegment>,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl Change {
+    pub fn path_string(&self) -> String {
+        if self.path.is_empty() {
+            return String::from(".");
+        }
+        self.path.iter().map(ToString::to_string).collect()
+    }
+
+    fn invert(&self) -> Change {
+        Change {
+            path: self.path.clone(),
+            old: self.new.clone(),
+            new: self.old.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => String::from("<missing>"),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path_string(),
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDiff<S> {
+    changes: Vec<Change>,
+    #[serde(skip)]
+    _phantom: PhantomData<S>,
+}
+
+impl<S> Default for StateDiff<S> {
+    fn default() -> Self {
+        Self {
+            changes: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<S: Serialize + DeserializeOwned> StateDiff<S> {
+    pub fn diff(old: &S, new: &S) -> Result<Self, Error> {
+        let mut changes = Vec::new();
+        diff_values(
+            &mut Vec::new(),
+            &to_value(old)?,
+            &to_value(new)?,
+            &mut changes,
+        );
+        Ok(Self {
+            changes,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn apply(&self, state: &mut S) -> Result<(), Error> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        let mut value = to_value(state)?;
+        for change in &self.changes {
+            apply_change(&mut value, change)?;
+        }
+        *state = from_value(&value)?;
+        Ok(())
+    }
+
+    pub fn invert(&self) -> Self {
+        Self {
+            changes: self.changes.iter().rev().map(Change::invert).collect(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> StateDiff<S> {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub path: String,
+    pub expected: Option<Value>,
+    pub actual: Option<Value>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => String::from("<missing>"),
+        };
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.path,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+pub fn divergences<T: Serialize>(expected: &T, actual: &T) -> Result<Vec<Divergence>, Error> {
+    Ok(divergences_between(
+        &to_value(expected)?,
+        &to_value(actual)?,
+    ))
+}
+
+// Works on states serialized with any of the msgpack encodings, but without
+// field names the paths only contain indices.
+pub fn divergences_serialized(expected: &[u8], actual: &[u8]) -> Result<Vec<Divergence>, Error> {
+    let decode = |mut bytes: &[u8]| {
+        rmpv::decode::read_value(&mut bytes).map_err(|err| Error::Decode(err.to_string()))
+    };
+    Ok(divergences_between(&decode(expected)?, &decode(actual)?))
+}
+
+fn divergences_between(expected: &Value, actual: &Value) -> Vec<Divergence> {
+    let mut changes = Vec::new();
+    diff_values(&mut Vec::new(), expected, actual, &mut changes);
+    changes
+        .into_iter()
+        .map(|change| Divergence {
+            path: change.path_string(),
+            expected: change.old,
+            actual: change.new,
+        })
+        .collect()
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, Error> {
+    let bytes = rmp_serde::to_vec_named(value).map_err(|err| Error::Encode(err.to_string()))?;
+    rmpv::decode::read_value(&mut bytes.as_slice()).map_err(|err| Error::Decode(err.to_string()))
+}
+
+fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, Error> {
+    let mut bytes = Vec::new();
+    rmpv::encode::write_value(&mut bytes, value).map_err(|err| Error::Encode(err.to_string()))?;
+    rmp_serde::from_slice(&bytes).map_err(|err| Error::Decode(err.to_string()))
+}
+
+fn key_bytes(key: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    rmpv::encode::write_value(&mut bytes, key).expect("writing to a vec can't fail");
+    bytes
+}
+
+// Whether removing the entries of `from` that `to` doesn't have and appending
+// the ones it doesn't have itself results in the key order of `to`.
+fn order_preserved(
+    from: &[(Value, Value)],
+    to: &[(Value, Value)],
+    from_keys: &HashMap<Vec<u8>, usize>,
+    to_keys: &HashMap<Vec<u8>, usize>,
+) -> bool {
+    let kept = from
+        .iter()
+        .filter(|(key, _)| to_keys.contains_key(&key_bytes(key)));
+    let inserted = to
+        .iter()
+        .filter(|(key, _)| !from_keys.contains_key(&key_bytes(key)));
+    kept.chain(inserted)
+        .map(|(key, _)| key)
+        .eq(to.iter().map(|(key, _)| key))
+}
+
+fn diff_values(path: &mut Vec<PathSegment>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    if old == new {
+        return;
+    }
+
+    match (old, new) {
+        (Value::Map(old_entries), Value::Map(new_entries)) => {
+            let new_index: HashMap<Vec<u8>, usize> = new_entries
+                .iter()
+                .enumerate()
+                .map(|(i, (key, _))| (key_bytes(key), i))
+                .collect();
+            let old_keys: HashMap<Vec<u8>, usize> = old_entries
+                .iter()
+                .enumerate()
+                .map(|(i, (key, _))| (key_bytes(key), i))
+                .collect();
+
+            // Applying the diff removes entries in place and appends inserted
+            // ones, so the key order has to match for the result to serialize
+            // identically. The same goes for the inverted diff, which removes
+            // the inserted entries and appends the removed ones. Otherwise, the
+            // whole map is replaced.
+            if !order_preserved(old_entries, new_entries, &old_keys, &new_index)
+                || !order_preserved(new_entries, old_entries, &new_index, &old_keys)
+            {
+                changes.push(Change {
+                    path: path.clone(),
+                    old: Some(old.clone()),
+                    new: Some(new.clone()),
+                });
+                return;
+            }
+
+            for (key, old_value) in old_entries {
+                path.push(PathSegment::Key(key.clone()));
+                match new_index.get(&key_bytes(key)) {
+                    Some(&i) => diff_values(path, old_value, &new_entries[i].1, changes),
+                    None => changes.push(Change {
+                        path: path.clone(),
+                        old: Some(old_value.clone()),
+                        new: None,
+                    }),
+                }
+                path.pop();
+            }
+            for (key, new_value) in new_entries {
+                if !old_keys.contains_key(&key_bytes(key)) {
+                    path.push(PathSegment::Key(key.clone()));
+                    changes.push(Change {
+                        path: path.clone(),
+                        old: None,
+                        new: Some(new_value.clone()),
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items))
+            if old_items.len() == new_items.len() =>
+        {
+            for (i, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                path.push(PathSegment::Index(i));
+                diff_values(path, old_item, new_item, changes);
+                path.pop();
+            }
+        }
+        _ => changes.push(Change {
+            path: path.clone(),
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+    }
+}
+
+fn apply_change(root: &mut Value, change: &Change) -> Result<(), Error> {
+    let mismatch = || Error::DiffMismatch(change.path_string());
+
+    let Some((last, parents)) = change.path.split_last() else {
+        if Some(&*root) != change.old.as_ref() {
+            return Err(mismatch());
+        }
+        *root = change.new.clone().ok_or_else(mismatch)?;
+        return Ok(());
+    };
+
+    let mut value = root;
+    for segment in parents {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Value::Map(entries)) => entries
+                .iter_mut()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or_else(mismatch)?,
+            (PathSegment::Index(i), Value::Array(items)) => {
+                items.get_mut(*i).ok_or_else(mismatch)?
+            }
+            _ => return Err(mismatch()),
+        };
+    }
+
+    match (last, value) {
+        (PathSegment::Key(key), Value::Map(entries)) => {
+            let position = entries.iter().position(|(k, _)| k == key);
+            match (position, &change.old, &change.new) {
+                (None, None, Some(new)) => entries.push((key.clone(), new.clone())),
+                (Some(i), Some(old), None) if &entries[i].1 == old => {
+                    entries.remove(i);
+                }
+                (Some(i), Some(old), Some(new)) if &entries[i].1 == old => {
+                    entries[i].1 = new.clone();
+                }
+                _ => return Err(mismatch()),
+            }
+        }
+        (PathSegment::Index(i), Value::Array(items)) => {
+            match (items.get_mut(*i), &change.old, &change.new) {
+                (Some(item), Some(old), Some(new)) if item == old => *item = new.clone(),
+                _ => return Err(mismatch()),
+            }
+        }
+        _ => return Err(mismatch()),
+    }
+
+    Ok(())
+}
@@ -1,19 +1,31 @@
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod diff;
+pub mod replay;
+pub mod testing;
 pub mod utils;
 
-use rand::{Rng, SeedableRng};
+use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::time::Duration;
-use utils::custom_map::CustomMap;
+use utils::custom_map::{CustomMap, MapDiff};
+use utils::rng::Fork;
 
 pub type Seed = [u8; 32];
 pub type Checksum = [u8; 32];
 
 pub type GameId = i64;
 
+pub type SchemaVersion = u32;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EventData<S: State> {
     pub event: Event<S>,
@@ -27,21 +39,54 @@ pub type EventIndex = u64;
 pub enum Req<S: State> {
     Event(S::ClientEvent),
     Sync,
+    SetLocales(Vec<i18n::Locale>),
+    // Answered with a `Pong` with the same id, to measure the latency.
+    Ping(u32),
+    // An event the server answers with an `Ack` with the same id once it
+    // got it. Events that are sent again with the same id, e.g. because the
+    // ack was lost, are only applied once.
+    AckedEvent(u64, S::ClientEvent),
+    // Events that are applied one after another, without other events in
+    // between. If one of them is rejected, all of them are.
+    Events(Vec<S::ClientEvent>),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Res<S: State> {
     Sync(SyncData<S>),
     Event(EventData<S>),
-    UserUpdate(CustomMap<S::UserId, S::UserData>),
+    // The users that changed since the last update or sync.
+    UserUpdate(MapDiff<S::UserId, S::UserData>),
+    Message(String),
+    Catalog(i18n::Locale, String),
+    // The id of the ping, and the server's time in milliseconds since the
+    // Unix epoch when it was answered.
+    Pong(u32, u64),
+    Ack(u64),
+    // An own event that the server didn't apply, see `State::validate`.
+    Rejected(S::ClientEvent, String),
+    // A message of the application that isn't part of the state, e.g. for a
+    // chat, with its kind and its encoded payload.
+    Extension(String, Vec<u8>),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SyncData<S: State> {
     pub user_id: S::UserId,
+    pub schema_version: SchemaVersion,
     pub state: StateWrapper<S>,
 }
 
+impl<S: State> SyncData<S> {
+    pub fn new(user_id: S::UserId, state: StateWrapper<S>) -> Self {
+        SyncData {
+            user_id,
+            schema_version: S::SCHEMA_VERSION,
+            state,
+        }
+    }
+}
+
 pub trait State: Clone + Debug + Send + Sized + Default + 'static {
     type ServerEvent: ServerEvent<Self>;
     type ClientEvent: ClientEvent;
@@ -49,14 +94,56 @@ pub trait State: Clone + Debug + Send + Sized + Default + 'static {
     type UserData: UserData;
 
     const DURATION_PER_TICK: Duration;
+    const SCHEMA_VERSION: SchemaVersion = 0;
 
     fn update(
         &mut self,
-        rng: &mut impl Rng,
+        rng: &mut impl Fork,
         event: Event<Self>,
         user_data: &CustomMap<Self::UserId, Self::UserData>,
     );
     fn closed(&self) -> bool;
+
+    fn invariants(&self) -> Option<&dyn StateInvariants> {
+        None
+    }
+
+    // Rejects client events that are illegal in this state, e.g. building
+    // without enough resources. The server doesn't apply rejected events and
+    // tells the sender why, and clients can check events before sending.
+    fn validate(&self, _event: &Self::ClientEvent, _user_id: &Self::UserId) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn upgrade_from(version: SchemaVersion, _bytes: &[u8]) -> Result<Self, Error> {
+        Err(Error::UnsupportedSchemaVersion {
+            expected: Self::SCHEMA_VERSION,
+            actual: version,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionedState {
+    pub schema_version: SchemaVersion,
+    pub bytes: Vec<u8>,
+}
+
+impl VersionedState {
+    pub fn encode<S: State + Serialize>(state: &S) -> Result<Self, Error> {
+        Ok(VersionedState {
+            schema_version: S::SCHEMA_VERSION,
+            bytes: rmp_serde::to_vec(state).map_err(|err| Error::Encode(err.to_string()))?,
+        })
+    }
+
+    pub fn decode<S: State + DeserializeOwned>(&self) -> Result<S, Error> {
+        if self.schema_version == S::SCHEMA_VERSION {
+            rmp_serde::from_slice(&self.bytes).map_err(|err| Error::Decode(err.to_string()))
+        } else {
+            S::upgrade_from(self.schema_version, &self.bytes)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +152,10 @@ pub enum Event<S: State> {
     ClientEvent(S::ClientEvent, S::UserId),
 }
 
+pub trait StateInvariants {
+    fn check(&self) -> Result<(), String>;
+}
+
 pub trait ServerEvent<S: State>:
     Clone + Serialize + DeserializeOwned + Send + Debug + Send + 'static
 {
@@ -75,24 +166,73 @@ pub trait ClientEvent:
     Clone + Serialize + DeserializeOwned + Send + Debug + Send + 'static
 {
     fn init() -> Self;
+
+    // Whether the client drops the event while it's offline instead of
+    // sending it after reconnecting, e.g. if it's only relevant right now.
+    fn droppable(&self) -> bool {
+        false
+    }
+
+    // The event that reverts this one, e.g. cancelling an order, so it can
+    // be undone after it was sent.
+    fn undo(&self) -> Option<Self> {
+        None
+    }
 }
 
+// Ordered, so the users serialize the same independent of when they joined.
 pub trait UserId:
-    Clone + Serialize + DeserializeOwned + Send + Debug + PartialEq + Eq + Hash + Send + 'static
+    Clone + Serialize + DeserializeOwned + Send + Debug + PartialEq + Eq + Hash + Ord + Send + 'static
 {
 }
 
 pub trait UserData: Clone + Serialize + DeserializeOwned + Send + Debug + Send + 'static {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Implemented by user data that knows the preferred locales of its user, so
+// that server messages can be rendered in the language of each recipient.
+pub trait HasLocale {
+    fn locales(&self) -> &[i18n::Locale];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
 pub enum Error {
-    InvalidChecksum,
-    WorldClosed,
+    #[error(
+        "invalid checksum before applying {event}: expected {}, got {}",
+        hex(.expected),
+        hex(.actual)
+    )]
+    InvalidChecksum {
+        expected: Checksum,
+        actual: Checksum,
+        event: String,
+    },
+    #[error("world is closed, rejected {event}")]
+    WorldClosed { event: String },
+    #[error("invariant violated after applying {event}: {violation}")]
+    InvariantViolated { event: String, violation: String },
+    #[error("failed to encode message: {0}")]
+    Encode(String),
+    #[error("failed to decode message: {0}")]
+    Decode(String),
+    #[error("diff doesn't match the state at {0}")]
+    DiffMismatch(String),
+    #[error("no entry found for key {0}")]
+    MissingKey(String),
+    #[error("can't upgrade state from schema version {actual} to {expected}")]
+    UnsupportedSchemaVersion {
+        expected: SchemaVersion,
+        actual: SchemaVersion,
+    },
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateWrapper<S: State> {
     pub state: S,
+    #[serde(with = "utils::custom_map::sorted")]
     pub users: CustomMap<S::UserId, S::UserData>,
 }
 
@@ -121,18 +261,48 @@ impl<S: State> StateWrapper<S> {
         Self: Serialize,
     {
         if self.state.closed() {
-            return Err(Error::WorldClosed);
+            return Err(Error::WorldClosed {
+                event: format!("{event:?}"),
+            });
         }
 
         let checksum = self.checksum();
         if checksum != state_checksum {
-            return Err(Error::InvalidChecksum);
+            return Err(Error::InvalidChecksum {
+                expected: state_checksum,
+                actual: checksum,
+                event: format!("{event:?}"),
+            });
         }
 
+        #[cfg(debug_assertions)]
+        let debug_event = event.clone();
+
         let mut rng = ChaCha8Rng::from_seed(seed);
 
         self.state.update(&mut rng, event, &self.users);
 
+        #[cfg(debug_assertions)]
+        self.check_invariants(&debug_event)?;
+
         Ok(())
     }
+
+    // Applies an event without verifying the checksum, e.g. to predict the
+    // outcome of an own event before the server confirms it.
+    pub fn update_unchecked(&mut self, event: Event<S>, seed: Seed) {
+        let mut rng = ChaCha8Rng::from_seed(seed);
+        self.state.update(&mut rng, event, &self.users);
+    }
+
+    #[cfg(debug_assertions)]
+    pub(crate) fn check_invariants(&self, event: &Event<S>) -> Result<(), Error> {
+        match self.state.invariants().map(StateInvariants::check) {
+            Some(Err(violation)) => Err(Error::InvariantViolated {
+                event: format!("{event:?}"),
+                violation,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
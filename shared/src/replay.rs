@@ -0,0 +1,157 @@
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    utils::custom_map::MapDiff, Error, EventData, Res, SchemaVersion, State, StateWrapper,
+};
+
+// States are kept this many entries apart while playing, so seeking back
+// doesn't have to start over from the beginning.
+const CHECKPOINT_INTERVAL: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplayEntry<S: State> {
+    Event(EventData<S>),
+    UserUpdate(MapDiff<S::UserId, S::UserData>),
+}
+
+// A recorded match: the state it started from and everything that changed
+// it since, with the seeds the server drew, so it plays back exactly like it
+// happened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay<S: State> {
+    pub schema_version: SchemaVersion,
+    pub start: StateWrapper<S>,
+    pub entries: Vec<ReplayEntry<S>>,
+}
+
+impl<S: State> Replay<S> {
+    pub fn new(start: StateWrapper<S>) -> Self {
+        Replay {
+            schema_version: S::SCHEMA_VERSION,
+            start,
+            entries: Vec::new(),
+        }
+    }
+
+    // Records the responses that change the state, so the responses of a
+    // connection can be recorded as they arrive, starting with its sync.
+    pub fn record(&mut self, res: &Res<S>) {
+        match res {
+            Res::Event(event) => self.entries.push(ReplayEntry::Event(event.clone())),
+            Res::UserUpdate(diff) => self.entries.push(ReplayEntry::UserUpdate(diff.clone())),
+            _ => {}
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Error>
+    where
+        S: Serialize,
+    {
+        rmp_serde::to_vec(self).map_err(|err| Error::Encode(err.to_string()))
+    }
+
+    // Replays of other schema versions can't be decoded, and wouldn't play
+    // back the same anyway.
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error>
+    where
+        S: DeserializeOwned,
+    {
+        #[derive(Deserialize)]
+        struct Header {
+            schema_version: SchemaVersion,
+            #[allow(dead_code)]
+            start: IgnoredAny,
+            #[allow(dead_code)]
+            entries: IgnoredAny,
+        }
+
+        let header: Header =
+            rmp_serde::from_slice(bytes).map_err(|err| Error::Decode(err.to_string()))?;
+        if header.schema_version != S::SCHEMA_VERSION {
+            return Err(Error::UnsupportedSchemaVersion {
+                expected: S::SCHEMA_VERSION,
+                actual: header.schema_version,
+            });
+        }
+        rmp_serde::from_slice(bytes).map_err(|err| Error::Decode(err.to_string()))
+    }
+}
+
+// Steps through a replay and verifies the checksum of every event, so a
+// replay that doesn't match the game's logic anymore is noticed.
+pub struct ReplayPlayer<S: State> {
+    replay: Replay<S>,
+    state: StateWrapper<S>,
+    position: usize,
+    checkpoints: Vec<StateWrapper<S>>,
+}
+
+impl<S: State + Serialize> ReplayPlayer<S> {
+    pub fn new(replay: Replay<S>) -> Self {
+        let state = replay.start.clone();
+        ReplayPlayer {
+            checkpoints: vec![state.clone()],
+            replay,
+            state,
+            position: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay<S> {
+        &self.replay
+    }
+
+    // The state after the entries before the position.
+    pub fn state(&self) -> &StateWrapper<S> {
+        &self.state
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.entries.is_empty()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.len()
+    }
+
+    // Applies the next entry and returns it, or `None` at the end.
+    pub fn step(&mut self) -> Result<Option<&ReplayEntry<S>>, Error> {
+        let Some(entry) = self.replay.entries.get(self.position) else {
+            return Ok(None);
+        };
+        match entry {
+            ReplayEntry::Event(event) => self.state.update_checked(event.clone())?,
+            ReplayEntry::UserUpdate(diff) => self.state.users.apply_diff(diff.clone()),
+        }
+        self.position += 1;
+        if self.position == self.checkpoints.len() * CHECKPOINT_INTERVAL {
+            self.checkpoints.push(self.state.clone());
+        }
+        Ok(Some(entry))
+    }
+
+    // Moves to the given position, from the closest state already known.
+    pub fn seek(&mut self, position: usize) -> Result<(), Error> {
+        let position = position.min(self.len());
+        let checkpoint = (position / CHECKPOINT_INTERVAL).min(self.checkpoints.len() - 1);
+        if position < self.position || checkpoint * CHECKPOINT_INTERVAL > self.position {
+            self.state = self.checkpoints[checkpoint].clone();
+            self.position = checkpoint * CHECKPOINT_INTERVAL;
+        }
+        while self.position < position {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{de::DeserializeOwned, Serialize};
+use std::time::Duration;
+
+use crate::{
+    utils::custom_map::CustomMap, Checksum, ClientEvent, Error, Event, EventData, Seed,
+    ServerEvent, State, StateWrapper,
+};
+
+pub struct Simulation<S: State> {
+    server: StateWrapper<S>,
+    clients: CustomMap<S::UserId, StateWrapper<S>>,
+    rng: ChaCha8Rng,
+    events: Vec<EventData<S>>,
+    ticks: u64,
+}
+
+impl<S: State + Serialize + DeserializeOwned> Simulation<S> {
+    pub fn new(seed: u64, state: S) -> Self {
+        Self::with_users(seed, state, CustomMap::new())
+    }
+
+    pub fn with_users(seed: u64, state: S, users: CustomMap<S::UserId, S::UserData>) -> Self {
+        Simulation {
+            server: StateWrapper { state, users },
+            clients: CustomMap::new(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            events: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    pub fn set_user_data(&mut self, user_id: S::UserId, user_data: S::UserData) {
+        self.server.users.insert(user_id.clone(), user_data.clone());
+        for client in self.clients.values_mut() {
+            client.users.insert(user_id.clone(), user_data.clone());
+        }
+    }
+
+    pub fn connect(&mut self, user_id: S::UserId) -> Result<(), Error> {
+        self.sync(user_id.clone())?;
+        self.client_event(user_id, <S::ClientEvent as ClientEvent>::init())
+    }
+
+    pub fn disconnect(&mut self, user_id: &S::UserId) -> bool {
+        self.clients.shift_remove(user_id).is_some()
+    }
+
+    pub fn sync(&mut self, user_id: S::UserId) -> Result<(), Error> {
+        let bytes =
+            rmp_serde::to_vec(&self.server).map_err(|err| Error::Encode(err.to_string()))?;
+        let state = rmp_serde::from_slice(&bytes).map_err(|err| Error::Decode(err.to_string()))?;
+        self.clients.insert(user_id, state);
+        Ok(())
+    }
+
+    pub fn client_event(&mut self, user_id: S::UserId, event: S::ClientEvent) -> Result<(), Error> {
+        self.dispatch(Event::ClientEvent(event, user_id))
+    }
+
+    pub fn server_event(&mut self, event: S::ServerEvent) -> Result<(), Error> {
+        self.dispatch(Event::ServerEvent(event))
+    }
+
+    pub fn tick(&mut self) -> Result<(), Error> {
+        self.ticks += 1;
+        self.server_event(<S::ServerEvent as ServerEvent<S>>::tick())
+    }
+
+    // Runs the ticks without computing checksums or recording the events, for
+    // long horizons where only the resulting state is of interest.
+    pub fn advance_ticks(&mut self, ticks: u64) -> Result<(), Error> {
+        for _ in 0..ticks {
+            let event = Event::ServerEvent(<S::ServerEvent as ServerEvent<S>>::tick());
+            if self.server.state.closed() {
+                return Err(Error::WorldClosed {
+                    event: format!("{event:?}"),
+                });
+            }
+
+            let seed: Seed = self.rng.gen();
+            for client in self.clients.values_mut() {
+                let mut rng = ChaCha8Rng::from_seed(seed);
+                client.state.update(&mut rng, event.clone(), &client.users);
+            }
+            let mut rng = ChaCha8Rng::from_seed(seed);
+            self.server
+                .state
+                .update(&mut rng, event.clone(), &self.server.users);
+            self.ticks += 1;
+
+            #[cfg(debug_assertions)]
+            self.server.check_invariants(&event)?;
+        }
+        Ok(())
+    }
+
+    pub fn advance_time(&mut self, duration: Duration) -> Result<(), Error> {
+        let ticks = duration.as_nanos() / S::DURATION_PER_TICK.as_nanos().max(1);
+        self.advance_ticks(ticks.try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn dispatch(&mut self, event: Event<S>) -> Result<(), Error> {
+        let seed: Seed = self.rng.gen();
+        let event = EventData {
+            event,
+            seed,
+            state_checksum: self.server.checksum(),
+        };
+
+        self.server.update_checked(event.clone())?;
+        for client in self.clients.values_mut() {
+            client.update_checked(event.clone())?;
+        }
+        self.events.push(event);
+
+        Ok(())
+    }
+
+    pub fn run(&mut self, events: impl IntoIterator<Item = Event<S>>) -> Result<(), Error> {
+        events
+            .into_iter()
+            .try_for_each(|event| self.dispatch(event))
+    }
+
+    // Dispatches generated events until the input runs out. Client events are
+    // only sent on behalf of connected users.
+    #[cfg(feature = "arbitrary")]
+    pub fn run_arbitrary<'a>(&mut self, u: &mut arbitrary::Unstructured<'a>) -> Result<(), Error>
+    where
+        S::ServerEvent: arbitrary::Arbitrary<'a>,
+        S::ClientEvent: arbitrary::Arbitrary<'a>,
+    {
+        while !u.is_empty() {
+            let user_ids: Vec<S::UserId> = self.clients.keys().cloned().collect();
+            let event = if user_ids.is_empty() || u.arbitrary().unwrap_or_default() {
+                u.arbitrary().map(Event::ServerEvent)
+            } else {
+                u.choose(&user_ids).cloned().and_then(|user_id| {
+                    u.arbitrary()
+                        .map(|event| Event::ClientEvent(event, user_id))
+                })
+            };
+            match event {
+                Ok(event) => self.dispatch(event)?,
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn state(&self) -> &S {
+        &self.server.state
+    }
+
+    pub fn client_state(&self, user_id: &S::UserId) -> Option<&S> {
+        self.clients.get(user_id).map(|client| &client.state)
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        self.server.checksum()
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn events(&self) -> &[EventData<S>] {
+        &self.events
+    }
+
+    pub fn diverged(&self) -> Vec<S::UserId> {
+        let checksum = self.checksum();
+        self.clients
+            .iter()
+            .filter(|(_, client)| client.checksum() != checksum)
+            .map(|(user_id, _)| user_id.clone())
+            .collect()
+    }
+
+    #[track_caller]
+    pub fn assert_in_sync(&self) {
+        let diverged = self.diverged();
+        assert!(
+            diverged.is_empty(),
+            "clients diverged from the server after {} events: {diverged:?}",
+            self.events.len()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_checksum(&self, expected: Checksum) {
+        let actual = self.checksum();
+        assert_eq!(
+            actual,
+            expected,
+            "unexpected checksum after {} events",
+            self.events.len()
+        );
+    }
+}
@@ -1,3 +1,15 @@
+pub mod build_queue;
+pub mod combat;
+pub mod components;
 pub mod custom_map;
+pub mod entity_index;
 pub mod entity_set;
+pub mod fixed;
+pub mod generate;
+pub mod grid;
 pub mod qty;
+pub mod rate;
+pub mod relation;
+pub mod rng;
+pub mod spatial;
+pub mod tech_tree;
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub type JobId = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Job<T> {
+    id: JobId,
+    item: T,
+    duration: u64,
+    remaining: u64,
+}
+
+impl<T> Job<T> {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn item(&self) -> &T {
+        &self.item
+    }
+
+    pub fn into_item(self) -> T {
+        self.item
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    // Ticks worked on it so far, including sped up ones, e.g. to refund the
+    // costs of cancelled jobs in proportion with `Qty::scale_ratio`.
+    pub fn elapsed(&self) -> u64 {
+        self.duration - self.remaining
+    }
+}
+
+// Constructions, upgrades or trainings that take a number of ticks. Only the
+// first `slots` jobs are worked on at the same time, the others wait in order.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct BuildQueue<T> {
+    jobs: VecDeque<Job<T>>,
+    slots: usize,
+    next_id: JobId,
+}
+
+impl<T> Default for BuildQueue<T> {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl<T> BuildQueue<T> {
+    pub fn new(slots: usize) -> Self {
+        BuildQueue {
+            jobs: VecDeque::new(),
+            slots: slots.max(1),
+            next_id: 0,
+        }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    pub fn set_slots(&mut self, slots: usize) {
+        self.slots = slots.max(1);
+    }
+
+    pub fn push(&mut self, item: T, duration: u64) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push_back(Job {
+            id,
+            item,
+            duration,
+            remaining: duration,
+        });
+        id
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&Job<T>> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    // In order, the ones that are worked on first.
+    pub fn jobs(&self) -> impl Iterator<Item = &Job<T>> {
+        self.jobs.iter()
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Job<T>> {
+        self.jobs.iter().take(self.slots)
+    }
+
+    pub fn is_active(&self, id: JobId) -> bool {
+        self.active().any(|job| job.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    // Returns the ticks it actually saved. Jobs without remaining ticks are
+    // completed with the next tick, like all others.
+    pub fn speed_up(&mut self, id: JobId, ticks: u64) -> u64 {
+        match self.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) => {
+                let saved = ticks.min(job.remaining);
+                job.remaining -= saved;
+                saved
+            }
+            None => 0,
+        }
+    }
+
+    pub fn finish_now(&mut self, id: JobId) -> u64 {
+        self.speed_up(id, u64::MAX)
+    }
+
+    // The job is returned, so its costs can be refunded.
+    pub fn cancel(&mut self, id: JobId) -> Option<Job<T>> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        self.jobs.remove(index)
+    }
+
+    // Called for every tick in `State::update`. Returns the jobs that were
+    // completed, in the order they were queued, so their events can be raised.
+    pub fn tick(&mut self) -> Vec<Job<T>> {
+        self.advance(1)
+    }
+
+    // Jobs that wait start as soon as a slot becomes free, also in between.
+    pub fn advance(&mut self, mut ticks: u64) -> Vec<Job<T>> {
+        let mut completed = Vec::new();
+        loop {
+            self.complete(&mut completed);
+            let Some(step) = self.active().map(|job| job.remaining).min() else {
+                break;
+            };
+            let step = step.min(ticks);
+            if step == 0 {
+                break;
+            }
+            for job in self.jobs.iter_mut().take(self.slots) {
+                job.remaining -= step;
+            }
+            ticks -= step;
+        }
+        completed
+    }
+
+    fn complete(&mut self, completed: &mut Vec<Job<T>>) {
+        loop {
+            let done = self.active().position(|job| job.remaining == 0);
+            let Some(index) = done else {
+                break;
+            };
+            completed.extend(self.jobs.remove(index));
+        }
+    }
+}
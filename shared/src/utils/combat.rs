@@ -0,0 +1,199 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+use super::{custom_map::CustomMap, qty::Qty};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct UnitStats {
+    pub attack: u64,
+    // Reduces the damage taken, 100 defense halves it.
+    pub defense: u64,
+    pub health: u64,
+}
+
+// Resolves battles between two armies in rounds in which both sides deal
+// damage at the same time, until one is defeated or `max_rounds` are over.
+// The only randomness is drawn from the given RNG, so with e.g.
+// `rng.fork("combat")` in `State::update`, battles play out the same on the
+// server, the clients and in replays.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct CombatResolver<U: Hash + Eq> {
+    stats: CustomMap<U, UnitStats>,
+    max_rounds: u32,
+    spread: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    AttackerWon,
+    DefenderWon,
+    // Both sides were defeated or the rounds ran out.
+    Draw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Round<U: Hash + Eq> {
+    pub attacker_damage: u64,
+    pub defender_damage: u64,
+    pub attacker_losses: Qty<U>,
+    pub defender_losses: Qty<U>,
+}
+
+// Only data, so games can render it in the reader's language, e.g. the
+// losses with `Qty`'s `Localizable` implementation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct BattleReport<U: Hash + Eq> {
+    pub attacker: Qty<U>,
+    pub defender: Qty<U>,
+    pub rounds: Vec<Round<U>>,
+    pub outcome: Outcome,
+}
+
+impl<U: Hash + Eq + Copy> BattleReport<U> {
+    pub fn attacker_losses(&self) -> Qty<U> {
+        self.rounds.iter().fold(Qty::default(), |losses, round| {
+            losses + round.attacker_losses.clone()
+        })
+    }
+
+    pub fn defender_losses(&self) -> Qty<U> {
+        self.rounds.iter().fold(Qty::default(), |losses, round| {
+            losses + round.defender_losses.clone()
+        })
+    }
+
+    pub fn attacker_survivors(&self) -> Qty<U> {
+        self.attacker.saturating_sub(&self.attacker_losses())
+    }
+
+    pub fn defender_survivors(&self) -> Qty<U> {
+        self.defender.saturating_sub(&self.defender_losses())
+    }
+}
+
+// The units of a side that are still fighting, and the damage taken by the
+// next unit of each type.
+struct Side<U: Hash + Eq> {
+    units: Qty<U>,
+    wounds: CustomMap<U, u64>,
+}
+
+impl<U: Hash + Eq + Copy> CombatResolver<U> {
+    pub fn new(max_rounds: u32) -> Self {
+        CombatResolver {
+            stats: CustomMap::new(),
+            max_rounds,
+            spread: 0,
+        }
+    }
+
+    // Units without stats don't fight and always survive, so a side with only
+    // such units is defeated right away.
+    pub fn with_unit(mut self, unit: U, stats: UnitStats) -> Self {
+        self.stats.insert(unit, stats);
+        self
+    }
+
+    // The damage of a side varies by up to this many percent either way in
+    // every round.
+    pub fn with_spread(mut self, percent: u64) -> Self {
+        self.spread = percent.min(100);
+        self
+    }
+
+    pub fn stats(&self, unit: &U) -> Option<&UnitStats> {
+        self.stats.get(unit)
+    }
+
+    pub fn resolve(
+        &self,
+        rng: &mut impl Rng,
+        attacker: &Qty<U>,
+        defender: &Qty<U>,
+    ) -> BattleReport<U> {
+        let mut attacking = self.side(attacker);
+        let mut defending = self.side(defender);
+        let mut rounds = Vec::new();
+        while rounds.len() < self.max_rounds as usize
+            && !attacking.units.is_empty()
+            && !defending.units.is_empty()
+        {
+            let attacker_damage = self.damage(rng, &attacking.units);
+            let defender_damage = self.damage(rng, &defending.units);
+            rounds.push(Round {
+                attacker_damage,
+                defender_damage,
+                attacker_losses: self.take(&mut attacking, defender_damage),
+                defender_losses: self.take(&mut defending, attacker_damage),
+            });
+        }
+
+        let outcome = match (attacking.units.is_empty(), defending.units.is_empty()) {
+            (false, true) => Outcome::AttackerWon,
+            (true, false) => Outcome::DefenderWon,
+            _ => Outcome::Draw,
+        };
+        BattleReport {
+            attacker: attacker.clone(),
+            defender: defender.clone(),
+            rounds,
+            outcome,
+        }
+    }
+
+    fn side(&self, army: &Qty<U>) -> Side<U> {
+        let mut units = Qty::default();
+        for (unit, num) in army.iter() {
+            if self.stats.contains_key(&unit) {
+                units.add(unit, num);
+            }
+        }
+        Side {
+            units,
+            wounds: CustomMap::new(),
+        }
+    }
+
+    fn damage(&self, rng: &mut impl Rng, units: &Qty<U>) -> u64 {
+        let base: u128 = self
+            .stats
+            .iter()
+            .map(|(unit, stats)| units.get(unit) as u128 * stats.attack as u128)
+            .sum();
+        let percent = rng.gen_range(100 - self.spread..=100 + self.spread);
+        (base * percent as u128 / 100)
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    // Spreads the damage over the unit types by their numbers, in the order
+    // the stats were added, so the result doesn't depend on how the army was
+    // built.
+    fn take(&self, side: &mut Side<U>, damage: u64) -> Qty<U> {
+        let total = side.units.total() as u128;
+        let mut losses = Qty::default();
+        for (unit, stats) in &self.stats {
+            let num = side.units.get(unit);
+            if num == 0 {
+                continue;
+            }
+            let share = damage as u128 * num as u128 / total;
+            let mitigated = share * 100 / (100 + stats.defense as u128);
+            let wounds = side.wounds.get(unit).copied().unwrap_or_default();
+            let health = stats.health.max(1) as u128;
+            let damage = mitigated + wounds as u128;
+            let killed = (damage / health).min(num as u128) as u64;
+            if killed == num {
+                side.wounds.swap_remove(unit);
+            } else {
+                side.wounds.insert(*unit, (damage % health) as u64);
+            }
+            if killed > 0 {
+                losses.add(*unit, killed);
+            }
+        }
+        side.units = side.units.saturating_sub(&losses);
+        losses
+    }
+}
@@ -0,0 +1,159 @@
+use rand::Rng;
+use std::hash::Hash;
+
+use super::{
+    custom_map::CustomMap,
+    entity_set::{EntityRef, EntitySet},
+};
+
+pub type ComponentMap<M, C> = CustomMap<EntityRef<M>, C>;
+
+pub trait HasComponent<M, C> {
+    fn components(&self) -> &ComponentMap<M, C>;
+    fn components_mut(&mut self) -> &mut ComponentMap<M, C>;
+}
+
+pub trait Components<M: Hash + 'static>: Sized {
+    fn entities(&self) -> &EntitySet<M>;
+    fn entities_mut(&mut self) -> &mut EntitySet<M>;
+    fn despawn(&mut self, entity: &EntityRef<M>) -> bool;
+
+    fn spawn(&mut self, rng: &mut impl Rng) -> EntityRef<M>
+    where
+        M: Default,
+    {
+        self.entities_mut().insert_with(rng, M::default())
+    }
+
+    fn insert<C>(&mut self, entity: EntityRef<M>, component: C) -> Option<C>
+    where
+        Self: HasComponent<M, C>,
+    {
+        self.components_mut().insert(entity, component)
+    }
+
+    fn remove<C>(&mut self, entity: &EntityRef<M>) -> Option<C>
+    where
+        Self: HasComponent<M, C>,
+    {
+        self.components_mut().swap_remove(entity)
+    }
+
+    fn get<C>(&self, entity: &EntityRef<M>) -> Option<&C>
+    where
+        Self: HasComponent<M, C>,
+    {
+        self.components().get(entity)
+    }
+
+    fn get_mut<C>(&mut self, entity: &EntityRef<M>) -> Option<&mut C>
+    where
+        Self: HasComponent<M, C>,
+    {
+        self.components_mut().get_mut(entity)
+    }
+
+    fn iter1<'a, C: 'a>(&'a self) -> impl Iterator<Item = (EntityRef<M>, &'a C)> + 'a
+    where
+        Self: HasComponent<M, C>,
+    {
+        self.components()
+            .iter()
+            .map(|(&entity, component)| (entity, component))
+    }
+
+    fn iter2<'a, A: 'a, B: 'a>(&'a self) -> impl Iterator<Item = (EntityRef<M>, &'a A, &'a B)> + 'a
+    where
+        Self: HasComponent<M, A> + HasComponent<M, B>,
+    {
+        let b_map = <Self as HasComponent<M, B>>::components(self);
+        <Self as HasComponent<M, A>>::components(self)
+            .iter()
+            .flat_map(move |(&entity, a)| b_map.get(&entity).map(|b| (entity, a, b)))
+    }
+
+    fn iter3<'a, A: 'a, B: 'a, C: 'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (EntityRef<M>, &'a A, &'a B, &'a C)> + 'a
+    where
+        Self: HasComponent<M, A> + HasComponent<M, B> + HasComponent<M, C>,
+    {
+        let c_map = <Self as HasComponent<M, C>>::components(self);
+        self.iter2::<A, B>()
+            .flat_map(move |(entity, a, b)| c_map.get(&entity).map(|c| (entity, a, b, c)))
+    }
+
+    // The component types have to be distinct.
+    fn for_each2_mut<A, B, F>(&mut self, mut f: F)
+    where
+        Self: HasComponent<M, A> + HasComponent<M, B>,
+        F: FnMut(EntityRef<M>, &mut A, &B),
+    {
+        let mut a_map = std::mem::take(<Self as HasComponent<M, A>>::components_mut(self));
+        let b_map = <Self as HasComponent<M, B>>::components(self);
+        for (&entity, a) in a_map.iter_mut() {
+            if let Some(b) = b_map.get(&entity) {
+                f(entity, a, b);
+            }
+        }
+        *<Self as HasComponent<M, A>>::components_mut(self) = a_map;
+    }
+}
+
+#[macro_export]
+macro_rules! components {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident for $marker:ty {
+            $( $field:ident: $component:ty ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            entities: $crate::utils::entity_set::EntitySet<$marker>,
+            $(
+                $field: $crate::utils::components::ComponentMap<$marker, $component>,
+            )*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    entities: Default::default(),
+                    $(
+                        $field: $crate::utils::custom_map::CustomMap::new(),
+                    )*
+                }
+            }
+        }
+
+        $(
+            impl $crate::utils::components::HasComponent<$marker, $component> for $name {
+                fn components(&self) -> &$crate::utils::components::ComponentMap<$marker, $component> {
+                    &self.$field
+                }
+
+                fn components_mut(&mut self) -> &mut $crate::utils::components::ComponentMap<$marker, $component> {
+                    &mut self.$field
+                }
+            }
+        )*
+
+        impl $crate::utils::components::Components<$marker> for $name {
+            fn entities(&self) -> &$crate::utils::entity_set::EntitySet<$marker> {
+                &self.entities
+            }
+
+            fn entities_mut(&mut self) -> &mut $crate::utils::entity_set::EntitySet<$marker> {
+                &mut self.entities
+            }
+
+            fn despawn(&mut self, entity: &$crate::utils::entity_set::EntityRef<$marker>) -> bool {
+                $(
+                    self.$field.swap_remove(entity);
+                )*
+                self.entities.remove(entity).is_some()
+            }
+        }
+    };
+}
@@ -1,9 +1,13 @@
+use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::ops::{Deref, DerefMut};
 
 use fxhash::{FxBuildHasher, FxHasher};
 use indexmap::{IndexMap, IndexSet};
-use serde::{Deserialize, Serialize};
+use rand::Rng;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::Error;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CustomMap<K: Eq + Hash, V>(IndexMap<K, V, FxBuildHasher>);
@@ -18,6 +22,134 @@ impl<K: Eq + Hash, V> CustomMap<K, V> {
     pub fn new() -> Self {
         Self(IndexMap::with_hasher(FxBuildHasher::default()))
     }
+
+    pub fn get_or_insert_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        self.0.entry(key).or_default()
+    }
+
+    // Missing values are updated starting from the default.
+    pub fn update_or_insert<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        V: Default,
+        F: FnOnce(&mut V),
+    {
+        let value = self.0.entry(key).or_default();
+        f(value);
+        value
+    }
+
+    pub fn retain_map<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, V) -> Option<V>,
+    {
+        let entries = std::mem::take(&mut self.0);
+        for (key, value) in entries {
+            if let Some(value) = f(&key, value) {
+                self.0.insert(key, value);
+            }
+        }
+    }
+
+    pub fn try_get(&self, key: &K) -> Result<&V, Error>
+    where
+        K: Debug,
+    {
+        self.0
+            .get(key)
+            .ok_or_else(|| Error::MissingKey(format!("{key:?}")))
+    }
+
+    pub fn try_get_mut(&mut self, key: &K) -> Result<&mut V, Error>
+    where
+        K: Debug,
+    {
+        self.0
+            .get_mut(key)
+            .ok_or_else(|| Error::MissingKey(format!("{key:?}")))
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone + PartialEq> CustomMap<K, V> {
+    pub fn diff(&self, other: &Self) -> MapDiff<K, V> {
+        self.diff_by(other, PartialEq::eq)
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> CustomMap<K, V> {
+    pub fn diff_by<F>(&self, other: &Self, mut eq: F) -> MapDiff<K, V>
+    where
+        F: FnMut(&V, &V) -> bool,
+    {
+        let mut diff = MapDiff::default();
+        for (key, value) in other {
+            match self.get(key) {
+                None => {
+                    diff.inserted.insert(key.clone(), value.clone());
+                }
+                Some(old_value) if !eq(old_value, value) => {
+                    diff.updated.insert(key.clone(), value.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        for key in self.keys() {
+            if !other.contains_key(key) {
+                diff.removed.insert(key.clone());
+            }
+        }
+        diff
+    }
+}
+
+impl<K: Eq + Hash, V> CustomMap<K, V> {
+    pub fn apply_diff(&mut self, diff: MapDiff<K, V>) {
+        for key in &diff.removed {
+            self.shift_remove(key);
+        }
+        for (key, value) in diff.updated.into_iter().chain(diff.inserted) {
+            self.insert(key, value);
+        }
+    }
+
+    pub fn merge_with<F>(&mut self, other: Self, mut resolve: F)
+    where
+        F: FnMut(&K, &mut V, V),
+    {
+        for (key, value) in other {
+            match self.get_mut(&key) {
+                Some(existing) => resolve(&key, existing, value),
+                None => {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MapDiff<K: Eq + Hash, V> {
+    pub inserted: CustomMap<K, V>,
+    pub updated: CustomMap<K, V>,
+    pub removed: CustomSet<K>,
+}
+
+impl<K: Eq + Hash, V> Default for MapDiff<K, V> {
+    fn default() -> Self {
+        MapDiff {
+            inserted: CustomMap::new(),
+            updated: CustomMap::new(),
+            removed: CustomSet::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> MapDiff<K, V> {
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
 }
 
 impl<K: Eq + Hash, V> Deref for CustomMap<K, V> {
@@ -80,13 +212,71 @@ impl<K: Eq + Hash, V: Hash> FromIterator<(K, V)> for CustomMap<K, V> {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CustomSet<T: Eq + Hash>(IndexSet<T, FxBuildHasher>);
 
+impl<T: Eq + Hash> Default for CustomSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Eq + Hash> CustomSet<T> {
     pub fn new() -> Self {
         Self(IndexSet::with_hasher(FxBuildHasher::default()))
     }
+
+    pub fn union(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.0.union(&other.0).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.0.intersection(&other.0).cloned().collect()
+    }
+
+    pub fn difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.0.difference(&other.0).cloned().collect()
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        self.0.symmetric_difference(&other.0).cloned().collect()
+    }
+
+    pub fn sorted_iter(&self) -> impl Iterator<Item = &T>
+    where
+        T: Ord,
+    {
+        let mut values: Vec<&T> = self.0.iter().collect();
+        values.sort_unstable();
+        values.into_iter()
+    }
+
+    pub fn choose_random(&self, rng: &mut impl Rng) -> Option<&T> {
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0.get_index(rng.gen_range(0..self.0.len()))
+    }
+
+    pub fn pop_random(&mut self, rng: &mut impl Rng) -> Option<T> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let index = rng.gen_range(0..self.0.len());
+        self.0.swap_remove_index(index)
+    }
 }
 
 impl<T: Eq + Hash> Deref for CustomSet<T> {
@@ -147,3 +337,91 @@ impl<T: Eq + Hash> FromIterator<T> for CustomSet<T> {
         set
     }
 }
+
+pub trait SortedSerialize {
+    fn serialize_sorted<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    fn sort(&mut self);
+}
+
+impl<K: Eq + Hash + Ord + Serialize, V: Serialize> SortedSerialize for CustomMap<K, V> {
+    fn serialize_sorted<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        serializer.collect_map(entries)
+    }
+
+    fn sort(&mut self) {
+        self.sort_keys();
+    }
+}
+
+impl<T: Eq + Hash + Ord + Serialize> SortedSerialize for CustomSet<T> {
+    fn serialize_sorted<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut values: Vec<&T> = self.iter().collect();
+        values.sort_unstable();
+        serializer.collect_seq(values)
+    }
+
+    fn sort(&mut self) {
+        self.0.sort();
+    }
+}
+
+// Use with `#[serde(with = "engine_shared::utils::custom_map::sorted")]` to
+// serialize a map or set in key order, independent of insertion order.
+pub mod sorted {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::SortedSerialize;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: SortedSerialize,
+        S: Serializer,
+    {
+        value.serialize_sorted(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: SortedSerialize + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let mut value = T::deserialize(deserializer)?;
+        value.sort();
+        Ok(value)
+    }
+}
+
+// Use with `#[serde(with = "engine_shared::utils::custom_map::pairs")]` to
+// serialize a map as a sequence of `(key, value)` pairs, which keeps formats
+// like JSON working for maps with non-string keys.
+pub mod pairs {
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::CustomMap;
+
+    pub fn serialize<K, V, S>(map: &CustomMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Eq + Hash + Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<CustomMap<K, V>, D::Error>
+    where
+        K: Eq + Hash + Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let mut map = CustomMap::new();
+        for (key, value) in Vec::<(K, V)>::deserialize(deserializer)? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
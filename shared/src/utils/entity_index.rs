@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::{fmt, hash::Hash, marker::PhantomData, ops::Deref};
+
+use rand::Rng;
+
+use super::{
+    custom_map::{CustomMap, CustomSet},
+    entity_set::{EntityRef, EntitySet},
+};
+
+pub trait IndexKey<T> {
+    type Key: Eq + Hash + Clone;
+
+    // All keys the entity can be found by. For several indexes, use an enum
+    // with a variant per index, e.g. `Owner(UserId)` and `Tile(i32, i32)`.
+    fn keys(entity: &T) -> Vec<Self::Key>;
+}
+
+// Serializes exactly like the underlying `EntitySet`, the index is rebuilt
+// when deserializing.
+#[derive(Deserialize)]
+#[serde(from = "EntitySet<T>", bound(deserialize = "T: Deserialize<'de>"))]
+pub struct IndexedEntitySet<T: Hash, I: IndexKey<T>> {
+    set: EntitySet<T>,
+    index: CustomMap<I::Key, CustomSet<EntityRef<T>>>,
+    _phantom: PhantomData<fn() -> I>,
+}
+
+impl<T: Hash, I: IndexKey<T>> Default for IndexedEntitySet<T, I> {
+    fn default() -> Self {
+        Self {
+            set: EntitySet::default(),
+            index: CustomMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash + Clone, I: IndexKey<T>> Clone for IndexedEntitySet<T, I> {
+    fn clone(&self) -> Self {
+        Self {
+            set: self.set.clone(),
+            index: self.index.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash + fmt::Debug, I: IndexKey<T>> fmt::Debug for IndexedEntitySet<T, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.set.fmt(f)
+    }
+}
+
+impl<T: Hash, I: IndexKey<T>> Hash for IndexedEntitySet<T, I> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.set.hash(state);
+    }
+}
+
+impl<T: Hash + Serialize, I: IndexKey<T>> Serialize for IndexedEntitySet<T, I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.set.serialize(serializer)
+    }
+}
+
+impl<T: Hash, I: IndexKey<T>> From<EntitySet<T>> for IndexedEntitySet<T, I> {
+    fn from(set: EntitySet<T>) -> Self {
+        let mut index: CustomMap<I::Key, CustomSet<EntityRef<T>>> = CustomMap::new();
+        for (&entity_ref, entity) in &set {
+            for key in I::keys(entity) {
+                index.get_or_insert_default(key).insert(entity_ref);
+            }
+        }
+        Self {
+            set,
+            index,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Hash, I: IndexKey<T>> From<IndexedEntitySet<T, I>> for EntitySet<T> {
+    fn from(indexed: IndexedEntitySet<T, I>) -> Self {
+        indexed.set
+    }
+}
+
+impl<T: Hash, I: IndexKey<T>> Deref for IndexedEntitySet<T, I> {
+    type Target = EntitySet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.set
+    }
+}
+
+impl<T: Hash, I: IndexKey<T>> IndexedEntitySet<T, I> {
+    pub fn insert_with(&mut self, rng: &mut impl Rng, entity: T) -> EntityRef<T> {
+        let keys = I::keys(&entity);
+        let entity_ref = self.set.insert_with(rng, entity);
+        self.index_all(keys, entity_ref);
+        entity_ref
+    }
+
+    pub fn remove(&mut self, entity_ref: &EntityRef<T>) -> Option<T> {
+        let entity = self.set.remove(entity_ref)?;
+        for key in I::keys(&entity) {
+            self.unindex(&key, entity_ref);
+        }
+        Some(entity)
+    }
+
+    pub fn modify<F, R>(&mut self, entity_ref: &EntityRef<T>, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let entity = self.set.get_mut(entity_ref)?;
+        let old_keys = I::keys(entity);
+        let result = f(entity);
+        let new_keys = I::keys(entity);
+        if old_keys != new_keys {
+            for key in &old_keys {
+                self.unindex(key, entity_ref);
+            }
+            self.index_all(new_keys, *entity_ref);
+        }
+        Some(result)
+    }
+
+    pub fn find_by_index<'a>(
+        &'a self,
+        key: &I::Key,
+    ) -> impl Iterator<Item = (&'a EntityRef<T>, &'a T)> + 'a {
+        self.index
+            .get(key)
+            .into_iter()
+            .flatten()
+            .flat_map(move |entity_ref| self.set.get(entity_ref).map(|entity| (entity_ref, entity)))
+    }
+
+    pub fn count_by_index(&self, key: &I::Key) -> usize {
+        self.index
+            .get(key)
+            .map(|refs| refs.len())
+            .unwrap_or_default()
+    }
+
+    fn index_all(&mut self, keys: Vec<I::Key>, entity_ref: EntityRef<T>) {
+        for key in keys {
+            self.index.get_or_insert_default(key).insert(entity_ref);
+        }
+    }
+
+    fn unindex(&mut self, key: &I::Key, entity_ref: &EntityRef<T>) {
+        if let Some(refs) = self.index.get_mut(key) {
+            refs.swap_remove(entity_ref);
+            if refs.is_empty() {
+                self.index.swap_remove(key);
+            }
+        }
+    }
+}
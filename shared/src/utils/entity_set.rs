@@ -1,32 +1,169 @@
-use serde::{Deserialize, Serialize};
-use std::{hash::Hash, marker::PhantomData, str::FromStr};
+use rand::Rng;
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{cmp::Ordering, fmt, hash::Hash, marker::PhantomData, str::FromStr};
+#[cfg(not(feature = "generational-ids"))]
+use uuid::Builder;
 use uuid::Uuid;
 
 use super::custom_map::{CustomMap, CustomSet};
 
+#[cfg(not(feature = "generational-ids"))]
+type EntityId = Uuid;
+#[cfg(feature = "generational-ids")]
+type EntityId = GenerationalOrUuid;
+
 #[derive(Debug, Clone, Hash, Serialize, Deserialize)]
-pub struct EntitySet<T: Hash> {
+pub struct EntitySet<T: Hash, G: Eq + Hash = ()> {
     entities: CustomMap<EntityRef<T>, T>,
+    // Left out while empty, so sets without tags serialize like before. With
+    // generational ids, the allocator comes after it, so it's always written.
+    #[serde(default = "CustomMap::new")]
+    #[cfg_attr(
+        not(feature = "generational-ids"),
+        serde(skip_serializing_if = "is_untagged")
+    )]
+    tags: CustomMap<G, CustomSet<EntityRef<T>>>,
+    #[cfg(feature = "generational-ids")]
+    #[serde(default)]
+    ids: IdAllocator,
+    // Skips the allocator of states saved with generational ids.
+    #[cfg(not(feature = "generational-ids"))]
+    #[serde(default, skip_serializing)]
+    ids: IgnoredIds,
+}
+
+#[cfg(not(feature = "generational-ids"))]
+fn is_untagged<T, G: Eq + Hash>(tags: &CustomMap<G, CustomSet<EntityRef<T>>>) -> bool {
+    tags.is_empty()
+}
+
+#[cfg(not(feature = "generational-ids"))]
+#[derive(Debug, Clone, Default, Hash)]
+struct IgnoredIds;
+
+#[cfg(not(feature = "generational-ids"))]
+impl<'de> Deserialize<'de> for IgnoredIds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        de::IgnoredAny::deserialize(deserializer)?;
+        Ok(IgnoredIds)
+    }
 }
 
-impl<T: Hash> Default for EntitySet<T> {
+impl<T: Hash, G: Eq + Hash> Default for EntitySet<T, G> {
     fn default() -> Self {
         Self {
             entities: CustomMap::new(),
+            tags: CustomMap::new(),
+            ids: Default::default(),
         }
     }
 }
 
-impl<T: Hash> EntitySet<T> {
+impl<T: Hash, G: Eq + Hash> EntitySet<T, G> {
+    #[deprecated(note = "random ids differ between client and server, use `insert_with` instead")]
     pub fn insert(&mut self, entity: T) -> EntityRef<T>
     where
         EntityRef<T>: Copy,
     {
+        #[cfg(not(feature = "generational-ids"))]
         let entity_ref = EntityRef::new();
+        #[cfg(feature = "generational-ids")]
+        let entity_ref = EntityRef(EntityId::Generational(self.ids.allocate()), PhantomData);
+        self.entities.insert(entity_ref, entity);
+        entity_ref
+    }
+
+    pub fn insert_with(&mut self, rng: &mut impl Rng, entity: T) -> EntityRef<T>
+    where
+        EntityRef<T>: Copy,
+    {
+        let entity_ref = self.next_ref(rng);
         self.entities.insert(entity_ref, entity);
         entity_ref
     }
 
+    #[cfg(not(feature = "generational-ids"))]
+    fn next_ref(&mut self, rng: &mut impl Rng) -> EntityRef<T> {
+        let mut entity_ref = EntityRef::from_rng(rng);
+        while self.entities.contains_key(&entity_ref) {
+            entity_ref = EntityRef::from_rng(rng);
+        }
+        entity_ref
+    }
+
+    #[cfg(feature = "generational-ids")]
+    fn next_ref(&mut self, _rng: &mut impl Rng) -> EntityRef<T> {
+        EntityRef(EntityId::Generational(self.ids.allocate()), PhantomData)
+    }
+
+    fn release(&mut self, entity_ref: &EntityRef<T>) {
+        self.tags.retain(|_, tagged| {
+            tagged.swap_remove(entity_ref);
+            !tagged.is_empty()
+        });
+        #[cfg(feature = "generational-ids")]
+        if let EntityId::Generational(id) = entity_ref.0 {
+            self.ids.release(id);
+        }
+    }
+
+    pub fn tag(&mut self, entity_ref: &EntityRef<T>, tag: G) -> bool {
+        if !self.entities.contains_key(entity_ref) {
+            return false;
+        }
+        self.tags.get_or_insert_default(tag).insert(*entity_ref)
+    }
+
+    pub fn untag(&mut self, entity_ref: &EntityRef<T>, tag: &G) -> bool {
+        let Some(tagged) = self.tags.get_mut(tag) else {
+            return false;
+        };
+        let removed = tagged.swap_remove(entity_ref);
+        if tagged.is_empty() {
+            self.tags.swap_remove(tag);
+        }
+        removed
+    }
+
+    pub fn has_tag(&self, entity_ref: &EntityRef<T>, tag: &G) -> bool {
+        self.tags
+            .get(tag)
+            .map(|tagged| tagged.contains(entity_ref))
+            .unwrap_or_default()
+    }
+
+    pub fn tags_of<'a>(&'a self, entity_ref: &'a EntityRef<T>) -> impl Iterator<Item = &'a G> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(_, tagged)| tagged.contains(entity_ref))
+            .map(|(tag, _)| tag)
+    }
+
+    pub fn count_tagged(&self, tag: &G) -> usize {
+        self.tags
+            .get(tag)
+            .map(|tagged| tagged.len())
+            .unwrap_or_default()
+    }
+
+    pub fn iter_tagged<'a>(
+        &'a self,
+        tag: &G,
+    ) -> impl Iterator<Item = (&'a EntityRef<T>, &'a T)> + 'a {
+        self.tags
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .flat_map(move |entity_ref| {
+                self.entities
+                    .get(entity_ref)
+                    .map(|entity| (entity_ref, entity))
+            })
+    }
+
     pub fn get(&self, entity_ref: &EntityRef<T>) -> Option<&T> {
         self.entities.get(entity_ref)
     }
@@ -36,7 +173,81 @@ impl<T: Hash> EntitySet<T> {
     }
 
     pub fn remove(&mut self, entity_ref: &EntityRef<T>) -> Option<T> {
-        self.entities.swap_remove(entity_ref)
+        let entity = self.entities.swap_remove(entity_ref)?;
+        self.release(entity_ref);
+        Some(entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn extend<I>(&mut self, rng: &mut impl Rng, entities: I) -> Vec<EntityRef<T>>
+    where
+        I: IntoIterator<Item = T>,
+        EntityRef<T>: Copy,
+    {
+        entities
+            .into_iter()
+            .map(|entity| self.insert_with(rng, entity))
+            .collect()
+    }
+
+    pub fn remove_many(&mut self, entity_refs: &[EntityRef<T>]) -> Vec<T> {
+        entity_refs
+            .iter()
+            .flat_map(|entity_ref| self.remove(entity_ref))
+            .collect()
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&EntityRef<T>, &mut T) -> bool,
+        EntityRef<T>: Copy,
+    {
+        let mut removed = Vec::new();
+        self.entities.retain(|entity_ref, entity| {
+            let keep = f(entity_ref, entity);
+            if !keep {
+                removed.push(*entity_ref);
+            }
+            keep
+        });
+        for entity_ref in &removed {
+            self.release(entity_ref);
+        }
+    }
+
+    pub fn drain_filter<F>(&mut self, mut f: F) -> Vec<(EntityRef<T>, T)>
+    where
+        F: FnMut(&EntityRef<T>, &mut T) -> bool,
+        EntityRef<T>: Copy,
+    {
+        let mut drained = Vec::new();
+        let mut kept = CustomMap::new();
+        for (entity_ref, mut entity) in std::mem::take(&mut self.entities) {
+            if f(&entity_ref, &mut entity) {
+                drained.push((entity_ref, entity));
+            } else {
+                kept.insert(entity_ref, entity);
+            }
+        }
+        self.entities = kept;
+        for (entity_ref, _) in &drained {
+            self.release(entity_ref);
+        }
+        drained
+    }
+
+    pub fn clear(&mut self)
+    where
+        EntityRef<T>: Copy,
+    {
+        self.retain(|_, _| false);
     }
 
     pub fn for_each_mut<F>(&mut self, mut f: F)
@@ -52,7 +263,10 @@ impl<T: Hash> EntitySet<T> {
         }
 
         self.entities
-            .retain(|entity_ref, _| !to_remove.contains(entity_ref))
+            .retain(|entity_ref, _| !to_remove.contains(entity_ref));
+        for entity_ref in &to_remove {
+            self.release(entity_ref);
+        }
     }
 
     pub fn for_each_in_mut<F>(&mut self, set: &mut EntityRefSet<T>, mut f: F)
@@ -75,19 +289,81 @@ impl<T: Hash> EntitySet<T> {
             .retain(|entity_ref, _| !to_remove.contains(entity_ref));
         set.entities
             .retain(|entity_ref| !to_remove.contains(entity_ref));
+        for entity_ref in &to_remove {
+            self.release(entity_ref);
+        }
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&EntityRef<T>, &T)> + 'a
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a EntityRef<T>, &'a T)> + 'a
     where
         EntityRef<T>: Copy,
     {
         self.entities.as_slice().iter()
     }
 
+    pub fn query<'a, F>(&'a self, mut f: F) -> impl Iterator<Item = (&'a EntityRef<T>, &'a T)> + 'a
+    where
+        F: FnMut(&T) -> bool + 'a,
+        EntityRef<T>: Copy,
+    {
+        self.iter().filter(move |(_, entity)| f(entity))
+    }
+
+    pub fn iter_sorted_by<F>(&self, mut compare: F) -> impl Iterator<Item = (&EntityRef<T>, &T)>
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        EntityRef<T>: Copy,
+    {
+        let mut entities: Vec<_> = self.iter().collect();
+        entities.sort_by(|(a_ref, a), (b_ref, b)| compare(a, b).then_with(|| a_ref.cmp(b_ref)));
+        entities.into_iter()
+    }
+
+    pub fn iter_sorted_by_key<K, F>(&self, mut f: F) -> impl Iterator<Item = (&EntityRef<T>, &T)>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+        EntityRef<T>: Copy,
+    {
+        self.iter_sorted_by(move |a, b| f(a).cmp(&f(b)))
+    }
+
+    pub fn page(&self, offset: usize, len: usize) -> impl Iterator<Item = (&EntityRef<T>, &T)>
+    where
+        EntityRef<T>: Copy,
+    {
+        self.iter().skip(offset).take(len)
+    }
+
+    pub fn page_sorted_by<F>(
+        &self,
+        offset: usize,
+        len: usize,
+        mut compare: F,
+    ) -> impl Iterator<Item = (&EntityRef<T>, &T)>
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        EntityRef<T>: Copy,
+    {
+        let mut compare = move |(a_ref, a): &(&EntityRef<T>, &T),
+                                (b_ref, b): &(&EntityRef<T>, &T)| {
+            compare(a, b).then_with(|| a_ref.cmp(b_ref))
+        };
+
+        let mut entities: Vec<_> = self.iter().collect();
+        let end = offset.saturating_add(len).min(entities.len());
+        if end > 0 && end < entities.len() {
+            entities.select_nth_unstable_by(end - 1, &mut compare);
+        }
+        entities.truncate(end);
+        entities.sort_by(compare);
+        entities.into_iter().skip(offset)
+    }
+
     pub fn iter_in<'a, 'b: 'a>(
         &'a self,
         set: &'b EntityRefSet<T>,
-    ) -> impl Iterator<Item = (&EntityRef<T>, &T)> + 'a
+    ) -> impl Iterator<Item = (&'a EntityRef<T>, &'a T)> + 'a
     where
         EntityRef<T>: Copy,
     {
@@ -99,7 +375,7 @@ impl<T: Hash> EntitySet<T> {
     }
 }
 
-impl<'a, T: Hash> IntoIterator for &'a EntitySet<T> {
+impl<'a, T: Hash, G: Eq + Hash> IntoIterator for &'a EntitySet<T, G> {
     type Item = (&'a EntityRef<T>, &'a T);
 
     type IntoIter = indexmap::map::Iter<'a, EntityRef<T>, T>;
@@ -130,10 +406,93 @@ impl<T: Hash> EntityRefSet<T> {
     pub fn remove(&mut self, entity_ref: &EntityRef<T>) -> bool {
         self.entities.swap_remove(entity_ref)
     }
+
+    pub fn contains(&self, entity_ref: &EntityRef<T>) -> bool {
+        self.entities.contains(entity_ref)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EntityRef<T>> {
+        self.entities.iter()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            entities: self.entities.union(&other.entities),
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            entities: self.entities.intersection(&other.entities),
+        }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        Self {
+            entities: self.entities.difference(&other.entities),
+        }
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        Self {
+            entities: self.entities.symmetric_difference(&other.entities),
+        }
+    }
+}
+
+impl<T: Hash> FromIterator<EntityRef<T>> for EntityRefSet<T> {
+    fn from_iter<I: IntoIterator<Item = EntityRef<T>>>(iter: I) -> Self {
+        Self {
+            entities: iter.into_iter().collect(),
+        }
+    }
 }
 
-#[derive(Debug, Hash, Serialize, Deserialize)]
-pub struct EntityRef<T>(Uuid, PhantomData<T>);
+impl<T: Hash> Extend<EntityRef<T>> for EntityRefSet<T> {
+    fn extend<I: IntoIterator<Item = EntityRef<T>>>(&mut self, iter: I) {
+        self.entities.extend(iter);
+    }
+}
+
+impl<T: Hash> IntoIterator for EntityRefSet<T> {
+    type Item = EntityRef<T>;
+
+    type IntoIter = indexmap::set::IntoIter<EntityRef<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entities.into_iter()
+    }
+}
+
+impl<'a, T: Hash> IntoIterator for &'a EntityRefSet<T> {
+    type Item = &'a EntityRef<T>;
+
+    type IntoIter = indexmap::set::Iter<'a, EntityRef<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entities.iter()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntityRef<T>(
+    #[serde(deserialize_with = "deserialize_id")] EntityId,
+    PhantomData<T>,
+);
+
+impl<T> Hash for EntityRef<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
 
 impl<T> PartialEq for EntityRef<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -145,7 +504,7 @@ impl<T> Eq for EntityRef<T> {}
 
 impl<T> PartialOrd for EntityRef<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
@@ -155,15 +514,23 @@ impl<T> Ord for EntityRef<T> {
     }
 }
 
+#[cfg(not(feature = "generational-ids"))]
 impl<T> EntityRef<T> {
     fn new() -> Self {
-        EntityRef(Uuid::new_v4(), PhantomData::default())
+        EntityRef(Uuid::new_v4(), PhantomData)
+    }
+
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
+        EntityRef(
+            Builder::from_random_bytes(rng.gen()).into_uuid(),
+            PhantomData,
+        )
     }
 }
 
 impl<T> Clone for EntityRef<T> {
     fn clone(&self) -> Self {
-        Self(self.0.clone(), self.1.clone())
+        *self
     }
 }
 
@@ -174,14 +541,189 @@ impl<T> FromStr for EntityRef<T> {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(EntityRef(
-            Uuid::from_str(s).map_err(|_| ())?,
-            PhantomData::default(),
+            EntityId::from_str(s).map_err(|_| ())?,
+            PhantomData,
         ))
     }
 }
 
-impl<T> ToString for EntityRef<T> {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl<T> fmt::Display for EntityRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub struct GenerationalId {
+    pub index: u32,
+    pub generation: u32,
+}
+
+#[cfg(feature = "generational-ids")]
+impl From<u64> for GenerationalId {
+    fn from(value: u64) -> Self {
+        GenerationalId {
+            index: value as u32,
+            generation: (value >> 32) as u32,
+        }
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+impl From<GenerationalId> for u64 {
+    fn from(id: GenerationalId) -> Self {
+        (id.generation as u64) << 32 | id.index as u64
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+impl FromStr for GenerationalId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(GenerationalId::from(u64::from_str(s)?))
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+impl fmt::Display for GenerationalId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", u64::from(*self))
+    }
+}
+
+// Ids of entities that were inserted without generational ids stay UUIDs.
+#[cfg(feature = "generational-ids")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GenerationalOrUuid {
+    Generational(GenerationalId),
+    Uuid(Uuid),
+}
+
+#[cfg(feature = "generational-ids")]
+impl Serialize for GenerationalOrUuid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            GenerationalOrUuid::Generational(id) => serializer.serialize_u64(u64::from(*id)),
+            GenerationalOrUuid::Uuid(uuid) => uuid.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+impl FromStr for GenerationalOrUuid {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match u64::from_str(s) {
+            Ok(value) => Ok(id_from_u64(value)),
+            Err(_) => Uuid::from_str(s).map(id_from_uuid),
+        }
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+impl fmt::Display for GenerationalOrUuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationalOrUuid::Generational(id) => write!(f, "{id}"),
+            GenerationalOrUuid::Uuid(uuid) => write!(f, "{uuid}"),
+        }
+    }
+}
+
+// Generational ids become UUIDs with the id in the lower half, which random
+// UUIDs never have, and turn back into generational ids with the feature. So
+// states can be loaded after switching the feature either way.
+#[cfg(feature = "generational-ids")]
+fn id_from_u64(value: u64) -> EntityId {
+    GenerationalOrUuid::Generational(GenerationalId::from(value))
+}
+
+#[cfg(feature = "generational-ids")]
+fn id_from_uuid(uuid: Uuid) -> EntityId {
+    match uuid.as_u64_pair() {
+        (0, value) => id_from_u64(value),
+        _ => GenerationalOrUuid::Uuid(uuid),
+    }
+}
+
+#[cfg(not(feature = "generational-ids"))]
+fn id_from_u64(value: u64) -> EntityId {
+    Uuid::from_u64_pair(0, value)
+}
+
+#[cfg(not(feature = "generational-ids"))]
+fn id_from_uuid(uuid: Uuid) -> EntityId {
+    uuid
+}
+
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<EntityId, D::Error> {
+    deserializer.deserialize_any(IdVisitor)
+}
+
+struct IdVisitor;
+
+impl<'de> Visitor<'de> for IdVisitor {
+    type Value = EntityId;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a UUID or a generational id")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<EntityId, E> {
+        Ok(id_from_u64(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<EntityId, E> {
+        match u64::from_str(value) {
+            Ok(value) => Ok(id_from_u64(value)),
+            Err(_) => Uuid::from_str(value)
+                .map(id_from_uuid)
+                .map_err(|_| E::invalid_value(Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<EntityId, E> {
+        Uuid::from_slice(value)
+            .map(id_from_uuid)
+            .map_err(|_| E::invalid_value(Unexpected::Bytes(value), &self))
+    }
+}
+
+#[cfg(feature = "generational-ids")]
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+struct IdAllocator {
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+#[cfg(feature = "generational-ids")]
+impl IdAllocator {
+    fn allocate(&mut self) -> GenerationalId {
+        match self.free.pop() {
+            Some(index) => GenerationalId {
+                index,
+                generation: self.generations[index as usize],
+            },
+            None => {
+                self.generations.push(0);
+                GenerationalId {
+                    index: (self.generations.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, id: GenerationalId) {
+        if let Some(generation) = self.generations.get_mut(id.index as usize) {
+            if *generation == id.generation {
+                *generation = generation.wrapping_add(1);
+                self.free.push(id.index);
+            }
+        }
     }
 }
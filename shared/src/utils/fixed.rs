@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    hash::Hash,
+    ops::{Add, AddAssign, Div, Mul, Sub, SubAssign},
+};
+
+use super::{custom_map::CustomMap, qty::Qty};
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Fixed(u64);
+
+impl Fixed {
+    pub const SCALE: u64 = 1000;
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(Self::SCALE);
+
+    pub const fn from_units(units: u64) -> Self {
+        Fixed(units.saturating_mul(Self::SCALE))
+    }
+
+    pub const fn from_milli(milli: u64) -> Self {
+        Fixed(milli)
+    }
+
+    // Rounds down, None if the denominator is 0.
+    pub const fn from_ratio(numerator: u64, denominator: u64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let ratio = numerator as u128 * Self::SCALE as u128 / denominator as u128;
+        Some(Fixed(if ratio > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            ratio as u64
+        }))
+    }
+
+    pub const fn milli(self) -> u64 {
+        self.0
+    }
+
+    pub const fn units(self) -> u64 {
+        self.0 / Self::SCALE
+    }
+
+    pub const fn fraction(self) -> Fixed {
+        Fixed(self.0 % Self::SCALE)
+    }
+
+    pub fn checked_sub(self, rhs: Fixed) -> Option<Fixed> {
+        self.0.checked_sub(rhs.0).map(Fixed)
+    }
+
+    pub fn saturating_sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:03}", self.units(), self.fraction().0)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) {
+        self.0 = self.0.saturating_add(rhs.0);
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        self.saturating_sub(rhs)
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) {
+        *self = self.saturating_sub(rhs);
+    }
+}
+
+// Rounds down to the nearest milli-unit. Like the other operations, it
+// saturates instead of overflowing.
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: Fixed) -> Fixed {
+        let product = self.0 as u128 * rhs.0 as u128 / Self::SCALE as u128;
+        Fixed(u64::try_from(product).unwrap_or(u64::MAX))
+    }
+}
+
+impl Mul<u64> for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, rhs: u64) -> Fixed {
+        Fixed(self.0.saturating_mul(rhs))
+    }
+}
+
+// Panics if the divisor is 0, like integer division.
+impl Div<u64> for Fixed {
+    type Output = Fixed;
+
+    fn div(self, rhs: u64) -> Fixed {
+        Fixed(self.0 / rhs)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct FixedQty<T: Hash + Eq>(CustomMap<T, Fixed>);
+
+impl<T: Hash + Eq> Default for FixedQty<T> {
+    fn default() -> Self {
+        FixedQty(CustomMap::new())
+    }
+}
+
+impl<T: Hash + Eq + Copy> FixedQty<T> {
+    pub fn with(mut self, resource: T, num: Fixed) -> Self {
+        *self.0.entry(resource).or_default() += num;
+        self
+    }
+
+    pub fn add(&mut self, resource: T, num: Fixed) {
+        *self.0.entry(resource).or_default() += num;
+    }
+
+    pub fn get(&self, resource: &T) -> Fixed {
+        self.0.get(resource).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (T, Fixed)> + '_ {
+        self.0
+            .iter()
+            .filter(|(_, &num)| num > Fixed::ZERO)
+            .map(|(&resource, &num)| (resource, num))
+    }
+
+    pub fn scale(&self, factor: Fixed) -> Self {
+        FixedQty(
+            self.0
+                .iter()
+                .map(|(&resource, &num)| (resource, num * factor))
+                .collect(),
+        )
+    }
+
+    pub fn settle(&mut self, into: &mut Qty<T>) {
+        for (resource, num) in self.0.iter_mut() {
+            into.add(*resource, num.units());
+            *num = num.fraction();
+        }
+        self.0.retain(|_, num| *num > Fixed::ZERO);
+    }
+}
+
+impl<T: Hash + Eq + Copy> From<Qty<T>> for FixedQty<T> {
+    fn from(qty: Qty<T>) -> Self {
+        FixedQty(
+            qty.iter()
+                .map(|(resource, num)| (resource, Fixed::from_units(num)))
+                .collect(),
+        )
+    }
+}
+
+impl<T: Hash + Eq + Copy> AddAssign for FixedQty<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        for (resource, num) in rhs.0 {
+            *self.0.entry(resource).or_default() += num;
+        }
+    }
+}
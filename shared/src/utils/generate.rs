@@ -0,0 +1,224 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::grid::{Coord, Grid, Square};
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+pub struct NameGenerator {
+    prefixes: Vec<String>,
+    middles: Vec<String>,
+    suffixes: Vec<String>,
+    max_middles: usize,
+}
+
+impl NameGenerator {
+    pub fn new<S: Into<String>>(
+        prefixes: impl IntoIterator<Item = S>,
+        middles: impl IntoIterator<Item = S>,
+        suffixes: impl IntoIterator<Item = S>,
+    ) -> Self {
+        NameGenerator {
+            prefixes: prefixes.into_iter().map(Into::into).collect(),
+            middles: middles.into_iter().map(Into::into).collect(),
+            suffixes: suffixes.into_iter().map(Into::into).collect(),
+            max_middles: 1,
+        }
+    }
+
+    pub fn with_max_middles(mut self, max_middles: usize) -> Self {
+        self.max_middles = max_middles;
+        self
+    }
+
+    pub fn generate(&self, rng: &mut impl Rng) -> String {
+        let mut name = String::new();
+        if !self.prefixes.is_empty() {
+            name.push_str(&self.prefixes[rng.gen_range(0..self.prefixes.len())]);
+        }
+        if !self.middles.is_empty() {
+            for _ in 0..rng.gen_range(0..=self.max_middles) {
+                name.push_str(&self.middles[rng.gen_range(0..self.middles.len())]);
+            }
+        }
+        if !self.suffixes.is_empty() {
+            name.push_str(&self.suffixes[rng.gen_range(0..self.suffixes.len())]);
+        }
+
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+pub struct LootTable<T> {
+    entries: Vec<(T, u32)>,
+    total_weight: u64,
+}
+
+impl<T> Default for LootTable<T> {
+    fn default() -> Self {
+        LootTable {
+            entries: Vec::new(),
+            total_weight: 0,
+        }
+    }
+}
+
+impl<T> LootTable<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, item: T, weight: u32) -> Self {
+        self.add(item, weight);
+        self
+    }
+
+    pub fn add(&mut self, item: T, weight: u32) {
+        if weight > 0 {
+            self.total_weight += weight as u64;
+            self.entries.push((item, weight));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.total_weight
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<&T> {
+        if self.total_weight == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..self.total_weight);
+        for (item, weight) in &self.entries {
+            let weight = *weight as u64;
+            if roll < weight {
+                return Some(item);
+            }
+            roll -= weight;
+        }
+        unreachable!("roll is below the total weight")
+    }
+
+    pub fn roll_many(&self, rng: &mut impl Rng, count: usize) -> Vec<&T> {
+        (0..count).filter_map(|_| self.roll(rng)).collect()
+    }
+}
+
+impl<T> FromIterator<(T, u32)> for LootTable<T> {
+    fn from_iter<I: IntoIterator<Item = (T, u32)>>(iter: I) -> Self {
+        let mut table = LootTable::new();
+        for (item, weight) in iter {
+            table.add(item, weight);
+        }
+        table
+    }
+}
+
+// Value noise over hashed lattice points, which doesn't depend on any platform
+// specific float functions, so a seed generates the same map everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Noise { seed }
+    }
+
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
+        Noise { seed: rng.gen() }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn lattice(&self, x: i64, y: i64) -> f64 {
+        let hash = mix(mix(self.seed ^ x as u64) ^ y as u64);
+        (hash >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = lerp(self.lattice(x0, y0), self.lattice(x0 + 1, y0), tx);
+        let bottom = lerp(self.lattice(x0, y0 + 1), self.lattice(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+
+    pub fn fractal(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        let mut value = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total = 0.0;
+        for octave in 0..octaves.max(1) {
+            let layer = Noise::new(mix(self.seed.wrapping_add(octave as u64)));
+            value += layer.get(x * frequency, y * frequency) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        value / total
+    }
+
+    pub fn map<C, T, F>(
+        &self,
+        coords: impl IntoIterator<Item = C>,
+        scale: f64,
+        octaves: u32,
+        mut f: F,
+    ) -> Grid<C, T>
+    where
+        C: Coord + Into<(f64, f64)>,
+        F: FnMut(C, f64) -> T,
+    {
+        Grid::from_fn(coords, |coord| {
+            let (x, y) = coord.into();
+            f(coord, self.fractal(x / scale, y / scale, octaves))
+        })
+    }
+
+    pub fn square_map<T, F>(
+        &self,
+        width: i32,
+        height: i32,
+        scale: f64,
+        octaves: u32,
+        f: F,
+    ) -> Grid<Square, T>
+    where
+        F: FnMut(Square, f64) -> T,
+    {
+        self.map(Square::rect(width, height), scale, octaves, f)
+    }
+}
+
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
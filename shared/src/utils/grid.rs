@@ -0,0 +1,335 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt,
+    hash::Hash,
+};
+
+use super::custom_map::{pairs, CustomMap};
+
+pub trait Coord: Copy + Eq + Ord + Hash {
+    fn neighbors(self) -> Vec<Self>;
+
+    fn distance(self, other: Self) -> u32;
+
+    fn line_to(self, other: Self) -> Vec<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Square {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Square {
+    pub fn new(x: i32, y: i32) -> Self {
+        Square { x, y }
+    }
+
+    pub fn rect(width: i32, height: i32) -> impl Iterator<Item = Square> {
+        (0..height).flat_map(move |y| (0..width).map(move |x| Square { x, y }))
+    }
+}
+
+impl Coord for Square {
+    fn neighbors(self) -> Vec<Self> {
+        vec![
+            Square::new(self.x + 1, self.y),
+            Square::new(self.x, self.y + 1),
+            Square::new(self.x - 1, self.y),
+            Square::new(self.x, self.y - 1),
+        ]
+    }
+
+    fn distance(self, other: Self) -> u32 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    fn line_to(self, other: Self) -> Vec<Self> {
+        let dx = (other.x - self.x).abs();
+        let dy = -(other.y - self.y).abs();
+        let sx = (other.x - self.x).signum();
+        let sy = (other.y - self.y).signum();
+        let mut error = dx + dy;
+        let mut current = self;
+        let mut line = vec![current];
+        while current != other {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                current.x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                current.y += sy;
+            }
+            line.push(current);
+        }
+        line
+    }
+}
+
+impl From<Square> for (f64, f64) {
+    fn from(square: Square) -> Self {
+        (square.x as f64, square.y as f64)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+// Axial coordinates, the third cube coordinate is `-q - r`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl Hex {
+    pub fn new(q: i32, r: i32) -> Self {
+        Hex { q, r }
+    }
+
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+
+    pub fn range(self, radius: i32) -> impl Iterator<Item = Hex> {
+        (-radius..=radius).flat_map(move |dq| {
+            let from = (-radius).max(-dq - radius);
+            let to = radius.min(-dq + radius);
+            (from..=to).map(move |dr| Hex::new(self.q + dq, self.r + dr))
+        })
+    }
+
+    fn round(q: f64, r: f64) -> Hex {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        Hex::new(rq as i32, rr as i32)
+    }
+}
+
+impl Coord for Hex {
+    fn neighbors(self) -> Vec<Self> {
+        vec![
+            Hex::new(self.q + 1, self.r),
+            Hex::new(self.q + 1, self.r - 1),
+            Hex::new(self.q, self.r - 1),
+            Hex::new(self.q - 1, self.r),
+            Hex::new(self.q - 1, self.r + 1),
+            Hex::new(self.q, self.r + 1),
+        ]
+    }
+
+    fn distance(self, other: Self) -> u32 {
+        (self.q.abs_diff(other.q) + self.r.abs_diff(other.r) + self.s().abs_diff(other.s())) / 2
+    }
+
+    fn line_to(self, other: Self) -> Vec<Self> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+        // Nudge the end points so lines along cell edges round consistently.
+        let (q0, r0) = (self.q as f64 + 1e-6, self.r as f64 + 1e-6);
+        let (q1, r1) = (other.q as f64 + 1e-6, other.r as f64 + 1e-6);
+        (0..=steps)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                Hex::round(q0 + (q1 - q0) * t, r0 + (r1 - r0) * t)
+            })
+            .collect()
+    }
+}
+
+impl From<Hex> for (f64, f64) {
+    fn from(hex: Hex) -> Self {
+        (
+            hex.q as f64 + hex.r as f64 / 2.0,
+            hex.r as f64 * 3f64.sqrt() / 2.0,
+        )
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.q, self.r)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Path<C> {
+    pub steps: Vec<C>,
+    pub cost: u32,
+}
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "C: Serialize, T: Serialize",
+    deserialize = "C: Deserialize<'de>, T: Deserialize<'de>"
+))]
+pub struct Grid<C: Coord, T> {
+    #[serde(with = "pairs")]
+    cells: CustomMap<C, T>,
+}
+
+pub type SquareGrid<T> = Grid<Square, T>;
+pub type HexGrid<T> = Grid<Hex, T>;
+
+impl<C: Coord, T> Default for Grid<C, T> {
+    fn default() -> Self {
+        Grid {
+            cells: CustomMap::new(),
+        }
+    }
+}
+
+impl<C: Coord, T> Grid<C, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_fn<F>(coords: impl IntoIterator<Item = C>, mut f: F) -> Self
+    where
+        F: FnMut(C) -> T,
+    {
+        coords.into_iter().map(|coord| (coord, f(coord))).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, coord: C) -> bool {
+        self.cells.contains_key(&coord)
+    }
+
+    pub fn get(&self, coord: C) -> Option<&T> {
+        self.cells.get(&coord)
+    }
+
+    pub fn get_mut(&mut self, coord: C) -> Option<&mut T> {
+        self.cells.get_mut(&coord)
+    }
+
+    pub fn insert(&mut self, coord: C, cell: T) -> Option<T> {
+        self.cells.insert(coord, cell)
+    }
+
+    pub fn remove(&mut self, coord: C) -> Option<T> {
+        self.cells.shift_remove(&coord)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (C, &T)> {
+        self.cells.iter().map(|(coord, cell)| (*coord, cell))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (C, &mut T)> {
+        self.cells.iter_mut().map(|(coord, cell)| (*coord, cell))
+    }
+
+    pub fn neighbors(&self, coord: C) -> impl Iterator<Item = (C, &T)> {
+        coord
+            .neighbors()
+            .into_iter()
+            .filter_map(move |neighbor| self.get(neighbor).map(|cell| (neighbor, cell)))
+    }
+
+    // Only the cells between both ends are checked, cells outside of the grid
+    // always block the line.
+    pub fn line_of_sight<F>(&self, from: C, to: C, mut blocks: F) -> bool
+    where
+        F: FnMut(C, &T) -> bool,
+    {
+        let line = from.line_to(to);
+        let Some((_, inner)) = line.split_first() else {
+            return true;
+        };
+        let inner = inner
+            .split_last()
+            .map(|(_, inner)| inner)
+            .unwrap_or_default();
+        inner.iter().all(|&coord| match self.get(coord) {
+            Some(cell) => !blocks(coord, cell),
+            None => false,
+        })
+    }
+
+    // `cost` returns the cost of entering a cell, or `None` if it can't be
+    // entered. Costs are expected to be at least 1, otherwise the distance
+    // heuristic can overestimate and the path isn't guaranteed to be optimal.
+    pub fn find_path<F>(&self, from: C, to: C, mut cost: F) -> Option<Path<C>>
+    where
+        F: FnMut(C, &T) -> Option<u32>,
+    {
+        if !self.contains(from) || !self.contains(to) {
+            return None;
+        }
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<C, C> = HashMap::new();
+        let mut best: HashMap<C, u32> = HashMap::new();
+
+        best.insert(from, 0);
+        open.push(Reverse((from.distance(to), 0, from)));
+
+        while let Some(Reverse((_, spent, current))) = open.pop() {
+            if current == to {
+                let mut steps = vec![current];
+                let mut current = current;
+                while let Some(&previous) = came_from.get(&current) {
+                    steps.push(previous);
+                    current = previous;
+                }
+                steps.reverse();
+                return Some(Path { steps, cost: spent });
+            }
+            if best.get(&current).is_some_and(|&known| known < spent) {
+                continue;
+            }
+
+            for (neighbor, cell) in self.neighbors(current) {
+                let Some(step) = cost(neighbor, cell) else {
+                    continue;
+                };
+                let spent = spent.saturating_add(step);
+                if best.get(&neighbor).is_some_and(|&known| known <= spent) {
+                    continue;
+                }
+                best.insert(neighbor, spent);
+                came_from.insert(neighbor, current);
+                open.push(Reverse((
+                    spent.saturating_add(neighbor.distance(to)),
+                    spent,
+                    neighbor,
+                )));
+            }
+        }
+
+        None
+    }
+}
+
+impl<C: Coord, T> FromIterator<(C, T)> for Grid<C, T> {
+    fn from_iter<I: IntoIterator<Item = (C, T)>>(iter: I) -> Self {
+        let mut grid = Grid::new();
+        for (coord, cell) in iter {
+            grid.insert(coord, cell);
+        }
+        grid
+    }
+}
@@ -1,8 +1,10 @@
 use fxhash::FxHashSet;
+use i18n::{Locale, Localizable, Localized, Number};
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt::{self, Debug},
     hash::Hash,
-    ops::{Add, AddAssign, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 use super::custom_map::CustomMap;
@@ -10,6 +12,129 @@ use super::custom_map::CustomMap;
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct Qty<T: Hash + Eq>(CustomMap<T, u64>);
 
+impl<T: Hash + Eq + Copy + fmt::Display> fmt::Display for Qty<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let formatted = self.display_with(", ", |resource, num| format!("{num} {resource}"));
+        write!(f, "{formatted}")
+    }
+}
+
+impl<T: Hash + Eq + Copy + Localizable> Localizable for &Qty<T> {
+    fn localize_with(self, locales: &[Locale]) -> Localized {
+        let locale = locales.first().copied().unwrap_or_else(i18n::current_locale);
+        let items: Vec<String> = i18n::with_locale(locale, || {
+            self.iter()
+                .map(|(resource, num)| {
+                    format!("{} {}", Number::from(num), resource.localize_with(locales))
+                })
+                .collect()
+        });
+        i18n::join_localized(locale, items)
+    }
+}
+
+impl<T: Hash + Eq + Copy + Localizable> Localizable for Qty<T> {
+    fn localize_with(self, locales: &[Locale]) -> Localized {
+        (&self).localize_with(locales)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct QtyCaps<T: Hash + Eq>(CustomMap<T, u64>);
+
+impl<T: Hash + Eq> Default for QtyCaps<T> {
+    fn default() -> Self {
+        QtyCaps(CustomMap::new())
+    }
+}
+
+impl<T: Hash + Eq + Copy> QtyCaps<T> {
+    pub fn with(mut self, resource: T, cap: u64) -> Self {
+        self.0.insert(resource, cap);
+        self
+    }
+
+    pub fn set(&mut self, resource: T, cap: u64) {
+        self.0.insert(resource, cap);
+    }
+
+    pub fn get(&self, resource: &T) -> Option<u64> {
+        self.0.get(resource).copied()
+    }
+
+    pub fn clamp(&self, qty: &mut Qty<T>) -> Qty<T> {
+        let mut overflow = Qty::default();
+        for (resource, num) in qty.0.iter_mut() {
+            if let Some(&cap) = self.0.get(resource) {
+                if *num > cap {
+                    overflow.0.insert(*resource, *num - cap);
+                    *num = cap;
+                }
+            }
+        }
+        overflow
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct Escrow<T: Hash + Eq> {
+    first: Qty<T>,
+    second: Qty<T>,
+}
+
+impl<T: Hash + Eq> Default for Escrow<T> {
+    fn default() -> Self {
+        Escrow {
+            first: Qty::default(),
+            second: Qty::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Escrow<T> {
+    pub fn deposit_first(&mut self, from: &mut Qty<T>, amount: &Qty<T>) -> Result<(), Missing<T>> {
+        Qty::transfer(from, &mut self.first, amount)
+    }
+
+    pub fn deposit_second(&mut self, from: &mut Qty<T>, amount: &Qty<T>) -> Result<(), Missing<T>> {
+        Qty::transfer(from, &mut self.second, amount)
+    }
+
+    pub fn first(&self) -> &Qty<T> {
+        &self.first
+    }
+
+    pub fn second(&self) -> &Qty<T> {
+        &self.second
+    }
+
+    pub fn commit(self, first: &mut Qty<T>, second: &mut Qty<T>) {
+        *first += self.second;
+        *second += self.first;
+    }
+
+    pub fn refund(self, first: &mut Qty<T>, second: &mut Qty<T>) {
+        *first += self.first;
+        *second += self.second;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Missing<T: Hash + Eq>(pub Qty<T>);
+
+impl<T: Hash + Eq + Debug> fmt::Display for Missing<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing")?;
+        for (i, (resource, num)) in self.0 .0.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{separator}{num} {resource:?}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Hash + Eq + Debug> std::error::Error for Missing<T> {}
+
 impl<T: Hash + Eq> Default for Qty<T> {
     fn default() -> Self {
         Qty(CustomMap::new())
@@ -26,10 +151,147 @@ impl<T: Hash + Eq + Copy> Qty<T> {
         *self.0.entry(resource).or_default() += num;
     }
 
+    pub fn add_clamped(&mut self, resource: T, num: u64, cap: u64) -> u64 {
+        let entry = self.0.entry(resource).or_default();
+        let total = entry.saturating_add(num);
+        *entry = total.min(cap).max(*entry);
+        total - *entry
+    }
+
+    pub fn add_capped(&mut self, other: &Self, caps: &QtyCaps<T>) -> Self {
+        let mut overflow = Qty::default();
+        for (&resource, &num) in other.0.iter() {
+            let lost = match caps.get(&resource) {
+                Some(cap) => self.add_clamped(resource, num, cap),
+                None => {
+                    self.add(resource, num);
+                    0
+                }
+            };
+            if lost > 0 {
+                overflow.0.insert(resource, lost);
+            }
+        }
+        overflow
+    }
+
     pub fn get(&self, resource: &T) -> u64 {
         self.0.get(resource).copied().unwrap_or_default()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (T, u64)> + '_ {
+        self.0
+            .iter()
+            .filter(|(_, &num)| num > 0)
+            .map(|(&resource, &num)| (resource, num))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|&num| num == 0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.values().copied().fold(0, u64::saturating_add)
+    }
+
+    pub fn into_vec(self) -> Vec<(T, u64)> {
+        self.0.into_iter().filter(|&(_, num)| num > 0).collect()
+    }
+
+    pub fn display_with<F>(&self, separator: &str, mut f: F) -> String
+    where
+        F: FnMut(T, u64) -> String,
+    {
+        self.iter()
+            .map(|(resource, num)| f(resource, num))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    pub fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        let mut result = self.clone();
+        for (&resource, &num) in rhs.0.iter() {
+            let entry = result.0.entry(resource).or_default();
+            *entry = entry.checked_sub(num)?;
+        }
+        Some(result)
+    }
+
+    pub fn saturating_sub(&self, rhs: &Self) -> Self {
+        let mut result = self.clone();
+        for (&resource, &num) in rhs.0.iter() {
+            let entry = result.0.entry(resource).or_default();
+            *entry = entry.saturating_sub(num);
+        }
+        result
+    }
+
+    pub fn shortfall(&self, cost: &Self) -> Self {
+        let mut missing = Qty::default();
+        for (&resource, &num) in cost.0.iter() {
+            let available = self.get(&resource);
+            if available < num {
+                missing.0.insert(resource, num - available);
+            }
+        }
+        missing
+    }
+
+    pub fn try_pay(&mut self, cost: &Self) -> Result<(), Missing<T>> {
+        let missing = self.shortfall(cost);
+        if !missing.0.is_empty() {
+            return Err(Missing(missing));
+        }
+        for (&resource, &num) in cost.0.iter() {
+            *self.0.entry(resource).or_default() -= num;
+        }
+        Ok(())
+    }
+
+    // Rounds each amount down.
+    // Lossy for amounts above 2^53, use `scale_ratio` for exact results.
+    pub fn scale(&self, factor: f64) -> Self {
+        Qty(self
+            .0
+            .iter()
+            .map(|(&resource, &num)| (resource, (num as f64 * factor).floor() as u64))
+            .collect())
+    }
+
+    // Rounds each amount down, computed without intermediate overflow. None if
+    // the denominator is 0.
+    pub fn scale_ratio(&self, numerator: u64, denominator: u64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        Some(Qty(self
+            .0
+            .iter()
+            .map(|(&resource, &num)| {
+                let scaled = num as u128 * numerator as u128 / denominator as u128;
+                (resource, scaled.try_into().unwrap_or(u64::MAX))
+            })
+            .collect()))
+    }
+
+    // Rounds each amount down. None if the divisor is 0.
+    pub fn checked_div(&self, rhs: u64) -> Option<Self> {
+        if rhs == 0 {
+            return None;
+        }
+        Some(Qty(self
+            .0
+            .iter()
+            .map(|(&resource, &num)| (resource, num / rhs))
+            .collect()))
+    }
+
+    pub fn transfer(from: &mut Self, to: &mut Self, amount: &Self) -> Result<(), Missing<T>> {
+        from.try_pay(amount)?;
+        *to += amount.clone();
+        Ok(())
+    }
+
     pub fn covers(&self, cost: &Self) -> bool {
         for resource in self
             .0
@@ -109,3 +371,175 @@ impl<T: Hash + Eq + Copy> SubAssign for Qty<T> {
         }
     }
 }
+
+impl<T: Hash + Eq + Copy> Mul<u64> for Qty<T> {
+    type Output = Self;
+
+    fn mul(mut self, rhs: u64) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl<T: Hash + Eq + Copy> MulAssign<u64> for Qty<T> {
+    fn mul_assign(&mut self, rhs: u64) {
+        for num in self.0.values_mut() {
+            *num = num.saturating_mul(rhs);
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Div<u64> for Qty<T> {
+    type Output = Self;
+
+    fn div(mut self, rhs: u64) -> Self::Output {
+        self /= rhs;
+        self
+    }
+}
+
+// Panics if the divisor is 0, like integer division. See `Qty::checked_div`.
+impl<T: Hash + Eq + Copy> DivAssign<u64> for Qty<T> {
+    fn div_assign(&mut self, rhs: u64) {
+        for num in self.0.values_mut() {
+            *num /= rhs;
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct IQty<T: Hash + Eq>(CustomMap<T, i64>);
+
+impl<T: Hash + Eq> Default for IQty<T> {
+    fn default() -> Self {
+        IQty(CustomMap::new())
+    }
+}
+
+impl<T: Hash + Eq + Copy> IQty<T> {
+    pub fn with(mut self, resource: T, num: i64) -> Self {
+        *self.0.entry(resource).or_default() += num;
+        self
+    }
+
+    pub fn add(&mut self, resource: T, num: i64) {
+        *self.0.entry(resource).or_default() += num;
+    }
+
+    pub fn get(&self, resource: &T) -> i64 {
+        self.0.get(resource).copied().unwrap_or_default()
+    }
+
+    pub fn delta(from: &Qty<T>, to: &Qty<T>) -> Self {
+        IQty::from(to.clone()) - IQty::from(from.clone())
+    }
+
+    pub fn is_non_negative(&self) -> bool {
+        self.0.values().all(|&num| num >= 0)
+    }
+
+    pub fn positive(&self) -> Qty<T> {
+        Qty(self
+            .0
+            .iter()
+            .filter(|(_, &num)| num > 0)
+            .map(|(&resource, &num)| (resource, num.unsigned_abs()))
+            .collect())
+    }
+
+    pub fn negative(&self) -> Qty<T> {
+        Qty(self
+            .0
+            .iter()
+            .filter(|(_, &num)| num < 0)
+            .map(|(&resource, &num)| (resource, num.unsigned_abs()))
+            .collect())
+    }
+
+    pub fn apply_to(&self, qty: &mut Qty<T>) -> Result<(), Missing<T>> {
+        qty.try_pay(&self.negative())?;
+        *qty += self.positive();
+        Ok(())
+    }
+}
+
+impl<T: Hash + Eq + Copy> From<Qty<T>> for IQty<T> {
+    fn from(qty: Qty<T>) -> Self {
+        IQty(
+            qty.0
+                .into_iter()
+                .map(|(resource, num)| (resource, i64::try_from(num).unwrap_or(i64::MAX)))
+                .collect(),
+        )
+    }
+}
+
+impl<T: Hash + Eq + Copy> TryFrom<IQty<T>> for Qty<T> {
+    type Error = Missing<T>;
+
+    fn try_from(iqty: IQty<T>) -> Result<Self, Self::Error> {
+        if iqty.is_non_negative() {
+            Ok(iqty.positive())
+        } else {
+            Err(Missing(iqty.negative()))
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Add for IQty<T> {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl<T: Hash + Eq + Copy> AddAssign for IQty<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        for (resource, num) in rhs.0 {
+            let entry = self.0.entry(resource).or_default();
+            *entry = entry.saturating_add(num);
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Sub for IQty<T> {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl<T: Hash + Eq + Copy> SubAssign for IQty<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        for (resource, num) in rhs.0 {
+            let entry = self.0.entry(resource).or_default();
+            *entry = entry.saturating_sub(num);
+        }
+    }
+}
+
+impl<T: Hash + Eq + Copy> Neg for IQty<T> {
+    type Output = Self;
+
+    fn neg(mut self) -> Self::Output {
+        for num in self.0.values_mut() {
+            *num = num.saturating_neg();
+        }
+        self
+    }
+}
+
+impl<T: Hash + Eq + Copy> Mul<i64> for IQty<T> {
+    type Output = Self;
+
+    fn mul(mut self, rhs: i64) -> Self::Output {
+        for num in self.0.values_mut() {
+            *num = num.saturating_mul(rhs);
+        }
+        self
+    }
+}
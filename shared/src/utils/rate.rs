@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::{hash::Hash, time::Duration};
+
+use super::{custom_map::CustomMap, qty::Qty};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Rate<T: Hash + Eq> {
+    amounts: Qty<T>,
+    period: u64,
+    carry: CustomMap<T, u64>,
+}
+
+impl<T: Hash + Eq + Copy> Rate<T> {
+    pub fn new(amounts: Qty<T>, period: u64) -> Self {
+        Rate {
+            amounts,
+            period: period.max(1),
+            carry: CustomMap::new(),
+        }
+    }
+
+    pub fn per_tick(amounts: Qty<T>) -> Self {
+        Self::new(amounts, 1)
+    }
+
+    // The period is rounded to the nearest whole number of ticks.
+    pub fn per_duration(amounts: Qty<T>, duration: Duration, duration_per_tick: Duration) -> Self {
+        let tick = duration_per_tick.as_nanos().max(1);
+        let period = (duration.as_nanos() + tick / 2) / tick;
+        Self::new(amounts, period.try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn amounts(&self) -> &Qty<T> {
+        &self.amounts
+    }
+
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    pub fn set_amounts(&mut self, amounts: Qty<T>) {
+        self.amounts = amounts;
+        self.carry
+            .retain(|resource, _| self.amounts.get(resource) > 0);
+    }
+
+    pub fn produced(&self, ticks: u64) -> Qty<T> {
+        let mut produced = Qty::default();
+        for (resource, num) in self.amounts.iter() {
+            let total = num as u128 * ticks as u128 + self.carry_of(&resource) as u128;
+            produced.add(resource, self.whole(total));
+        }
+        produced
+    }
+
+    pub fn apply(&mut self, qty: &mut Qty<T>, ticks: u64) {
+        for (resource, num) in self.amounts.iter() {
+            let total = num as u128 * ticks as u128 + self.carry_of(&resource) as u128;
+            qty.add(resource, self.whole(total));
+            let carry = (total % self.period as u128) as u64;
+            if carry > 0 {
+                self.carry.insert(resource, carry);
+            } else {
+                self.carry.swap_remove(&resource);
+            }
+        }
+    }
+
+    fn carry_of(&self, resource: &T) -> u64 {
+        self.carry.get(resource).copied().unwrap_or_default()
+    }
+
+    fn whole(&self, total: u128) -> u64 {
+        (total / self.period as u128).try_into().unwrap_or(u64::MAX)
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+use super::{
+    custom_map::{CustomMap, CustomSet},
+    entity_set::{EntityRef, EntitySet},
+};
+
+// The accessors take the entity sets and skip removed entities, so refs
+// never dangle even if an entity was removed without `despawn_parent` or
+// `despawn_child`. `retain_alive` frees what the removed entities left behind.
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+pub struct Relation<P: Hash, C: Hash> {
+    children: CustomMap<EntityRef<P>, CustomSet<EntityRef<C>>>,
+    parents: CustomMap<EntityRef<C>, EntityRef<P>>,
+}
+
+impl<P: Hash, C: Hash> Default for Relation<P, C> {
+    fn default() -> Self {
+        Self {
+            children: CustomMap::new(),
+            parents: CustomMap::new(),
+        }
+    }
+}
+
+impl<P: Hash, C: Hash> Relation<P, C> {
+    pub fn link(&mut self, parent: EntityRef<P>, child: EntityRef<C>) {
+        self.unlink(&child);
+        self.children.get_or_insert_default(parent).insert(child);
+        self.parents.insert(child, parent);
+    }
+
+    pub fn unlink(&mut self, child: &EntityRef<C>) -> Option<EntityRef<P>> {
+        let parent = self.parents.swap_remove(child)?;
+        if let Some(children) = self.children.get_mut(&parent) {
+            children.swap_remove(child);
+            if children.is_empty() {
+                self.children.swap_remove(&parent);
+            }
+        }
+        Some(parent)
+    }
+
+    pub fn parent_of<G: Eq + Hash>(
+        &self,
+        parents: &EntitySet<P, G>,
+        child: &EntityRef<C>,
+    ) -> Option<EntityRef<P>> {
+        self.parents
+            .get(child)
+            .filter(|parent| parents.get(parent).is_some())
+            .copied()
+    }
+
+    pub fn children_of<'a, G: Eq + Hash>(
+        &'a self,
+        children: &'a EntitySet<C, G>,
+        parent: &EntityRef<P>,
+    ) -> impl Iterator<Item = EntityRef<C>> + 'a {
+        self.children
+            .get(parent)
+            .into_iter()
+            .flatten()
+            .filter(|child| children.get(child).is_some())
+            .copied()
+    }
+
+    pub fn remove_parent(&mut self, parent: &EntityRef<P>) -> CustomSet<EntityRef<C>> {
+        let children = self.children.swap_remove(parent).unwrap_or_default();
+        for child in &children {
+            self.parents.swap_remove(child);
+        }
+        children
+    }
+
+    pub fn despawn_parent(
+        &mut self,
+        parents: &mut EntitySet<P>,
+        children: &mut EntitySet<C>,
+        parent: &EntityRef<P>,
+    ) -> Option<P> {
+        for child in self.remove_parent(parent) {
+            children.remove(&child);
+        }
+        parents.remove(parent)
+    }
+
+    pub fn despawn_child(
+        &mut self,
+        children: &mut EntitySet<C>,
+        child: &EntityRef<C>,
+    ) -> Option<C> {
+        self.unlink(child);
+        children.remove(child)
+    }
+
+    pub fn retain_alive(&mut self, parents: &EntitySet<P>, children: &EntitySet<C>) {
+        self.parents
+            .retain(|child, parent| children.get(child).is_some() && parents.get(parent).is_some());
+        let linked = &self.parents;
+        self.children.retain(|_, children| {
+            children.retain(|child| linked.contains_key(child));
+            !children.is_empty()
+        });
+    }
+}
+
+// Like with `Relation`, the accessors skip removed entities.
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+pub struct Links<A: Hash, B: Hash> {
+    forward: CustomMap<EntityRef<A>, CustomSet<EntityRef<B>>>,
+    backward: CustomMap<EntityRef<B>, CustomSet<EntityRef<A>>>,
+}
+
+impl<A: Hash, B: Hash> Default for Links<A, B> {
+    fn default() -> Self {
+        Self {
+            forward: CustomMap::new(),
+            backward: CustomMap::new(),
+        }
+    }
+}
+
+impl<A: Hash, B: Hash> Links<A, B> {
+    pub fn link(&mut self, a: EntityRef<A>, b: EntityRef<B>) {
+        self.forward.get_or_insert_default(a).insert(b);
+        self.backward.get_or_insert_default(b).insert(a);
+    }
+
+    pub fn unlink(&mut self, a: &EntityRef<A>, b: &EntityRef<B>) -> bool {
+        let removed = remove_from(&mut self.forward, a, b);
+        remove_from(&mut self.backward, b, a);
+        removed
+    }
+
+    pub fn is_linked(&self, a: &EntityRef<A>, b: &EntityRef<B>) -> bool {
+        self.forward
+            .get(a)
+            .map(|linked| linked.contains(b))
+            .unwrap_or_default()
+    }
+
+    pub fn linked_to_a<'a, G: Eq + Hash>(
+        &'a self,
+        b_entities: &'a EntitySet<B, G>,
+        a: &EntityRef<A>,
+    ) -> impl Iterator<Item = EntityRef<B>> + 'a {
+        self.forward
+            .get(a)
+            .into_iter()
+            .flatten()
+            .filter(|b| b_entities.get(b).is_some())
+            .copied()
+    }
+
+    pub fn linked_to_b<'a, G: Eq + Hash>(
+        &'a self,
+        a_entities: &'a EntitySet<A, G>,
+        b: &EntityRef<B>,
+    ) -> impl Iterator<Item = EntityRef<A>> + 'a {
+        self.backward
+            .get(b)
+            .into_iter()
+            .flatten()
+            .filter(|a| a_entities.get(a).is_some())
+            .copied()
+    }
+
+    pub fn remove_a(&mut self, a: &EntityRef<A>) {
+        for b in self.forward.swap_remove(a).unwrap_or_default() {
+            remove_from(&mut self.backward, &b, a);
+        }
+    }
+
+    pub fn remove_b(&mut self, b: &EntityRef<B>) {
+        for a in self.backward.swap_remove(b).unwrap_or_default() {
+            remove_from(&mut self.forward, &a, b);
+        }
+    }
+
+    // Unlike with `Relation`, the linked entities stay alive.
+    pub fn despawn_a(&mut self, entities: &mut EntitySet<A>, a: &EntityRef<A>) -> Option<A> {
+        self.remove_a(a);
+        entities.remove(a)
+    }
+
+    pub fn despawn_b(&mut self, entities: &mut EntitySet<B>, b: &EntityRef<B>) -> Option<B> {
+        self.remove_b(b);
+        entities.remove(b)
+    }
+
+    pub fn retain_alive(&mut self, a_entities: &EntitySet<A>, b_entities: &EntitySet<B>) {
+        self.forward.retain(|a, linked| {
+            linked.retain(|b| b_entities.get(b).is_some());
+            a_entities.get(a).is_some() && !linked.is_empty()
+        });
+        self.backward.retain(|b, linked| {
+            linked.retain(|a| a_entities.get(a).is_some());
+            b_entities.get(b).is_some() && !linked.is_empty()
+        });
+    }
+}
+
+fn remove_from<K: Hash, V: Hash>(
+    map: &mut CustomMap<EntityRef<K>, CustomSet<EntityRef<V>>>,
+    key: &EntityRef<K>,
+    value: &EntityRef<V>,
+) -> bool {
+    let Some(values) = map.get_mut(key) else {
+        return false;
+    };
+    let removed = values.swap_remove(value);
+    if values.is_empty() {
+        map.swap_remove(key);
+    }
+    removed
+}
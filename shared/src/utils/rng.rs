@@ -0,0 +1,46 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sha2::{Digest, Sha256};
+
+use crate::Seed;
+
+pub trait Fork: Rng {
+    fn fork(&self, label: &str) -> ChaCha8Rng;
+
+    fn fork_indexed(&self, label: &str, index: u64) -> ChaCha8Rng;
+}
+
+impl Fork for ChaCha8Rng {
+    fn fork(&self, label: &str) -> ChaCha8Rng {
+        ChaCha8Rng::from_seed(derive_seed(
+            &self.get_seed(),
+            self.get_stream(),
+            label,
+            None,
+        ))
+    }
+
+    fn fork_indexed(&self, label: &str, index: u64) -> ChaCha8Rng {
+        ChaCha8Rng::from_seed(derive_seed(
+            &self.get_seed(),
+            self.get_stream(),
+            label,
+            Some(index),
+        ))
+    }
+}
+
+// Forks only depend on the seed of the parent and not on how many values were
+// drawn from it, so new draws in one subsystem don't shift the others.
+pub fn derive_seed(seed: &Seed, stream: u64, label: &str, index: Option<u64>) -> Seed {
+    let mut hasher = Sha256::new();
+    hasher.update(seed);
+    hasher.update(stream.to_le_bytes());
+    hasher.update((label.len() as u64).to_le_bytes());
+    hasher.update(label.as_bytes());
+    if let Some(index) = index {
+        hasher.update(index.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+use super::{
+    custom_map::{CustomMap, CustomSet},
+    entity_set::EntityRef,
+};
+
+pub type Position = (i64, i64);
+
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+pub struct SpatialGrid<T: Hash> {
+    cell_size: i64,
+    cells: CustomMap<Position, CustomSet<EntityRef<T>>>,
+    positions: CustomMap<EntityRef<T>, Position>,
+}
+
+impl<T: Hash> SpatialGrid<T> {
+    pub fn new(cell_size: i64) -> Self {
+        SpatialGrid {
+            cell_size: cell_size.max(1),
+            cells: CustomMap::new(),
+            positions: CustomMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, entity_ref: &EntityRef<T>) -> Option<Position> {
+        self.positions.get(entity_ref).copied()
+    }
+
+    pub fn insert(&mut self, entity_ref: EntityRef<T>, position: Position) -> Option<Position> {
+        let old_position = self.remove(&entity_ref);
+        self.cells
+            .get_or_insert_default(self.cell_of(position))
+            .insert(entity_ref);
+        self.positions.insert(entity_ref, position);
+        old_position
+    }
+
+    pub fn move_to(&mut self, entity_ref: &EntityRef<T>, position: Position) -> bool {
+        let Some(old_position) = self.positions.get_mut(entity_ref) else {
+            return false;
+        };
+        let old_cell = cell_of(self.cell_size, *old_position);
+        *old_position = position;
+
+        let new_cell = self.cell_of(position);
+        if old_cell != new_cell {
+            self.remove_from_cell(old_cell, entity_ref);
+            self.cells
+                .get_or_insert_default(new_cell)
+                .insert(*entity_ref);
+        }
+        true
+    }
+
+    pub fn remove(&mut self, entity_ref: &EntityRef<T>) -> Option<Position> {
+        let position = self.positions.swap_remove(entity_ref)?;
+        self.remove_from_cell(self.cell_of(position), entity_ref);
+        Some(position)
+    }
+
+    pub fn query_rect(
+        &self,
+        (min_x, min_y): Position,
+        (max_x, max_y): Position,
+    ) -> impl Iterator<Item = (EntityRef<T>, Position)> + '_ {
+        let (min_cell_x, min_cell_y) = self.cell_of((min_x, min_y));
+        let (max_cell_x, max_cell_y) = self.cell_of((max_x, max_y));
+        (min_cell_x..=max_cell_x)
+            .flat_map(move |cell_x| (min_cell_y..=max_cell_y).map(move |cell_y| (cell_x, cell_y)))
+            .flat_map(move |cell| self.cells.get(&cell).into_iter().flatten())
+            .map(move |entity_ref| (*entity_ref, self.positions[entity_ref]))
+            .filter(move |(_, (x, y))| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+    }
+
+    pub fn query_radius(
+        &self,
+        (center_x, center_y): Position,
+        radius: i64,
+    ) -> impl Iterator<Item = (EntityRef<T>, Position)> + '_ {
+        let radius_squared = radius as i128 * radius as i128;
+        self.query_rect(
+            (center_x - radius, center_y - radius),
+            (center_x + radius, center_y + radius),
+        )
+        .filter(move |(_, (x, y))| {
+            let dx = (x - center_x) as i128;
+            let dy = (y - center_y) as i128;
+            dx * dx + dy * dy <= radius_squared
+        })
+    }
+
+    fn cell_of(&self, position: Position) -> Position {
+        cell_of(self.cell_size, position)
+    }
+
+    fn remove_from_cell(&mut self, cell: Position, entity_ref: &EntityRef<T>) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.swap_remove(entity_ref);
+            if entities.is_empty() {
+                self.cells.swap_remove(&cell);
+            }
+        }
+    }
+}
+
+fn cell_of(cell_size: i64, (x, y): Position) -> Position {
+    (x.div_euclid(cell_size), y.div_euclid(cell_size))
+}
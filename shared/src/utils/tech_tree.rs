@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Debug},
+    hash::Hash,
+};
+
+use super::{
+    custom_map::{CustomMap, CustomSet},
+    qty::{Missing, Qty},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TechNode<N, R: Hash + Eq> {
+    pub prerequisites: Vec<N>,
+    pub cost: Qty<R>,
+}
+
+// The technologies of a game with their prerequisites and costs. It's usually
+// the same for all players and built once, while every player has their own
+// `Unlocks`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TechTree<N: Hash + Eq, R: Hash + Eq> {
+    nodes: CustomMap<N, TechNode<N, R>>,
+}
+
+impl<N: Hash + Eq, R: Hash + Eq> Default for TechTree<N, R> {
+    fn default() -> Self {
+        TechTree {
+            nodes: CustomMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Unlocks<N: Hash + Eq>(CustomSet<N>);
+
+impl<N: Hash + Eq> Default for Unlocks<N> {
+    fn default() -> Self {
+        Unlocks(CustomSet::new())
+    }
+}
+
+impl<N: Hash + Eq + Copy> Unlocks<N> {
+    pub fn is_unlocked(&self, node: &N) -> bool {
+        self.0.contains(node)
+    }
+
+    // In the order they were unlocked.
+    pub fn iter(&self) -> impl Iterator<Item = N> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // Unlocks the node without checking its prerequisites or paying for it,
+    // e.g. for the technologies players start with.
+    pub fn grant(&mut self, node: N) {
+        self.0.insert(node);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TechTreeError<N> {
+    UnknownPrerequisite { node: N, prerequisite: N },
+    // The nodes that can never be unlocked, since they're part of a cycle of
+    // prerequisites or depend on one.
+    Cycle(Vec<N>),
+}
+
+impl<N: Debug> fmt::Display for TechTreeError<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TechTreeError::UnknownPrerequisite { node, prerequisite } => {
+                write!(f, "{node:?} requires unknown {prerequisite:?}")
+            }
+            TechTreeError::Cycle(nodes) => write!(f, "cyclic prerequisites of {nodes:?}"),
+        }
+    }
+}
+
+impl<N: Debug> std::error::Error for TechTreeError<N> {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnlockError<N, R: Hash + Eq> {
+    Unknown(N),
+    AlreadyUnlocked(N),
+    MissingPrerequisites(Vec<N>),
+    Missing(Missing<R>),
+}
+
+impl<N: Debug, R: Hash + Eq + Debug> fmt::Display for UnlockError<N, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnlockError::Unknown(node) => write!(f, "unknown technology {node:?}"),
+            UnlockError::AlreadyUnlocked(node) => write!(f, "{node:?} is already unlocked"),
+            UnlockError::MissingPrerequisites(nodes) => write!(f, "requires {nodes:?} first"),
+            UnlockError::Missing(missing) => write!(f, "{missing}"),
+        }
+    }
+}
+
+impl<N: Debug, R: Hash + Eq + Debug> std::error::Error for UnlockError<N, R> {}
+
+impl<N: Hash + Eq + Copy, R: Hash + Eq + Copy> TechTree<N, R> {
+    pub fn with(mut self, node: N, prerequisites: Vec<N>, cost: Qty<R>) -> Self {
+        self.insert(node, prerequisites, cost);
+        self
+    }
+
+    pub fn insert(&mut self, node: N, prerequisites: Vec<N>, cost: Qty<R>) {
+        self.nodes.insert(
+            node,
+            TechNode {
+                prerequisites,
+                cost,
+            },
+        );
+    }
+
+    pub fn get(&self, node: &N) -> Option<&TechNode<N, R>> {
+        self.nodes.get(node)
+    }
+
+    // In the order they were inserted.
+    pub fn nodes(&self) -> impl Iterator<Item = (N, &TechNode<N, R>)> {
+        self.nodes.iter().map(|(node, tech)| (*node, tech))
+    }
+
+    // Checks that all prerequisites exist and every node can be unlocked
+    // eventually, e.g. once when the state is created.
+    pub fn validate(&self) -> Result<(), TechTreeError<N>> {
+        for (node, tech) in &self.nodes {
+            if let Some(prerequisite) = tech
+                .prerequisites
+                .iter()
+                .find(|prerequisite| !self.nodes.contains_key(*prerequisite))
+            {
+                return Err(TechTreeError::UnknownPrerequisite {
+                    node: *node,
+                    prerequisite: *prerequisite,
+                });
+            }
+        }
+
+        let mut reachable = Unlocks::default();
+        loop {
+            let before = reachable.len();
+            for (node, tech) in &self.nodes {
+                if tech
+                    .prerequisites
+                    .iter()
+                    .all(|prerequisite| reachable.is_unlocked(prerequisite))
+                {
+                    reachable.grant(*node);
+                }
+            }
+            if reachable.len() == before {
+                break;
+            }
+        }
+        let unreachable: Vec<N> = self
+            .nodes
+            .keys()
+            .filter(|node| !reachable.is_unlocked(node))
+            .copied()
+            .collect();
+        if unreachable.is_empty() {
+            Ok(())
+        } else {
+            Err(TechTreeError::Cycle(unreachable))
+        }
+    }
+
+    // Whether the player could unlock the node right now, e.g. in
+    // `State::validate`.
+    pub fn check(
+        &self,
+        unlocks: &Unlocks<N>,
+        resources: &Qty<R>,
+        node: N,
+    ) -> Result<(), UnlockError<N, R>> {
+        let tech = self.nodes.get(&node).ok_or(UnlockError::Unknown(node))?;
+        if unlocks.is_unlocked(&node) {
+            return Err(UnlockError::AlreadyUnlocked(node));
+        }
+        let missing: Vec<N> = tech
+            .prerequisites
+            .iter()
+            .filter(|prerequisite| !unlocks.is_unlocked(prerequisite))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Err(UnlockError::MissingPrerequisites(missing));
+        }
+        let shortfall = resources.shortfall(&tech.cost);
+        if !shortfall.is_empty() {
+            return Err(UnlockError::Missing(Missing(shortfall)));
+        }
+        Ok(())
+    }
+
+    // Pays for the node and unlocks it. Nothing changes if it fails.
+    pub fn unlock(
+        &self,
+        unlocks: &mut Unlocks<N>,
+        resources: &mut Qty<R>,
+        node: N,
+    ) -> Result<(), UnlockError<N, R>> {
+        self.check(unlocks, resources, node)?;
+        resources
+            .try_pay(&self.nodes[&node].cost)
+            .map_err(UnlockError::Missing)?;
+        unlocks.grant(node);
+        Ok(())
+    }
+
+    // The nodes whose prerequisites are unlocked but that aren't unlocked
+    // themselves, whether they're affordable or not.
+    pub fn available<'a>(&'a self, unlocks: &'a Unlocks<N>) -> impl Iterator<Item = N> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |(node, tech)| {
+                !unlocks.is_unlocked(node)
+                    && tech
+                        .prerequisites
+                        .iter()
+                        .all(|prerequisite| unlocks.is_unlocked(prerequisite))
+            })
+            .map(|(node, _)| *node)
+    }
+}